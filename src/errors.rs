@@ -162,3 +162,71 @@ impl fmt::Display for InvalidTarget {
         write!(f, "Target is invalid")
     }
 }
+
+// Errors returned while loading a world file
+#[derive(Debug)]
+pub enum WorldLoadError {
+    Io(std::io::Error),
+    Header(toml::de::Error),
+    EmptyGrid,
+    InvalidTile { line: usize, column: usize, character: char },
+    OutOfBounds { what: String, x: i32, y: i32 },
+    UnknownRegion(String),
+    DuplicateRegion(String),
+    InvalidSpawn(String),
+    InvalidOverride(String),
+    TooManySpawns { kind: String, fixed: usize, count: usize },
+    Unreachable(usize),
+    UnknownMonsterType(String),
+    InvalidMonsterType(String),
+    InvalidTreasureConfig(String),
+}
+
+impl Error for WorldLoadError {}
+
+impl fmt::Display for WorldLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldLoadError::Io(error) => write!(f, "Could not read world file: {}", error),
+            WorldLoadError::Header(error) => write!(f, "Invalid world header: {}", error),
+            WorldLoadError::EmptyGrid => write!(f, "World has no tiles"),
+            WorldLoadError::InvalidTile { line, column, character } => write!(
+                f,
+                "Invalid tile character '{}' at line {}, column {}",
+                character, line, column
+            ),
+            WorldLoadError::OutOfBounds { what, x, y } => {
+                write!(f, "{} at ({}, {}) is outside the world", what, x, y)
+            }
+            WorldLoadError::UnknownRegion(name) => write!(f, "Unknown region '{}'", name),
+            WorldLoadError::DuplicateRegion(name) => write!(f, "Region '{}' is defined twice", name),
+            WorldLoadError::InvalidSpawn(reason) => write!(f, "Invalid spawn: {}", reason),
+            WorldLoadError::InvalidOverride(reason) => write!(f, "Invalid tile override: {}", reason),
+            WorldLoadError::TooManySpawns { kind, fixed, count } => write!(
+                f,
+                "{} fixed {} spawns but the {} count is only {}",
+                fixed, kind, kind, count
+            ),
+            WorldLoadError::Unreachable(count) => {
+                write!(f, "{} points cannot be reached from the villages", count)
+            }
+            WorldLoadError::UnknownMonsterType(name) => write!(f, "Unknown monster type '{}'", name),
+            WorldLoadError::InvalidMonsterType(reason) => write!(f, "Invalid monster type: {}", reason),
+            WorldLoadError::InvalidTreasureConfig(reason) => {
+                write!(f, "Invalid treasure settings: {}", reason)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for WorldLoadError {
+    fn from(error: std::io::Error) -> Self {
+        WorldLoadError::Io(error)
+    }
+}
+
+impl From<toml::de::Error> for WorldLoadError {
+    fn from(error: toml::de::Error) -> Self {
+        WorldLoadError::Header(error)
+    }
+}
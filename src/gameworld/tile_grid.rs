@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::gameworld::position::Position;
+use crate::gameworld::tile::Tile;
+
+/// Tiles stored row by row in one vector. Cloning only copies the `Arc`, the cells are
+/// copied on the first write to a shared grid, so simulation copies are cheap and reads never lock.
+/// Rows can be shorter than the widest row, the missing cells are `None`.
+#[derive(Clone, Default)]
+pub struct TileGrid {
+    origin_x: i32,
+    origin_y: i32,
+    width: usize,
+    height: usize,
+    cells: Arc<Vec<Option<Tile>>>,
+}
+
+impl TileGrid {
+    /// Builds a grid whose first cell is at (`origin_x`, `origin_y`).
+    pub fn new(
+        origin_x: i32,
+        origin_y: i32,
+        width: usize,
+        height: usize,
+        cells: Vec<Option<Tile>>,
+    ) -> Self {
+        assert_eq!(
+            cells.len(),
+            width * height,
+            "grid cells do not match its size"
+        );
+        TileGrid {
+            origin_x,
+            origin_y,
+            width,
+            height,
+            cells: Arc::new(cells),
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let column = x - self.origin_x;
+        let row = y - self.origin_y;
+        if column < 0 || row < 0 || column as usize >= self.width || row as usize >= self.height {
+            return None;
+        }
+        Some(row as usize * self.width + column as usize)
+    }
+
+    fn position(&self, index: usize) -> Position {
+        Position::new(
+            self.origin_x + (index % self.width) as i32,
+            self.origin_y + (index / self.width) as i32,
+        )
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<&Tile> {
+        self.index(x, y)
+            .and_then(|index| self.cells[index].as_ref())
+    }
+
+    /// Mutable access to a tile. A grid shared with clones is copied first.
+    pub fn get_mut(&mut self, x: i32, y: i32) -> Option<&mut Tile> {
+        let index = self.index(x, y)?;
+        Arc::make_mut(&mut self.cells)[index].as_mut()
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_some()
+    }
+
+    /// Every tile with its position, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (Position, &Tile)> + '_ {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, cell)| cell.as_ref().map(|tile| (self.position(index), tile)))
+    }
+
+    /// Every position that holds a tile, row by row.
+    pub fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        self.iter().map(|(position, _)| position)
+    }
+
+    /// Number of tiles, not counting missing cells.
+    pub fn len(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+}
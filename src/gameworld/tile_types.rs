@@ -24,6 +24,33 @@ impl TileType {
             TileType::Mine => "Mine",
         }
     }
+    /// Parses the character used for a tile in world files.
+    pub fn from_char(c: char) -> Option<TileType> {
+        match c {
+            'm' => Some(TileType::Mountain),
+            'l' => Some(TileType::Lake),
+            'v' => Some(TileType::Village),
+            'd' => Some(TileType::Dungeon),
+            'f' => Some(TileType::Forest),
+            'F' => Some(TileType::Farm),
+            'M' => Some(TileType::Mine),
+            _ => None,
+        }
+    }
+
+    /// The character used for a tile in world files.
+    pub fn to_char(&self) -> char {
+        match self {
+            TileType::Mountain => 'm',
+            TileType::Lake => 'l',
+            TileType::Village => 'v',
+            TileType::Dungeon => 'd',
+            TileType::Forest => 'f',
+            TileType::Farm => 'F',
+            TileType::Mine => 'M',
+        }
+    }
+
     pub fn get_travel_weight(&self) -> f32 {
         match self {
             TileType::Forest => 1.25,
@@ -35,5 +62,27 @@ impl TileType {
             TileType::Mine => 1.0,
         }
     }
+
+    /// Every tile type.
+    pub fn all() -> [TileType; 7] {
+        [
+            TileType::Forest,
+            TileType::Mountain,
+            TileType::Lake,
+            TileType::Village,
+            TileType::Dungeon,
+            TileType::Farm,
+            TileType::Mine,
+        ]
+    }
+
+    /// Lowest weight of a passable tile, no step can be cheaper than this.
+    pub fn min_travel_weight() -> f32 {
+        TileType::all()
+            .iter()
+            .map(|tile_type| tile_type.get_travel_weight())
+            .filter(|weight| *weight > 0.0)
+            .fold(f32::INFINITY, f32::min)
+    }
     
 }
\ No newline at end of file
@@ -2,28 +2,35 @@
 use bevy::prelude::Resource;
 
 // Standard library imports
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 // Internal crate imports
+use crate::errors::WorldLoadError;
 use crate::gameworld::position::Position;
 use crate::gameworld::tile::Tile;
+use crate::gameworld::tile_grid::TileGrid;
 use crate::gameworld::tile_types::TileType;
+use crate::system::pathfinding::movement::MovementModel;
+use crate::system::pathfinding::path_service::PathService;
 
 #[derive(Clone, Resource)]
 pub struct GameWorld {
-    pub tiles: HashMap<Position, Arc<Mutex<Tile>>>,
+    pub tiles: TileGrid,
     pub width_mind: i32,
     pub height_min: i32,
     pub width_max: i32,
     pub height_max: i32,
+    /// Neighbours and step costs used by every path search on this world.
+    pub movement: MovementModel,
+    /// Distance fields shared by clones of this world until one of them changes a tile.
+    pub path_service: Arc<PathService>,
 }
 
 /// Standalone function to initialize the game world.
-pub fn initialize(text_file_name: &str) -> io::Result<GameWorld> {
+pub fn initialize(text_file_name: &str) -> Result<GameWorld, WorldLoadError> {
     GameWorld::initialize(text_file_name)
 }
 
@@ -42,89 +49,81 @@ impl GameWorld {
 
     // Construct an empty world
     pub fn new() -> Self {
-        let tiles: HashMap<Position, Arc<Mutex<Tile>>> = HashMap::new();
-
         GameWorld {
-            tiles,
+            tiles: TileGrid::default(),
             width_mind: i32::MAX,
             height_min: i32::MAX,
             width_max: i32::MIN,
             height_max: i32::MIN,
+            movement: MovementModel::default(),
+            path_service: Arc::new(PathService::new()),
         }
     }
 
     /// Creates a world using a vector of strings as the map data.
+    /// Panics on an invalid tile, use `try_create_world` for map data read from a file.
     pub fn create_world(map_data: Vec<String>) -> Self {
+        Self::try_create_world(&map_data).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Creates a world from rows of tile characters, returning an error for unknown characters
+    /// or a grid without tiles.
+    pub fn try_create_world(map_data: &[String]) -> Result<Self, WorldLoadError> {
         let mut world = GameWorld::new();
 
-        // Initialize min and max values
-        let mut min_x = i32::MAX;
-        let mut min_y = i32::MAX;
-        let mut max_x = i32::MIN;
-        let mut max_y = i32::MIN;
+        // Rows can have different lengths, the grid is as wide as the longest one
+        let height = map_data.len();
+        let width = map_data
+            .iter()
+            .map(|row_str| row_str.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut cells: Vec<Option<Tile>> = vec![None; width * height];
 
         for (y, row_str) in map_data.iter().enumerate() {
             for (x, c) in row_str.chars().enumerate() {
-                let tile_type = match c {
-                    'm' => TileType::Mountain,
-                    'l' => TileType::Lake,
-                    'v' => TileType::Village,
-                    'd' => TileType::Dungeon,
-                    'f' => TileType::Forest,
-                    'F' => TileType::Farm,
-                    'M' => TileType::Mine,
-                    _ => panic!("Invalid tile character: {}", c),
-                };
-
-                let tile = Arc::new(Mutex::new(Tile::new(tile_type)));
-
-                // Add the tile to the tiles HashMap with its position
-                let position = Position {
-                    x: x as i32,
-                    y: y as i32,
-                };
-                world.tiles.insert(position, tile);
-
-                // Update min and max values
-                if position.x < min_x {
-                    min_x = position.x;
-                }
-                if position.y < min_y {
-                    min_y = position.y;
-                }
-                if position.x > max_x {
-                    max_x = position.x;
-                }
-                if position.y > max_y {
-                    max_y = position.y;
-                }
+                let tile_type = TileType::from_char(c).ok_or(WorldLoadError::InvalidTile {
+                    line: y + 1,
+                    column: x + 1,
+                    character: c,
+                })?;
+                cells[y * width + x] = Some(Tile::new(tile_type));
             }
         }
 
+        if cells.iter().all(|cell| cell.is_none()) {
+            return Err(WorldLoadError::EmptyGrid);
+        }
+
         // Store the min and max values in the world
-        world.width_mind = min_x;
-        world.height_min = min_y;
-        world.width_max = max_x;
-        world.height_max = max_y;
+        world.width_mind = 0;
+        world.height_min = 0;
+        world.width_max = width as i32 - 1;
+        world.height_max = height as i32 - 1;
+        world.tiles = TileGrid::new(0, 0, width, height, cells);
 
-        world
+        Ok(world)
     }
 
-    pub fn initialize(text_file_name: &str) -> io::Result<Self> {
+    pub fn initialize(text_file_name: &str) -> Result<Self, WorldLoadError> {
         let map_data = Self::read_world(text_file_name)?;
-        Ok(Self::create_world(map_data))
+        Self::try_create_world(&map_data)
     }
 
     // Function to check if the position (x, y) is within the grid's bounds
     pub fn is_valid_position(&self, x: i32, y: i32) -> bool {
-        let position = Position { x, y };
-        self.tiles.contains_key(&position)
+        self.tiles.contains(x, y)
     }
 
-    pub fn get_tiles(&self) -> &HashMap<Position, Arc<Mutex<Tile>>> {
+    pub fn get_tiles(&self) -> &TileGrid {
         &self.tiles
     }
 
+    /// Every tile with its position, row by row.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (Position, &Tile)> + '_ {
+        self.tiles.iter()
+    }
+
     pub fn get_width_mind(&self) -> i32 {
         self.width_mind
     }
@@ -143,54 +142,44 @@ impl GameWorld {
 
     // Creates a copy of the world
     pub fn copy(&self) -> Self {
-        // Deep copy the tiles HashMap
-        let mut new_tiles = HashMap::new();
-        for (position, tile_arc_mutex) in &self.tiles {
-            let tile_mutex = Mutex::new(tile_arc_mutex.lock().unwrap().clone());
-            let arc_tile = Arc::new(tile_mutex);
-            new_tiles.insert(*position, arc_tile);
-        }
-
+        // The grid is shared until one of the worlds writes to it
         GameWorld {
-            tiles: new_tiles,
+            tiles: self.tiles.clone(),
             width_mind: self.width_mind,
             height_min: self.height_min,
             width_max: self.width_max,
             height_max: self.height_max,
+            movement: self.movement,
+            path_service: self.path_service.clone(),
         }
     }
 
     // Function to get the Tile at position (x, y)
-    pub fn get_tile(&self, x: i32, y: i32) -> Option<Arc<Mutex<Tile>>> {
-        let position = Position { x, y };
-        self.tiles.get(&position).cloned()
+    pub fn get_tile(&self, x: i32, y: i32) -> Option<&Tile> {
+        self.tiles.get(x, y)
     }
 
     // Function to get a mutable reference to the Tile at position (x, y)
-    pub fn get_tile_mut(&self, x: i32, y: i32) -> Option<Arc<Mutex<Tile>>> {
-        self.get_tile(x, y)
+    pub fn get_tile_mut(&mut self, x: i32, y: i32) -> Option<&mut Tile> {
+        if !self.is_valid_position(x, y) {
+            return None;
+        }
+        // The caller may change the tile type, so the cached fields no longer apply
+        self.path_service = Arc::new(PathService::new());
+        self.tiles.get_mut(x, y)
     }
 
     // Function to get the TileType at position (x, y)
     pub fn get_tile_type(&self, x: i32, y: i32) -> Option<TileType> {
-        self.get_tile(x, y).map(|tile| {
-            let tile_lock = tile.lock().unwrap();
-            tile_lock.get_tile_type().clone()
-        })
+        self.get_tile(x, y).map(|tile| tile.get_tile_type())
     }
 
     pub fn find_closest_villages(&self) -> Vec<(Position, Position)> {
+        // Row by row, so ties always resolve to the same village
         let village_positions: Vec<Position> = self
-            .tiles
-            .iter()
-            .filter_map(|(position, tile)| {
-                let tile_lock = tile.lock().unwrap();
-                if tile_lock.get_tile_type() == TileType::Village {
-                    Some(*position)
-                } else {
-                    None
-                }
-            })
+            .iter_tiles()
+            .filter(|(_, tile)| tile.get_tile_type() == TileType::Village)
+            .map(|(position, _)| position)
             .collect();
 
         let mut result = Vec::new();
@@ -219,22 +208,45 @@ impl GameWorld {
         result
     }
 
+    /// Closest tile of `tile_type` by travel cost, using the cached distance field.
     pub fn find_closest_tiletype(&self, position: Position, tile_type: TileType) -> Option<Position> {
-        let mut closest_tile = None;
-        let mut min_cost = i32::MAX;
-
-        for (&target_pos, tile) in &self.tiles {
-            let tile_lock = tile.lock().unwrap();
-            if tile_lock.get_tile_type() == tile_type {
-                let cost = (position.x - target_pos.x).abs() + (position.y - target_pos.y).abs();
-                if cost < min_cost {
-                    min_cost = cost;
-                    closest_tile = Some(target_pos);
-                }
-            }
+        self.path_service
+            .distance_field(self, tile_type)
+            .get_nearest(position)
+    }
+
+    /// Closest tile of `tile_type` and the path to it, without the start position.
+    pub fn path_to_closest_tiletype(
+        &self,
+        position: Position,
+        tile_type: TileType,
+    ) -> Option<(Position, Vec<Position>)> {
+        let field = self.path_service.distance_field(self, tile_type);
+        let target = field.get_nearest(position)?;
+        let path = field.path_from(self, position)?;
+        Some((target, path))
+    }
+
+    /// Changes the type of a tile. The world gets a new path service, clones that still
+    /// share the old tiles keep the old cached fields.
+    pub fn set_tile_type(&mut self, x: i32, y: i32, tile_type: TileType) {
+        if let Some(tile) = self.get_tile_mut(x, y) {
+            tile.update_tile_type(tile_type);
         }
+    }
+
+    pub fn get_movement(&self) -> MovementModel {
+        self.movement
+    }
+
+    /// Changes how units move, the cached distance fields were computed with the old model.
+    pub fn set_movement(&mut self, movement: MovementModel) {
+        self.movement = movement;
+        self.path_service = Arc::new(PathService::new());
+    }
 
-        closest_tile
+    pub fn get_path_service(&self) -> &PathService {
+        &self.path_service
     }
 
     // Function to check if the position is within the grid's bounds
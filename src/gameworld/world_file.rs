@@ -0,0 +1,527 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::Resource;
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use serde::Deserialize;
+
+use crate::errors::WorldLoadError;
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::monster::MonsterStats;
+use crate::npcs::treasure::TreasureConfig;
+
+/// Line that ends the TOML header of a `.world` file, the tile grid follows it.
+pub const HEADER_SEPARATOR: &str = "---";
+
+/*
+    A world file is a TOML header followed by the tile grid used by the old `.txt` worlds:
+
+        name = "example"
+
+        [counts]
+        agents = 10
+        monsters = 5
+        treasures = 5
+
+        [treasure]
+        min_value = 50
+        max_value = 150
+        loot_per_turn = 25
+        respawn_delay = 5
+        respawn_chance = 0.2
+
+        [[regions]]
+        name = "north"
+        x = 0
+        y = 0
+        width = 20
+        height = 5
+
+        [[spawns]]
+        kind = "agent"
+        region = "north"
+        count = 2
+
+        [[monster_types]]
+        name = "troll"
+        max_energy = 200
+        damage = 15
+
+        [[spawns]]
+        kind = "monster"
+        x = 4
+        y = 2
+        monster_type = "troll"
+
+        [[overrides]]
+        region = "north"
+        valid_monster_spawn = false
+        ---
+        fffvfff
+        ...
+
+    x is the column and y the row of the grid, starting at 0.
+*/
+
+/// How many NPCs of each kind are spawned. Fixed spawns count towards these totals.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnCounts {
+    pub agents: usize,
+    pub monsters: usize,
+    pub treasures: usize,
+}
+
+impl Default for SpawnCounts {
+    fn default() -> Self {
+        SpawnCounts {
+            agents: 10,
+            monsters: 5,
+            treasures: 5,
+        }
+    }
+}
+
+impl SpawnCounts {
+    pub fn get_count(&self, kind: SpawnKind) -> usize {
+        match kind {
+            SpawnKind::Player => 1,
+            SpawnKind::Agent => self.agents,
+            SpawnKind::Monster => self.monsters,
+            SpawnKind::Treasure => self.treasures,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpawnKind {
+    Player,
+    Agent,
+    Monster,
+    Treasure,
+}
+
+impl SpawnKind {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            SpawnKind::Player => "player",
+            SpawnKind::Agent => "agent",
+            SpawnKind::Monster => "monster",
+            SpawnKind::Treasure => "treasure",
+        }
+    }
+}
+
+/// Places `count` NPCs either on a tile (`x`, `y`) or on random tiles of a named region.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnEntry {
+    pub kind: SpawnKind,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub region: Option<String>,
+    #[serde(default = "default_spawn_count")]
+    pub count: usize,
+    /// Name of one of the `monster_types`, only for monster spawns.
+    pub monster_type: Option<String>,
+}
+
+fn default_spawn_count() -> usize {
+    1
+}
+
+/// Changes the tiles at (`x`, `y`) or in a named region after the grid is read.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TileOverride {
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub region: Option<String>,
+    pub tile: Option<char>,
+    pub valid_monster_spawn: Option<bool>,
+}
+
+/// A named rectangle of the world.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Region {
+    pub fn contains(&self, position: &Position) -> bool {
+        position.x >= self.x
+            && position.x < self.x + self.width
+            && position.y >= self.y
+            && position.y < self.y + self.height
+    }
+
+    /// All positions of the region, row by row.
+    pub fn positions(&self) -> Vec<Position> {
+        let mut positions = Vec::new();
+        for y in self.y..self.y + self.height {
+            for x in self.x..self.x + self.width {
+                positions.push(Position::new(x, y));
+            }
+        }
+        positions
+    }
+}
+
+/// The TOML header of a world file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldHeader {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub counts: SpawnCounts,
+    #[serde(default)]
+    pub regions: Vec<Region>,
+    #[serde(default)]
+    pub spawns: Vec<SpawnEntry>,
+    #[serde(default)]
+    pub overrides: Vec<TileOverride>,
+    #[serde(default)]
+    pub monster_types: Vec<MonsterStats>,
+    #[serde(default)]
+    pub treasure: TreasureConfig,
+}
+
+/// Everything in a world file besides the tiles, used by setup to populate the world.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct WorldDefinition {
+    pub name: String,
+    pub counts: SpawnCounts,
+    pub regions: Vec<Region>,
+    pub spawns: Vec<SpawnEntry>,
+    pub monster_types: Vec<MonsterStats>,
+    pub treasure: TreasureConfig,
+}
+
+impl WorldDefinition {
+    pub fn get_region(&self, name: &str) -> Option<&Region> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    pub fn get_count(&self, kind: SpawnKind) -> usize {
+        self.counts.get_count(kind)
+    }
+
+    /// Number of NPCs of a kind placed by the `spawns` entries.
+    pub fn fixed_count(&self, kind: SpawnKind) -> usize {
+        self.spawns
+            .iter()
+            .filter(|spawn| spawn.kind == kind)
+            .map(|spawn| spawn.count)
+            .sum()
+    }
+
+    /// Stats of a monster type. Monsters without a type use the type named "default",
+    /// or the built-in stats if the world file does not define one.
+    pub fn monster_stats(&self, monster_type: Option<&str>) -> MonsterStats {
+        let name = monster_type.unwrap_or("default");
+        self.monster_types
+            .iter()
+            .find(|stats| stats.name == name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Resolves the `spawns` entries of a kind to positions.
+    /// Region spawns pick random tiles of the region that NPCs can stand on.
+    pub fn spawn_positions(
+        &self,
+        kind: SpawnKind,
+        world: &GameWorld,
+        rng: &mut dyn RngCore,
+    ) -> Vec<Position> {
+        self.resolve_spawns(kind, world, rng)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    /// Resolves the monster spawns to positions and the stats of their monster type.
+    pub fn monster_spawns(
+        &self,
+        world: &GameWorld,
+        rng: &mut dyn RngCore,
+    ) -> Vec<(Position, MonsterStats)> {
+        self.resolve_spawns(SpawnKind::Monster, world, rng)
+            .into_iter()
+            .map(|(position, spawn)| (position, self.monster_stats(spawn.monster_type.as_deref())))
+            .collect()
+    }
+
+    fn resolve_spawns(
+        &self,
+        kind: SpawnKind,
+        world: &GameWorld,
+        rng: &mut dyn RngCore,
+    ) -> Vec<(Position, &SpawnEntry)> {
+        let mut positions = Vec::new();
+        for spawn in self.spawns.iter().filter(|spawn| spawn.kind == kind) {
+            if let (Some(x), Some(y)) = (spawn.x, spawn.y) {
+                positions.extend(std::iter::repeat((Position::new(x, y), spawn)).take(spawn.count));
+                continue;
+            }
+
+            let region = match spawn.region.as_deref().and_then(|name| self.get_region(name)) {
+                Some(region) => region,
+                None => continue,
+            };
+            let candidates: Vec<Position> = region
+                .positions()
+                .into_iter()
+                .filter(|position| {
+                    matches!(
+                        world.get_tile_type(position.x, position.y),
+                        Some(tile_type) if tile_type != TileType::Mountain && tile_type != TileType::Lake
+                    )
+                })
+                .collect();
+            if candidates.is_empty() {
+                eprintln!(
+                    "Region '{}' has no tile a {} can spawn on",
+                    region.name,
+                    kind.to_string()
+                );
+                continue;
+            }
+            for _ in 0..spawn.count {
+                positions.push((*candidates.choose(rng).unwrap(), spawn));
+            }
+        }
+        positions
+    }
+}
+
+/// Loads `./worlds/<name>.world`, or a plain tile grid from `./worlds/<name>.txt` if there is no world file.
+pub fn load_world(name: &str) -> Result<(GameWorld, WorldDefinition), WorldLoadError> {
+    let world_path = format!("./worlds/{}.world", name);
+    let (world, mut definition) = if Path::new(&world_path).exists() {
+        parse_world(&fs::read_to_string(&world_path)?)?
+    } else {
+        let rows = GameWorld::read_world(name)?;
+        build_world(WorldHeader::default(), &rows, 1)?
+    };
+
+    if definition.name.is_empty() {
+        definition.name = name.to_string();
+    }
+    Ok((world, definition))
+}
+
+/// Parses the contents of a world file. A file without a header separator is read as a plain grid.
+pub fn parse_world(contents: &str) -> Result<(GameWorld, WorldDefinition), WorldLoadError> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let (header, grid_start) = match lines.iter().position(|line| line.trim() == HEADER_SEPARATOR) {
+        Some(index) => (toml::from_str::<WorldHeader>(&lines[..index].join("\n"))?, index + 1),
+        None => (WorldHeader::default(), 0),
+    };
+
+    let mut rows: Vec<String> = lines[grid_start..]
+        .iter()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+    while rows.last().map_or(false, |row| row.is_empty()) {
+        rows.pop();
+    }
+
+    build_world(header, &rows, grid_start + 1)
+}
+
+/// Builds the world from the grid rows and checks the header against it.
+/// `first_line` is the line number of the first row, used in error messages.
+fn build_world(
+    header: WorldHeader,
+    rows: &[String],
+    first_line: usize,
+) -> Result<(GameWorld, WorldDefinition), WorldLoadError> {
+    let mut world = GameWorld::try_create_world(rows).map_err(|error| match error {
+        WorldLoadError::InvalidTile { line, column, character } => WorldLoadError::InvalidTile {
+            line: line + first_line - 1,
+            column,
+            character,
+        },
+        other => other,
+    })?;
+
+    // Regions
+    let mut region_names = HashSet::new();
+    for region in header.regions.iter() {
+        if !region_names.insert(region.name.as_str()) {
+            return Err(WorldLoadError::DuplicateRegion(region.name.clone()));
+        }
+        let corner = Position::new(region.x + region.width - 1, region.y + region.height - 1);
+        if region.width <= 0
+            || region.height <= 0
+            || !world.is_valid_position(region.x, region.y)
+            || !world.is_valid_position(corner.x, corner.y)
+        {
+            return Err(WorldLoadError::OutOfBounds {
+                what: format!("Region '{}'", region.name),
+                x: region.x,
+                y: region.y,
+            });
+        }
+    }
+    let find_region = |name: &str| -> Result<&Region, WorldLoadError> {
+        header
+            .regions
+            .iter()
+            .find(|region| region.name == name)
+            .ok_or_else(|| WorldLoadError::UnknownRegion(name.to_string()))
+    };
+
+    // Tile overrides
+    for tile_override in header.overrides.iter() {
+        let positions = match (tile_override.x, tile_override.y, &tile_override.region) {
+            (Some(x), Some(y), None) => vec![Position::new(x, y)],
+            (None, None, Some(name)) => find_region(name)?.positions(),
+            _ => {
+                return Err(WorldLoadError::InvalidOverride(
+                    "an override needs either x and y or a region".to_string(),
+                ))
+            }
+        };
+        let tile_type = match tile_override.tile {
+            Some(c) => Some(TileType::from_char(c).ok_or_else(|| {
+                WorldLoadError::InvalidOverride(format!("unknown tile character '{}'", c))
+            })?),
+            None => None,
+        };
+
+        for position in positions {
+            let tile = world.get_tile_mut(position.x, position.y).ok_or(WorldLoadError::OutOfBounds {
+                what: "Tile override".to_string(),
+                x: position.x,
+                y: position.y,
+            })?;
+            if let Some(valid_monster_spawn) = tile_override.valid_monster_spawn {
+                tile.set_monster_spawn(valid_monster_spawn);
+            }
+            if let Some(tile_type) = tile_type {
+                world.set_tile_type(position.x, position.y, tile_type);
+            }
+        }
+    }
+
+    // Spawns
+    for spawn in header.spawns.iter() {
+        match (spawn.x, spawn.y, &spawn.region) {
+            (Some(x), Some(y), None) => {
+                if !world.is_valid_position(x, y) {
+                    return Err(WorldLoadError::OutOfBounds {
+                        what: format!("Spawn of {}", spawn.kind.to_string()),
+                        x,
+                        y,
+                    });
+                }
+                // Same rule as the region spawns, nothing stands on a mountain or in a lake
+                if let Some(tile_type @ (TileType::Mountain | TileType::Lake)) =
+                    world.get_tile_type(x, y)
+                {
+                    return Err(WorldLoadError::InvalidSpawn(format!(
+                        "a {} spawn at ({}, {}) is on a {} tile",
+                        spawn.kind.to_string(),
+                        x,
+                        y,
+                        tile_type.to_string()
+                    )));
+                }
+            }
+            (None, None, Some(name)) => {
+                find_region(name)?;
+            }
+            _ => {
+                return Err(WorldLoadError::InvalidSpawn(format!(
+                    "a {} spawn needs either x and y or a region",
+                    spawn.kind.to_string()
+                )))
+            }
+        }
+        if spawn.count == 0 {
+            return Err(WorldLoadError::InvalidSpawn(format!(
+                "a {} spawn has a count of 0",
+                spawn.kind.to_string()
+            )));
+        }
+        if let Some(monster_type) = &spawn.monster_type {
+            if spawn.kind != SpawnKind::Monster {
+                return Err(WorldLoadError::InvalidSpawn(format!(
+                    "a {} spawn has a monster type",
+                    spawn.kind.to_string()
+                )));
+            }
+            if !header.monster_types.iter().any(|stats| &stats.name == monster_type) {
+                return Err(WorldLoadError::UnknownMonsterType(monster_type.clone()));
+            }
+        }
+    }
+
+    // Monster types
+    let mut monster_type_names = HashSet::new();
+    for stats in header.monster_types.iter() {
+        if !monster_type_names.insert(stats.name.as_str()) {
+            return Err(WorldLoadError::InvalidMonsterType(format!(
+                "'{}' is defined twice",
+                stats.name
+            )));
+        }
+        if stats.max_energy == 0
+            || stats.aggro_radius < 0
+            || stats.patrol_radius < 0
+            || stats.leash_radius < 0
+        {
+            return Err(WorldLoadError::InvalidMonsterType(format!(
+                "'{}' needs some energy and distances that are not negative",
+                stats.name
+            )));
+        }
+    }
+
+    // Treasures
+    let treasure = header.treasure;
+    if treasure.min_value == 0 || treasure.min_value > treasure.max_value {
+        return Err(WorldLoadError::InvalidTreasureConfig(
+            "min_value must be above 0 and at most max_value".to_string(),
+        ));
+    }
+    if treasure.loot_per_turn == 0 || !(0.0..=1.0).contains(&treasure.respawn_chance) {
+        return Err(WorldLoadError::InvalidTreasureConfig(
+            "loot_per_turn must be above 0 and respawn_chance between 0 and 1".to_string(),
+        ));
+    }
+
+    let definition = WorldDefinition {
+        name: header.name.clone().unwrap_or_default(),
+        counts: header.counts,
+        regions: header.regions.clone(),
+        spawns: header.spawns.clone(),
+        monster_types: header.monster_types.clone(),
+        treasure,
+    };
+    for kind in [SpawnKind::Player, SpawnKind::Agent, SpawnKind::Monster, SpawnKind::Treasure] {
+        let fixed = definition.fixed_count(kind);
+        let count = definition.get_count(kind);
+        if fixed > count {
+            return Err(WorldLoadError::TooManySpawns {
+                kind: kind.to_string().to_string(),
+                fixed,
+                count,
+            });
+        }
+    }
+
+    Ok((world, definition))
+}
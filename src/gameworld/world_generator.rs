@@ -0,0 +1,379 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+
+/// Parameters of the procedural world generator.
+#[derive(Clone, Debug)]
+pub struct WorldGenConfig {
+    pub width: i32,
+    pub height: i32,
+    /// Number of village clusters.
+    pub villages: usize,
+    /// Number of rivers flowing down from the mountains.
+    pub rivers: usize,
+    /// Size of a noise cell in tiles, bigger cells give bigger forests and mountain ranges.
+    pub noise_scale: f32,
+    /// Elevation above which a tile becomes a mountain.
+    pub mountain_level: f32,
+    /// Elevation below which a tile becomes a lake.
+    pub lake_level: f32,
+}
+
+impl Default for WorldGenConfig {
+    fn default() -> Self {
+        WorldGenConfig {
+            width: 40,
+            height: 40,
+            villages: 4,
+            rivers: 2,
+            noise_scale: 8.0,
+            mountain_level: 0.68,
+            lake_level: 0.22,
+        }
+    }
+}
+
+/// Parses a world size of the form `<width>x<height>`, e.g. `64x48`.
+pub fn parse_world_size(value: &str) -> Result<(i32, i32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected <width>x<height>, got '{}'", value))?;
+    let width = width
+        .trim()
+        .parse::<i32>()
+        .map_err(|_| format!("invalid width '{}'", width))?;
+    let height = height
+        .trim()
+        .parse::<i32>()
+        .map_err(|_| format!("invalid height '{}'", height))?;
+    if width < 8 || height < 8 {
+        return Err("a generated world must be at least 8x8".to_string());
+    }
+    Ok((width, height))
+}
+
+/// Smooth random values on a lattice, interpolated between the lattice points.
+struct ValueNoise {
+    lattice: Vec<f32>,
+    columns: usize,
+    scale: f32,
+}
+
+impl ValueNoise {
+    fn new(width: i32, height: i32, scale: f32, rng: &mut dyn RngCore) -> Self {
+        let columns = (width as f32 / scale).ceil() as usize + 2;
+        let rows = (height as f32 / scale).ceil() as usize + 2;
+        let lattice = (0..columns * rows).map(|_| rng.gen::<f32>()).collect();
+        ValueNoise {
+            lattice,
+            columns,
+            scale,
+        }
+    }
+
+    fn sample(&self, x: i32, y: i32) -> f32 {
+        let fx = x as f32 / self.scale;
+        let fy = y as f32 / self.scale;
+        let (cx, cy) = (fx.floor() as usize, fy.floor() as usize);
+        let (tx, ty) = (smoothstep(fx.fract()), smoothstep(fy.fract()));
+
+        let value = |column: usize, row: usize| self.lattice[row * self.columns + column];
+        let top = lerp(value(cx, cy), value(cx + 1, cy), tx);
+        let bottom = lerp(value(cx, cy + 1), value(cx + 1, cy + 1), tx);
+        lerp(top, bottom, ty)
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// The tile grid while it is being generated, indexed as `tiles[y][x]`.
+struct DraftGrid {
+    width: i32,
+    height: i32,
+    tiles: Vec<Vec<TileType>>,
+}
+
+impl DraftGrid {
+    fn get(&self, position: Position) -> TileType {
+        self.tiles[position.y as usize][position.x as usize]
+    }
+
+    fn set(&mut self, position: Position, tile_type: TileType) {
+        self.tiles[position.y as usize][position.x as usize] = tile_type;
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        position.x >= 0 && position.y >= 0 && position.x < self.width && position.y < self.height
+    }
+
+    /// All positions, row by row.
+    fn positions(&self) -> Vec<Position> {
+        let mut positions = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                positions.push(Position::new(x, y));
+            }
+        }
+        positions
+    }
+
+    fn neighbors(&self, position: Position) -> Vec<Position> {
+        [(0, -1), (1, 0), (0, 1), (-1, 0)]
+            .iter()
+            .map(|(dx, dy)| Position::new(position.x + dx, position.y + dy))
+            .filter(|neighbor| self.contains(*neighbor))
+            .collect()
+    }
+
+    fn is_next_to(&self, position: Position, tile_type: TileType) -> bool {
+        self.neighbors(position)
+            .iter()
+            .any(|neighbor| self.get(*neighbor) == tile_type)
+    }
+
+    fn to_rows(&self) -> Vec<String> {
+        self.tiles
+            .iter()
+            .map(|row| row.iter().map(|tile_type| tile_type.to_char()).collect())
+            .collect()
+    }
+}
+
+fn chebyshev(a: Position, b: Position) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+/// Generates a world from `rng` (the WorldGen stream), the same seed and config always give the same map.
+pub fn generate_world(config: &WorldGenConfig, rng: &mut dyn RngCore) -> GameWorld {
+    let mut grid = DraftGrid {
+        width: config.width,
+        height: config.height,
+        tiles: vec![vec![TileType::Forest; config.width as usize]; config.height as usize],
+    };
+
+    // 1. Terrain: two octaves of noise give the elevation of every tile
+    let coarse = ValueNoise::new(config.width, config.height, config.noise_scale, rng);
+    let fine = ValueNoise::new(config.width, config.height, config.noise_scale / 2.0, rng);
+    let elevation: Vec<Vec<f32>> = (0..config.height)
+        .map(|y| {
+            (0..config.width)
+                .map(|x| coarse.sample(x, y) * 0.7 + fine.sample(x, y) * 0.3)
+                .collect()
+        })
+        .collect();
+    let elevation_at = |position: Position| elevation[position.y as usize][position.x as usize];
+
+    for position in grid.positions() {
+        let height = elevation_at(position);
+        if height > config.mountain_level {
+            grid.set(position, TileType::Mountain);
+        } else if height < config.lake_level {
+            grid.set(position, TileType::Lake);
+        }
+    }
+
+    // 2. Rivers start at the foot of the mountains and flow downhill until they reach a lake or the edge
+    let mut sources: Vec<Position> = grid
+        .positions()
+        .into_iter()
+        .filter(|position| {
+            grid.get(*position) == TileType::Mountain && grid.is_next_to(*position, TileType::Forest)
+        })
+        .collect();
+    sources.shuffle(rng);
+    for source in sources.into_iter().take(config.rivers) {
+        carve_river(&mut grid, &elevation, source);
+    }
+
+    // 3. Village clusters, spread out over the land
+    let centers = place_village_centers(&grid, config, rng);
+    for center in centers.iter() {
+        grid.set(*center, TileType::Village);
+        let mut around: Vec<Position> = grid
+            .neighbors(*center)
+            .into_iter()
+            .filter(|position| grid.get(*position) == TileType::Forest)
+            .collect();
+        around.shuffle(rng);
+        let extra = rng.gen_range(0..=around.len().min(3));
+        for position in around.into_iter().take(extra) {
+            grid.set(position, TileType::Village);
+        }
+    }
+
+    // 4. Roads: every cluster is joined to the nearest connected one, lakes on the way become fords
+    for index in 1..centers.len() {
+        let from = centers[index];
+        let to = *centers[..index]
+            .iter()
+            .min_by_key(|center| chebyshev(from, **center))
+            .unwrap();
+        carve_road(&mut grid, from, to, rng);
+    }
+
+    // 5. Points of interest placed by rules
+    place_by_rule(
+        &mut grid,
+        &centers,
+        rng,
+        TileType::Farm,
+        (config.villages * 2).max(1),
+        |grid, position, distance| {
+            // Farms lie next to the villages
+            grid.get(position) == TileType::Forest && (1..=2).contains(&distance)
+        },
+    );
+    let area = (config.width * config.height) as usize;
+    place_by_rule(
+        &mut grid,
+        &centers,
+        rng,
+        TileType::Mine,
+        (area / 200).max(1),
+        |grid, position, distance| {
+            // Mines are dug into the foot of the mountains, within reach of a village
+            grid.get(position) == TileType::Forest
+                && grid.is_next_to(position, TileType::Mountain)
+                && (3..=12).contains(&distance)
+        },
+    );
+    place_by_rule(
+        &mut grid,
+        &centers,
+        rng,
+        TileType::Dungeon,
+        (area / 250).max(1),
+        |grid, position, distance| {
+            // Dungeons are far away from the villages
+            grid.get(position) == TileType::Forest && distance >= 6
+        },
+    );
+
+    GameWorld::try_create_world(&grid.to_rows()).expect("generated world has only known tiles")
+}
+
+/// Follows the steepest descent from `source`, turning the tiles into water.
+fn carve_river(grid: &mut DraftGrid, elevation: &[Vec<f32>], source: Position) {
+    let elevation_at = |position: Position| elevation[position.y as usize][position.x as usize];
+    let mut current = source;
+    let max_length = (grid.width + grid.height) as usize * 2;
+
+    for _ in 0..max_length {
+        let next = grid
+            .neighbors(current)
+            .into_iter()
+            .filter(|neighbor| grid.get(*neighbor) != TileType::Mountain)
+            .min_by(|a, b| elevation_at(*a).partial_cmp(&elevation_at(*b)).unwrap());
+        let next = match next {
+            Some(next) => next,
+            None => break,
+        };
+
+        if grid.get(next) == TileType::Lake {
+            break;
+        }
+        grid.set(next, TileType::Lake);
+
+        // A river that cannot flow any lower ends in a small lake
+        if elevation_at(next) >= elevation_at(current) {
+            for neighbor in grid.neighbors(next) {
+                if grid.get(neighbor) == TileType::Forest {
+                    grid.set(neighbor, TileType::Lake);
+                }
+            }
+            break;
+        }
+        let at_edge =
+            next.x == 0 || next.y == 0 || next.x == grid.width - 1 || next.y == grid.height - 1;
+        if at_edge {
+            break;
+        }
+        current = next;
+    }
+}
+
+/// Picks cluster centers on forest tiles, keeping them apart where the map allows it.
+fn place_village_centers(
+    grid: &DraftGrid,
+    config: &WorldGenConfig,
+    rng: &mut dyn RngCore,
+) -> Vec<Position> {
+    let mut land: Vec<Position> = grid
+        .positions()
+        .into_iter()
+        .filter(|position| grid.get(*position) == TileType::Forest)
+        .collect();
+    land.shuffle(rng);
+
+    let mut min_distance = (config.width.min(config.height) / (config.villages as i32 + 1)).max(3);
+    let mut centers = Vec::new();
+    while centers.len() < config.villages && min_distance > 0 {
+        for position in land.iter() {
+            if centers.len() == config.villages {
+                break;
+            }
+            if centers
+                .iter()
+                .all(|center| chebyshev(*center, *position) >= min_distance)
+            {
+                centers.push(*position);
+            }
+        }
+        // Not enough room, relax the spacing
+        min_distance -= 1;
+    }
+    centers
+}
+
+/// Walks from `from` to `to` one axis step at a time, replacing water with passable land.
+fn carve_road(grid: &mut DraftGrid, from: Position, to: Position, rng: &mut dyn RngCore) {
+    let mut current = from;
+    while current != to {
+        let step_x = current.x != to.x && (current.y == to.y || rng.gen_bool(0.5));
+        if step_x {
+            current.x += (to.x - current.x).signum();
+        } else {
+            current.y += (to.y - current.y).signum();
+        }
+        if grid.get(current) == TileType::Lake {
+            grid.set(current, TileType::Forest);
+        }
+    }
+}
+
+/// Places up to `count` tiles of `tile_type` on random positions accepted by `rule`,
+/// which gets the distance to the closest village cluster.
+fn place_by_rule(
+    grid: &mut DraftGrid,
+    centers: &[Position],
+    rng: &mut dyn RngCore,
+    tile_type: TileType,
+    count: usize,
+    rule: impl Fn(&DraftGrid, Position, i32) -> bool,
+) {
+    let mut candidates: Vec<Position> = grid
+        .positions()
+        .into_iter()
+        .filter(|position| {
+            let distance = centers
+                .iter()
+                .map(|center| chebyshev(*center, *position))
+                .min()
+                .unwrap_or(i32::MAX);
+            rule(grid, *position, distance)
+        })
+        .collect();
+    candidates.shuffle(rng);
+    for position in candidates.into_iter().take(count) {
+        grid.set(position, tile_type);
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use clap::ValueEnum;
+
+use crate::errors::WorldLoadError;
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+use crate::gameworld::world_file::WorldDefinition;
+use crate::system::pathfinding::movement::{Connectivity, CornerCutting};
+
+/// What to do with a world whose villages, points of interest or spawns cannot reach each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ValidationMode {
+    /// Do not check the world.
+    Off,
+    /// Print the unreachable points and keep the world as it is.
+    Warn,
+    /// Turn water into land until every point is reachable.
+    Repair,
+    /// Refuse to start with a world that has unreachable points.
+    Reject,
+}
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+const DIAGONALS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+fn is_passable(world: &GameWorld, position: Position) -> bool {
+    world
+        .get_tile_type(position.x, position.y)
+        .map_or(false, |tile_type| tile_type.get_travel_weight() > 0.0)
+}
+
+/// Connected components of the passable tiles, moving as the world's movement model allows.
+pub struct WorldConnectivity {
+    labels: HashMap<Position, usize>,
+    sizes: Vec<usize>,
+    village_components: HashSet<usize>,
+}
+
+impl WorldConnectivity {
+    pub fn compute(world: &GameWorld) -> Self {
+        let mut labels = HashMap::new();
+        let mut sizes = Vec::new();
+        for start in world.get_tiles().positions() {
+            if labels.contains_key(&start) || !is_passable(world, start) {
+                continue;
+            }
+
+            // Flood fill a new component
+            let component = sizes.len();
+            let mut size = 0;
+            let mut queue = VecDeque::from([start]);
+            labels.insert(start, component);
+            while let Some(position) = queue.pop_front() {
+                size += 1;
+                for next in world.get_movement().neighbors(world, position) {
+                    if !labels.contains_key(&next) {
+                        labels.insert(next, component);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+
+        let village_components = labels
+            .iter()
+            .filter(|(position, _)| {
+                world.get_tile_type(position.x, position.y) == Some(TileType::Village)
+            })
+            .map(|(_, component)| *component)
+            .collect();
+
+        WorldConnectivity {
+            labels,
+            sizes,
+            village_components,
+        }
+    }
+
+    /// Component of a tile, `None` for impassable tiles.
+    pub fn get_component(&self, position: Position) -> Option<usize> {
+        self.labels.get(&position).copied()
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn component_size(&self, component: usize) -> usize {
+        self.sizes.get(component).copied().unwrap_or(0)
+    }
+
+    pub fn is_connected(&self, a: Position, b: Position) -> bool {
+        matches!((self.get_component(a), self.get_component(b)), (Some(a), Some(b)) if a == b)
+    }
+
+    /// Whether a tile shares a component with at least one village.
+    pub fn is_connected_to_village(&self, position: Position) -> bool {
+        self.get_component(position).map_or(false, |component| {
+            self.village_components.contains(&component)
+        })
+    }
+
+    /// The component with the most villages, ties going to the lowest component.
+    pub fn main_component(&self, world: &GameWorld) -> Option<usize> {
+        let mut village_counts: HashMap<usize, usize> = HashMap::new();
+        for (position, component) in self.labels.iter() {
+            if world.get_tile_type(position.x, position.y) == Some(TileType::Village) {
+                *village_counts.entry(*component).or_insert(0) += 1;
+            }
+        }
+        village_counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+            .map(|(component, _)| component)
+    }
+}
+
+/// A point that should be reachable from the villages but is not.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnreachablePoint {
+    pub what: String,
+    pub position: Position,
+}
+
+/// Result of checking a world.
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub components: usize,
+    pub unreachable: Vec<UnreachablePoint>,
+    /// Tiles turned from water into land by a repair.
+    pub repaired_tiles: Vec<Position>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.unreachable.is_empty()
+    }
+}
+
+/// Lists the villages, dungeons, farms, mines and fixed spawns that are not in the main village component.
+pub fn find_unreachable(world: &GameWorld, definition: &WorldDefinition) -> Vec<UnreachablePoint> {
+    let connectivity = WorldConnectivity::compute(world);
+    let main = connectivity.main_component(world);
+
+    let mut points: Vec<UnreachablePoint> = Vec::new();
+    for (position, tile) in world.iter_tiles() {
+        let tile_type = tile.get_tile_type();
+        if matches!(
+            tile_type,
+            TileType::Village | TileType::Dungeon | TileType::Farm | TileType::Mine
+        ) && connectivity.get_component(position) != main
+        {
+            points.push(UnreachablePoint {
+                what: tile_type.to_string().to_string(),
+                position,
+            });
+        }
+    }
+
+    for spawn in definition.spawns.iter() {
+        if let (Some(x), Some(y)) = (spawn.x, spawn.y) {
+            let position = Position::new(x, y);
+            if connectivity.get_component(position) != main {
+                points.push(UnreachablePoint {
+                    what: format!("{} spawn", spawn.kind.to_string()),
+                    position,
+                });
+            }
+        }
+    }
+
+    // Region spawns only need one reachable tile to pick from
+    for spawn in definition.spawns.iter() {
+        if let Some(region) = spawn
+            .region
+            .as_deref()
+            .and_then(|name| definition.get_region(name))
+        {
+            let reachable = region
+                .positions()
+                .iter()
+                .any(|position| main.is_some() && connectivity.get_component(*position) == main);
+            if !reachable {
+                points.push(UnreachablePoint {
+                    what: format!(
+                        "{} spawn in region '{}'",
+                        spawn.kind.to_string(),
+                        region.name
+                    ),
+                    position: Position::new(region.x, region.y),
+                });
+            }
+        }
+    }
+
+    points
+}
+
+/// Steps a road can be carved along. A diagonal road through water only joins up
+/// when the movement model lets units cut the corners beside it.
+fn carving_steps(world: &GameWorld) -> Vec<(i32, i32)> {
+    let movement = world.get_movement();
+    let mut steps = DIRECTIONS.to_vec();
+    if movement.connectivity == Connectivity::Eight
+        && movement.corner_cutting == CornerCutting::Allow
+    {
+        steps.extend(DIAGONALS);
+    }
+    steps
+}
+
+/// Connects the component of `from` to the main component along the shortest line of tiles,
+/// turning water on the way into forest. Returns the changed tiles.
+fn connect_to_main(
+    world: &mut GameWorld,
+    connectivity: &WorldConnectivity,
+    main: usize,
+    from: Position,
+) -> Vec<Position> {
+    let mut came_from: HashMap<Position, Position> = HashMap::new();
+    let mut visited = HashSet::from([from]);
+    let mut queue = VecDeque::from([from]);
+    let steps = carving_steps(world);
+
+    while let Some(position) = queue.pop_front() {
+        if connectivity.get_component(position) == Some(main) {
+            // Walk back and fill in the water
+            let mut changed = Vec::new();
+            let mut current = position;
+            while let Some(previous) = came_from.get(&current) {
+                if !is_passable(world, current) {
+                    changed.push(current);
+                }
+                current = *previous;
+            }
+            if !is_passable(world, from) {
+                changed.push(from);
+            }
+            for tile_position in changed.iter() {
+                world.set_tile_type(tile_position.x, tile_position.y, TileType::Forest);
+            }
+            return changed;
+        }
+
+        for (dx, dy) in steps.iter() {
+            let next = Position::new(position.x + dx, position.y + dy);
+            if world.is_valid_position(next.x, next.y) && visited.insert(next) {
+                came_from.insert(next, position);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    Vec::new()
+}
+
+/// Checks the world and, depending on `mode`, repairs it or rejects it.
+pub fn validate_world(
+    world: &mut GameWorld,
+    definition: &WorldDefinition,
+    mode: ValidationMode,
+) -> Result<ValidationReport, WorldLoadError> {
+    let mut report = ValidationReport::default();
+    if mode == ValidationMode::Off {
+        return Ok(report);
+    }
+
+    report.unreachable = find_unreachable(world, definition);
+    if mode == ValidationMode::Repair {
+        // Every repair joins at least one component to the main one, so this ends
+        while let Some(point) = report.unreachable.first().cloned() {
+            let connectivity = WorldConnectivity::compute(world);
+            let main = match connectivity.main_component(world) {
+                Some(main) => main,
+                None => break,
+            };
+            let changed = connect_to_main(world, &connectivity, main, point.position);
+            if changed.is_empty() {
+                break;
+            }
+            report.repaired_tiles.extend(changed);
+            report.unreachable = find_unreachable(world, definition);
+        }
+    }
+    report.components = WorldConnectivity::compute(world).component_count();
+
+    if !report.is_valid() {
+        for point in report.unreachable.iter() {
+            eprintln!(
+                "Unreachable {} at {}",
+                point.what,
+                point.position.to_string()
+            );
+        }
+        if mode == ValidationMode::Reject {
+            return Err(WorldLoadError::Unreachable(report.unreachable.len()));
+        }
+    }
+
+    Ok(report)
+}
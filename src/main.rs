@@ -2,12 +2,21 @@
 
 use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
-use system::mcst_system::expansion::expansion_system;
+use system::mcst_system::backpropegation::backpropegate_system;
+use system::mcst_system::budget::{
+    check_budget_system, parse_agent_budget, report_budget_system, BudgetLimits, BudgetScope,
+    MctsBudget, MctsUsage,
+};
+use system::mcst_system::expansion::{expansion_system, ActionPriorConfig};
+use system::mcst_system::rollout_policy::{RolloutConfig, RolloutPolicyKind};
 use system::mcst_system::selection::selection_system;
+use system::mcst_system::simulation::simulation_system;
 use system::player_system::handle_player_movement::move_player;
 use system::player_system::highlight_squares::highlight_moveable_player_squares;
+use system::simulation::handle_monster_behaviour::handle_monster_system;
 use system::simulation::handle_npc_movement::handle_agent_movement;
 use system::simulation::handle_selected_action::handle_selected_action_system;
+use system::simulation::handle_treasure_respawn::treasure_respawn_system;
 use ui::mcst_tree_display::mcst_tree_display::agent_action_button_system;
 use ui::mcst_tree_display::mcst_tree_display::update_agent_action_button_visibility;
 use ui::mcst_tree_display::mcst_tree_display::DisplayTreeWindowState;
@@ -31,8 +40,12 @@ mod gameworld {
     pub mod highlight;
     pub mod position;
     pub mod tile;
+    pub mod tile_grid;
     pub mod tile_types;
     pub mod world;
+    pub mod world_file;
+    pub mod world_generator;
+    pub mod world_validator;
 }
 mod debug;
 mod errors;
@@ -41,6 +54,8 @@ mod npcs {
         pub mod action_rating;
         pub mod gene_type;
         pub mod genes;
+        pub mod inventory;
+        pub mod memory;
         pub mod npc_action;
         pub mod npc_base;
         pub mod npc_status;
@@ -54,6 +69,16 @@ mod npcs {
     pub mod treasure;
 }
 mod system {
+    pub(crate) mod forward_model {
+        pub mod combat;
+        pub mod conversation;
+        pub mod economy;
+        pub mod events;
+        pub mod game_state;
+        pub mod monster_rules;
+        pub mod rules;
+        pub mod theft;
+    }
     pub(crate) mod player_system {
         pub mod handle_player_movement;
         pub mod highlight_squares;
@@ -61,43 +86,73 @@ mod system {
     pub(crate) mod mcst_tree {
         pub mod mcst_node;
         pub mod mcst_tree;
+        pub mod selection_policy;
         pub mod simulation_tree;
     }
     pub(crate) mod mcst_system {
         pub mod backpropegation;
+        pub mod budget;
         pub mod expansion;
+        pub mod rollout_policy;
         pub mod selection;
         pub mod simulation;
     }
     pub(crate) mod pathfinding {
+        pub mod benchmark;
+        pub mod danger;
+        pub mod hierarchical;
+        pub mod movement;
+        pub mod path_service;
+        pub mod reservation;
         pub mod pathfinding_calculation;
     }
+    pub mod random;
     pub mod setup;
+    pub mod turn;
 
     pub(crate) mod simulation {
+        pub mod handle_monster_behaviour;
         pub mod handle_npc_movement;
         pub mod handle_selected_action;
+        pub mod handle_treasure_respawn;
     }
 }
 mod tests {
 
     pub mod check_mcst_trees_system;
+    #[cfg(test)]
+    pub mod mcst_tests;
+    #[cfg(test)]
+    pub mod pathfinding_tests;
+    #[cfg(test)]
+    pub mod world_tests;
 }
 
 use crate::npcs::agent::Agent;
 use clap::Parser;
 use gameworld::position::Position;
-use gameworld::world;
+use gameworld::world_file::{load_world, WorldDefinition};
+use gameworld::world_generator::{generate_world, parse_world_size, WorldGenConfig};
+use gameworld::world_validator::{validate_world, ValidationMode};
 use npcs::npc_components::npc_action::NpcAction;
 use npcs::npc_components::npc_base::NPCBase;
-use rand::rngs::StdRng;
+use npcs::treasure::DEFAULT_TREASURE_REWARD;
 use rand::Rng;
-use rand::SeedableRng;
 use std::collections::VecDeque;
+use std::time::Duration;
+use system::mcst_tree::selection_policy::{parse_agent_policy, SelectionPolicyConfig, SelectionPolicyKind};
 use system::mcst_tree::simulation_tree::SimulationTree;
+use system::random::{RngStream, RngStreams};
+use system::pathfinding::benchmark::run_pathfinding_benchmark;
+use system::pathfinding::danger::{DangerConfig, DangerMode};
+use system::pathfinding::movement::{Connectivity, CornerCutting, MovementModel};
 use system::pathfinding::pathfinding_calculation::a_star_pathfinding;
 use system::setup::check_npc_count;
 use system::setup::setup;
+use system::turn::{
+    end_turn_system, finish_execution_phase, score_turn, start_execution_phase, start_mcts_phase,
+    start_player_turn,
+};
 use ui::camera::{camera_drag_system, setup_camera, CameraDragging};
 use ui::npc_click::npc_click_system;
 use ui::npc_click::update_selected_npc_text;
@@ -105,41 +160,11 @@ use ui::player_ui::player_health_bar::setup_player_health_ui;
 use ui::setup_ui::end_turn_button_system;
 use ui::setup_ui::setup_ui;
 use ui::setup_ui::PanelState;
-use world::GameWorld;
+use gameworld::world::GameWorld;
 
 #[derive(Resource, Default)]
 pub struct PlayerMoved(pub bool);
 
-#[derive(Resource)]
-pub struct MCSTFlag(pub bool);
-
-#[derive(Resource)]
-pub struct RunningFlag(pub bool);
-
-#[derive(Resource)]
-pub struct FinishedSelectionPhase(pub bool);
-
-#[derive(Resource)]
-pub struct FinishedSelectingActions(pub bool);
-
-#[derive(Resource)]
-pub struct FinishedRunningFlag(pub bool);
-
-#[derive(Resource)]
-pub struct Backpropogate(pub bool);
-
-#[derive(Resource)]
-pub struct MCSTCurrent(pub i32);
-
-#[derive(Resource)]
-pub struct MCSTTotal(pub i32);
-
-#[derive(Resource)]
-pub struct IterationCurrent(pub i32);
-
-#[derive(Resource)]
-pub struct IterationTotal(pub i32);
-
 ///Simulation world
 #[derive(Resource)]
 pub struct WorldSim(pub GameWorld);
@@ -156,17 +181,9 @@ pub struct NpcActionsCopy(pub Vec<(u32, VecDeque<NpcAction>)>);
 #[derive(Resource)]
 pub struct ScoreTracker(pub Vec<(u32, i32)>);
 
-#[derive(Resource)]
-struct WorldRandom(StdRng);
-
-#[derive(Default, Resource)]
-pub struct IterationCount(pub i32);
 #[derive(Resource, Default)]
 pub struct SelectedNPC(pub Option<Entity>);
 
-#[derive(Resource)]
-pub struct EndTurn(pub bool);
-
 #[derive(Resource, Default)]
 pub struct SystemMove(pub bool);
 
@@ -185,6 +202,8 @@ impl WorldSim {
             height_min: world.get_height_min(),
             width_max: world.get_width_max(),
             height_max: world.get_height_max(),
+            movement: world.get_movement(),
+            path_service: world.path_service.clone(),
         })
     }
 }
@@ -192,16 +211,128 @@ impl WorldSim {
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// World loaded from ./worlds/<name>.world (or a plain ./worlds/<name>.txt grid)
+    #[arg(long, default_value = "test")]
+    world: String,
+
+    /// Generate a world from the seed instead of loading --world
+    #[arg(long)]
+    generate: bool,
+
+    /// Size of a generated world, e.g. `64x48`
+    #[arg(long = "world-size", value_parser = parse_world_size, default_value = "40x40")]
+    world_size: (i32, i32),
+
+    /// Number of village clusters in a generated world
+    #[arg(long, default_value_t = 4)]
+    villages: usize,
+
+    /// Number of rivers in a generated world
+    #[arg(long, default_value_t = 2)]
+    rivers: usize,
+
+    /// How to handle villages, points of interest and spawns that cannot reach each other
+    #[arg(long, value_enum, default_value_t = ValidationMode::Warn)]
+    validate: ValidationMode,
+
+    /// Whether units move to the four orthogonal tiles only or also diagonally
+    #[arg(long, value_enum, default_value_t = Connectivity::Eight)]
+    movement: Connectivity,
+
+    /// When a diagonal step may pass the corner of an impassable tile
+    #[arg(long = "corner-cutting", value_enum, default_value_t = CornerCutting::NoSqueeze)]
+    corner_cutting: CornerCutting,
+
+    /// Whether the danger around monsters adds to the travel cost or blocks the tiles
+    #[arg(long, value_enum, default_value_t = DangerMode::Soft)]
+    danger: DangerMode,
+
+    /// Distance in tiles up to which a monster radiates danger
+    #[arg(long = "danger-radius", default_value_t = 4)]
+    danger_radius: i32,
+
+    /// Danger of a monster's own tile for an agent with full SelfPreservation, halving with every tile
+    #[arg(long = "danger-cost", default_value_t = 8.0)]
+    danger_cost: f32,
+
+    /// Times plain A* against hierarchical pathfinding on this many random queries and exits
+    #[arg(long = "bench-pathfinding")]
+    bench_pathfinding: Option<usize>,
+
+    /// Master seed of the run, 0 picks a random one. The same seed replays the same run
     #[arg(short, long, default_value_t = 0)]
     seed: u64,
+
+    /// Selection policy used by every agent's MCTS tree
+    #[arg(long, value_enum, default_value_t = SelectionPolicyKind::Uct)]
+    policy: SelectionPolicyKind,
+
+    /// Overrides the selection policy of one agent, e.g. `--agent-policy 3=puct`
+    #[arg(long = "agent-policy", value_parser = parse_agent_policy)]
+    agent_policies: Vec<(i32, SelectionPolicyKind)>,
+
+    /// Exploration constant used by UCT, UCB1-Tuned and PUCT
+    #[arg(long, default_value_t = 1.414)]
+    exploration: f64,
+
+    /// Exploration probability used by epsilon-greedy
+    #[arg(long, default_value_t = 0.1)]
+    epsilon: f64,
+
+    /// Virtual visits given to a newly expanded action, weighted by the agent's genes
+    #[arg(long = "prior-visits", default_value_t = 1)]
+    prior_visits: usize,
+
+    /// Scale applied to the gene rating to get the virtual reward of a new action,
+    /// in units of --reward-scale gold
+    #[arg(long = "prior-reward", default_value_t = 1.0)]
+    prior_reward: f64,
+
+    /// Policy used to pick actions during rollouts
+    #[arg(long, value_enum, default_value_t = RolloutPolicyKind::GeneWeighted)]
+    rollout: RolloutPolicyKind,
+
+    /// Number of turns simulated by each rollout
+    #[arg(long = "rollout-horizon", default_value_t = 50)]
+    rollout_horizon: u32,
+
+    /// Gold a rollout has to make to score a reward of 1
+    #[arg(long = "reward-scale", default_value_t = DEFAULT_TREASURE_REWARD as f64)]
+    reward_scale: f64,
+
+    /// Whether the MCTS budget applies to each agent's tree or to all trees together
+    #[arg(long = "budget-scope", value_enum, default_value_t = BudgetScope::PerAgent)]
+    budget_scope: BudgetScope,
+
+    /// MCTS iterations per turn. Defaults to 50 when no other limit is given
+    #[arg(long)]
+    iterations: Option<u32>,
+
+    /// Wall-clock time in milliseconds the MCTS phase may use per turn
+    #[arg(long = "time-budget")]
+    time_budget: Option<u64>,
+
+    /// Maximum number of nodes in a tree (or in all trees with the global scope)
+    #[arg(long = "max-nodes")]
+    max_nodes: Option<usize>,
+
+    /// Overrides the budget of one agent, e.g. `--agent-budget 3=iterations:200,time:20`
+    #[arg(long = "agent-budget", value_parser = parse_agent_budget)]
+    agent_budgets: Vec<(i32, BudgetLimits)>,
+
+    /// Nodes deeper than this are simulated without being expanded
+    #[arg(long = "max-depth", default_value_t = 256)]
+    max_depth: u32,
 }
 
 /// A timer resource for querying NPCs
 #[derive(Resource, Default)]
 pub struct QueryTimer(pub Timer);
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// Phases of a turn. Systems are gated on the current state with `run_if(in_state(..))`.
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
 pub enum TurnState {
+    #[default]
     PlayerTurn,
     MCTSPhase,
     ExecutionPhase,
@@ -210,7 +341,78 @@ pub enum TurnState {
 
 #[allow(dead_code)]
 fn main() {
-    let game_world = world::initialize("test").expect("Failed to initialize the game world");
+    // Parse the command-line arguments
+    let args = Args::parse();
+
+    // Use the seed from the command-line or generate a random seed
+    let seed = if args.seed != 0 {
+        args.seed
+    } else {
+        rand::thread_rng().gen()
+    };
+    println!("Using seed: {}", seed);
+
+    // Every random draw of the run comes from a stream derived from this seed
+    let mut rng_streams = RngStreams::new(seed);
+
+    let (mut game_world, world_definition) = if args.generate {
+        let config = WorldGenConfig {
+            width: args.world_size.0,
+            height: args.world_size.1,
+            villages: args.villages,
+            rivers: args.rivers,
+            ..Default::default()
+        };
+        println!("Generating world: {:?}", config);
+        let world = generate_world(&config, rng_streams.stream(RngStream::WorldGen));
+        let definition = WorldDefinition {
+            name: format!("generated-{}", seed),
+            ..Default::default()
+        };
+        (world, definition)
+    } else {
+        match load_world(&args.world) {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                eprintln!("Failed to load world '{}': {}", args.world, error);
+                std::process::exit(1);
+            }
+        }
+    };
+    println!("Loaded world '{}': {:?}", world_definition.name, world_definition.counts);
+
+    // Validation checks connectivity with the movement model the agents will use
+    game_world.set_movement(MovementModel::new(args.movement, args.corner_cutting));
+    println!("Using movement model: {:?}", game_world.get_movement());
+
+    match validate_world(&mut game_world, &world_definition, args.validate) {
+        Ok(report) => println!(
+            "World has {} connected components, {} unreachable points, {} repaired tiles",
+            report.components,
+            report.unreachable.len(),
+            report.repaired_tiles.len()
+        ),
+        Err(error) => {
+            eprintln!("Rejected world '{}': {}", world_definition.name, error);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(queries) = args.bench_pathfinding {
+        let benchmark =
+            run_pathfinding_benchmark(&game_world, queries, rng_streams.stream(RngStream::Benchmark));
+        println!("Pathfinding benchmark: {:?}", benchmark);
+        if benchmark.queries > 0 {
+            println!(
+                "Plain A* {:?} per query, hierarchical {:?} per query after a {:?} build",
+                benchmark.plain_time / benchmark.queries as u32,
+                benchmark.hierarchical_time / benchmark.queries as u32,
+                benchmark.build_time
+            );
+        }
+        return;
+    }
+
     let start = Position { x: 0, y: 1 };
     let goal = Position { x: 0, y: 3 };
 
@@ -222,19 +424,56 @@ fn main() {
         println!("Path found: {:?}", path);
     }
 
-    // Parse the command-line arguments
-    let args = Args::parse();
+    let policy_config = SelectionPolicyConfig {
+        default_policy: args.policy,
+        agent_policies: args.agent_policies.iter().copied().collect(),
+        exploration_constant: args.exploration,
+        epsilon: args.epsilon,
+    };
+    println!("Using {:?} selection policy", policy_config.default_policy);
 
-    // Use the seed from the command-line or generate a random seed
-    let seed = if args.seed != 0 {
-        args.seed
-    } else {
-        rand::thread_rng().gen()
+    let prior_config = ActionPriorConfig {
+        virtual_visits: args.prior_visits,
+        virtual_reward_scale: args.prior_reward,
     };
-    println!("Using seed: {}", seed);
+    let rollout_config = RolloutConfig {
+        policy: args.rollout,
+        horizon: args.rollout_horizon,
+        reward_scale: args.reward_scale,
+    };
+    if rollout_config.reward_scale <= 0.0 {
+        eprintln!("--reward-scale must be positive, got {}", rollout_config.reward_scale);
+        std::process::exit(1);
+    }
+    println!("Using {:?} rollout policy", rollout_config.policy);
+
+    let danger_config = DangerConfig {
+        mode: args.danger,
+        radius: args.danger_radius,
+        peak_cost: args.danger_cost,
+        ..Default::default()
+    };
+    println!("Using danger config: {:?}", danger_config);
 
-    // Initialize a seeded RNG
-    let rng = StdRng::seed_from_u64(seed);
+    let mut limits = BudgetLimits {
+        iterations: args.iterations,
+        time: args.time_budget.map(Duration::from_millis),
+        max_nodes: args.max_nodes,
+    };
+    if limits.iterations.is_none() && limits.time.is_none() && limits.max_nodes.is_none() {
+        // Without any limit the MCTS phase would never end
+        limits.iterations = BudgetLimits::default().iterations;
+    }
+    let budget = MctsBudget {
+        scope: args.budget_scope,
+        limits,
+        agent_limits: args.agent_budgets.iter().copied().collect(),
+        max_depth: args.max_depth,
+    };
+    println!("Using MCTS budget: {:?}", budget);
+    if budget.limits.time.is_some() || budget.agent_limits.values().any(|limits| limits.time.is_some()) {
+        println!("Note: a time budget depends on the machine, runs with the same seed may differ");
+    }
 
     // Begin building the Bevy app using App::new().
     App::new()
@@ -250,37 +489,32 @@ fn main() {
             }),
             EguiPlugin,
         ))
+        .add_state::<TurnState>()
         // Insert various resources
         .insert_resource(game_world)
+        .insert_resource(world_definition)
         .insert_resource(WorldSim(GameWorld::new()))
         .insert_resource(SimulationTree::default())
+        .insert_resource(policy_config)
+        .insert_resource(prior_config)
+        .insert_resource(rollout_config)
+        .insert_resource(budget)
+        .insert_resource(danger_config)
+        .insert_resource(MctsUsage::default())
         .insert_resource(CameraDragging {
             is_dragging: false,
             previous_mouse_position: None,
         })
-        .insert_resource(MCSTFlag(true))
-        .insert_resource(RunningFlag(false))
         .insert_resource(SelectedNPC(None))
         .insert_resource(PanelState {
             is_collapsed: false,
         })
-        .insert_resource(MCSTCurrent(0))
-        .insert_resource(MCSTTotal(0))
-        .insert_resource(IterationCurrent(0))
-        .insert_resource(FinishedSelectionPhase(false))
-        .insert_resource(FinishedSelectingActions(false))
         .insert_resource(AgentList(Vec::new()))
-        .insert_resource(RunningFlag(false))
-        .insert_resource(FinishedRunningFlag(false))
-        .insert_resource(Backpropogate(false))
         .insert_resource(NpcActions(Vec::new()))
         .insert_resource(NpcActionsCopy(Vec::new()))
         .insert_resource(ScoreTracker(Vec::new()))
-        .insert_resource(IterationTotal(3))
-        .insert_resource(IterationCount(0))
-        .insert_resource(WorldRandom(rng))
+        .insert_resource(rng_streams)
         .insert_resource(QueryTimer(Timer::from_seconds(0.1, TimerMode::Once))) // Delay by 0.1 seconds
-        .insert_resource(EndTurn(false)) // Add the EndTurn resource
         .insert_resource(SystemMove(false)) // Initialize the flag as false
         .insert_resource(HighlightMovement(true))
         .insert_resource(DisplayTreeWindowState::default()) 
@@ -305,29 +539,54 @@ fn main() {
                 camera_drag_system,
                 npc_click_system,
                 update_selected_npc_text,
+                update_agent_action_button_visibility,
+                agent_action_button_system,
+                display_tree_window_system,
+            ),
+        )
+        .add_systems(OnEnter(TurnState::PlayerTurn), start_player_turn)
+        .add_systems(
+            Update,
+            (
                 end_turn_button_system,
                 highlight_moveable_player_squares,
                 move_player,
+            )
+                .run_if(in_state(TurnState::PlayerTurn)),
+        )
+        .add_systems(OnEnter(TurnState::MCTSPhase), start_mcts_phase)
+        .add_systems(
+            Update,
+            (
                 selection_system,
                 expansion_system,
+                simulation_system,
+                backpropegate_system,
+                check_budget_system,
+            )
+                .chain()
+                .run_if(in_state(TurnState::MCTSPhase)),
+        )
+        .add_systems(OnEnter(TurnState::ExecutionPhase), start_execution_phase)
+        .add_systems(
+            Update,
+            (
                 handle_selected_action_system,
+                handle_monster_system,
                 handle_agent_movement,
-                update_agent_action_button_visibility,
-                agent_action_button_system,
-                display_tree_window_system,
-                // movement_system
-                // action_system
-                // backpropegate_system,
-                //
-            ),
+                finish_execution_phase,
+            )
+                .chain()
+                .run_if(in_state(TurnState::ExecutionPhase)),
+        )
+        .add_systems(
+            OnEnter(TurnState::EndTurn),
+            (score_turn, report_budget_system, treasure_respawn_system),
+        )
+        .add_systems(
+            Update,
+            end_turn_system.run_if(in_state(TurnState::EndTurn)),
         )
-        // .add_systems(Update, (
-        //     // MCTS Phase
-        //
-        //     expansion_system,
-        //     backpropegate_system,
-        //     // delayed_check_npc_count,
-        // ))
         .run();
 }
 
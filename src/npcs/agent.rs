@@ -1,13 +1,15 @@
 use crate::gameworld::position::Position;
 use crate::npcs::npc_components::npc_action::NpcAction;
 use bevy::prelude::*;
-use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
 use std::{fmt, u32};
 use std::sync::atomic::{AtomicI32, Ordering};
-use std::sync::{Arc, Mutex};
 
 use super::npc_components::gene_type::GeneType;
 use super::npc_components::genes::Genes;
+use super::npc_components::inventory::{Inventory, ResourceType};
+use super::npc_components::memory::Memory;
 use super::npc_components::npc_status::Status;
 use super::npc_components::npc_type::NPCType;
 use super::npc_components::opinions::Opinions;
@@ -23,7 +25,7 @@ pub struct Agent {
     opinions: Opinions,
     action: NpcAction,
     id: i32,
-    reward: u32,
+    inventory: Inventory,
     status: Status,
     target: Target,
     retaliation_target: Target,
@@ -33,11 +35,14 @@ pub struct Agent {
     treasure_target_id: i32,
     tile_target: Option<Position>,
     path: Option<Vec<Position>>,
+    wait_turns: u32,
+    work_turns: u32,
+    talk_turns: u32,
     leader: bool,
     follower: bool,
     leader_id: i32,
     followers: Vec<i32>,
-    memory: Vec<(i32, NpcAction)>, // New memory field
+    memory: Vec<Memory>, // New memory field
 }
 
 impl fmt::Debug for Agent {
@@ -60,16 +65,14 @@ impl Agent {
     //    | |___| (_) | | | \__ \ |_| |  | |_| | (__| || (_) | |
     //     \_____\___/|_| |_|___/\__|_|   \__,_|\___|\__\___/|_|
 
-    pub fn new_agent() -> Agent {
+    pub fn new_agent(rng: &mut dyn RngCore) -> Agent {
 
         // Create the Agent component
         let agent = Agent {
-            genes: Genes::generate(),
-            opinions: Opinions {
-                opinion_scores: Arc::new(Mutex::new(HashMap::new())),
-            },
+            genes: Genes::generate(rng),
+            opinions: Opinions::default(),
             id: A_COUNTER.fetch_add(1, Ordering::SeqCst),
-            reward: 0,
+            inventory: Inventory::new(),
             action: NpcAction::None,
             status: Status::Idle,
             target: Target::None,
@@ -80,6 +83,9 @@ impl Agent {
             treasure_target_id: i32::MAX,
             tile_target: None,
             path: None,
+            wait_turns: 0,
+            work_turns: 0,
+            talk_turns: 0,
             leader: true,
             follower: false,
             leader_id: i32::MAX,
@@ -94,12 +100,10 @@ impl Agent {
     pub fn default() -> Self {
 
         Agent {
-            genes: Genes::generate(),
-            opinions: Opinions {
-                opinion_scores: Arc::new(Mutex::new(HashMap::new())), // Wrap HashMap in Arc<Mutex<>>
-            },
+            genes: Genes::generate(&mut StdRng::seed_from_u64(0)),
+            opinions: Opinions::default(),
             id: 0,
-            reward: 0,
+            inventory: Inventory::new(),
             action: NpcAction::None,
             status: Status::Idle,
             target: Target::None,
@@ -110,6 +114,9 @@ impl Agent {
             treasure_target_id: i32::MAX,
             tile_target: None,
             path: None,
+            wait_turns: 0,
+            work_turns: 0,
+            talk_turns: 0,
             leader: false,
             follower: false,
             leader_id: 0,
@@ -144,8 +151,7 @@ impl Agent {
     }
 
     pub fn modify_opinion(&mut self, id: i32, amount: f32) {
-        let mut opinion_scores = self.opinions.opinion_scores.lock().unwrap();
-        let current_opinion = opinion_scores.entry(id).or_insert(0.5);
+        let current_opinion = self.opinions.opinion_scores.entry(id).or_insert(0.5);
         *current_opinion += amount;
         if *current_opinion > 1.0 {
             *current_opinion = 1.0;
@@ -155,14 +161,12 @@ impl Agent {
     }
 
     pub fn get_agent_opinion(&self, id: i32) -> f32 {
-        let opinion_scores = self.opinions.opinion_scores.lock().unwrap();
-        *opinion_scores.get(&id).unwrap_or(&0.5)
+        *self.opinions.opinion_scores.get(&id).unwrap_or(&0.5)
     }
 
     pub fn influence_opinions(&mut self, influencing_opinions: Opinions) {
-        let influencing_opinion_scores = influencing_opinions.opinion_scores.lock().unwrap();
-        let mut current_opinion_scores = self.opinions.opinion_scores.lock().unwrap();
-        for (&id, &influencing_opinion) in influencing_opinion_scores.iter() {
+        let current_opinion_scores = &mut self.opinions.opinion_scores;
+        for (&id, &influencing_opinion) in influencing_opinions.opinion_scores.iter() {
             let influence = if influencing_opinion > 0.5 {
                 0.1 * (influencing_opinion - 0.5)
             } else {
@@ -174,16 +178,26 @@ impl Agent {
     }
 
 
+    pub fn get_inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn get_inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
+    }
+
+    /// Worth of everything the agent carries, in gold.
     pub fn get_reward(&self) -> u32 {
-        self.reward
+        self.inventory.get_value() as u32
     }
 
+    /// Pays the agent in gold.
     pub fn add_reward(&mut self, reward: u32) {
-        self.reward = self.reward + reward;
+        self.inventory.add(ResourceType::Gold, reward);
     }
 
     pub fn remove_reward(&mut self, reward: u32) {
-        self.reward = self.reward.saturating_sub(reward);
+        self.inventory.remove(ResourceType::Gold, reward);
     }
 
 
@@ -271,6 +285,33 @@ impl Agent {
         self.path = Some(new_path);
     }
 
+    /// Turns in a row the agent could not take the next step of its path.
+    pub fn get_wait_turns(&self) -> u32 {
+        self.wait_turns
+    }
+
+    pub fn set_wait_turns(&mut self, wait_turns: u32) {
+        self.wait_turns = wait_turns;
+    }
+
+    /// Turns the agent has spent on its current Farming or Mining job.
+    pub fn get_work_turns(&self) -> u32 {
+        self.work_turns
+    }
+
+    pub fn set_work_turns(&mut self, work_turns: u32) {
+        self.work_turns = work_turns;
+    }
+
+    /// Turns the agent has spent in its current conversation.
+    pub fn get_talk_turns(&self) -> u32 {
+        self.talk_turns
+    }
+
+    pub fn set_talk_turns(&mut self, talk_turns: u32) {
+        self.talk_turns = talk_turns;
+    }
+
     pub fn is_leader(&self) -> bool {
         self.leader
     }
@@ -392,25 +433,38 @@ impl Agent {
     }
 
     pub fn find_best_agent(&self) -> Option<i32> {
-        let opinion_scores = self.opinions.opinion_scores.lock().unwrap();
-        opinion_scores
+        self.opinions
+            .opinion_scores
             .iter()
-            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+            // Ties go to the lowest id so the result does not depend on the HashMap order
+            .max_by(|a, b| {
+                a.1.partial_cmp(b.1)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| b.0.cmp(a.0))
+            })
             .map(|(&id, _)| id as i32)
     }
 
     pub fn find_worst_agent(&self) -> Option<i32> {
-        let opinion_scores = self.opinions.opinion_scores.lock().unwrap();
-        opinion_scores
+        self.opinions
+            .opinion_scores
             .iter()
-            .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .min_by(|a, b| {
+                a.1.partial_cmp(b.1)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| a.0.cmp(b.0))
+            })
             .map(|(&id, _)| id as i32)
     }
 
-    // Add a new entry to the memory
-    pub fn add_to_memory(&mut self, agent_id: i32, action: NpcAction) {
+    // Add a new entry to the memory, things the agent already remembers are not added twice
+    pub fn add_to_memory(&mut self, memory: Memory) {
+        if self.remembers(&memory) {
+            return;
+        }
+
         // Add the new entry to the front of the vector
-        self.memory.insert(0, (agent_id, action));
+        self.memory.insert(0, memory);
 
         // Ensure the memory does not exceed 50 items
         if self.memory.len() > 50 {
@@ -419,9 +473,13 @@ impl Agent {
     }
 
     // Retrieve the memory
-    pub fn get_memory(&self) -> &Vec<(i32, NpcAction)> {
+    pub fn get_memory(&self) -> &Vec<Memory> {
         &self.memory
     }
+
+    pub fn remembers(&self, memory: &Memory) -> bool {
+        self.memory.contains(memory)
+    }
     
 }
 
@@ -1,7 +1,44 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 
 use crate::{gameworld::position::Position, npcs::npc_components::npc_status::Status};
 
+/// Stats of a kind of monster, read from the `[[monster_types]]` of a world file.
+/// Distances are in tiles, counted like `is_next_to_target` (diagonals count as one).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonsterStats {
+    pub name: String,
+    pub max_energy: u32,
+    /// Energy an agent loses to one hit.
+    pub damage: u32,
+    /// Agents this close to the monster are attacked.
+    pub aggro_radius: i32,
+    /// Distance from the spawn the monster wanders while nobody is around.
+    pub patrol_radius: i32,
+    /// The monster gives up a chase that would take it further from its spawn.
+    pub leash_radius: i32,
+    /// Reward for the agent that kills it.
+    pub reward: u32,
+}
+
+impl Default for MonsterStats {
+    fn default() -> Self {
+        MonsterStats {
+            name: "default".to_string(),
+            max_energy: 100,
+            damage: 8,
+            aggro_radius: 3,
+            patrol_radius: 2,
+            leash_radius: 8,
+            reward: 0,
+        }
+    }
+}
+
+/// A monster guarding the area around its spawn.
+/// `status` is Idle while it patrols, Attacking while it chases or hits its target,
+/// Finished while it walks back to its spawn and Dead once killed.
 #[derive(Clone, Component)]
 pub struct Monster {
     id: i32,
@@ -10,6 +47,8 @@ pub struct Monster {
     target_id: i32,
     reward: u32,
     status: Status,
+    stats: MonsterStats,
+    patrol_index: usize,
 }
 
 static mut M_COUNTER: i32 = 0;
@@ -17,6 +56,9 @@ static mut M_COUNTER: i32 = 0;
 impl Monster {
     pub fn new_monster(start_position: Position) -> Self {
 
+        unsafe {
+            M_COUNTER += 1;
+        }
         Monster {
             id: unsafe { M_COUNTER },
             reward: 0,
@@ -24,6 +66,8 @@ impl Monster {
             start_position,
             current_position: start_position,
             target_id: i32::MAX,
+            stats: MonsterStats::default(),
+            patrol_index: 0,
         }
     }
 
@@ -70,4 +114,42 @@ impl Monster {
     pub fn get_target_id(&self) -> i32 {
         self.target_id
     }
+
+    pub fn has_target(&self) -> bool {
+        self.target_id != i32::MAX
+    }
+
+    pub fn clear_target(&mut self) {
+        self.target_id = i32::MAX;
+    }
+
+    pub fn get_start_position(&self) -> Position {
+        self.start_position
+    }
+
+    pub fn get_current_position(&self) -> Position {
+        self.current_position
+    }
+
+    pub fn set_current_position(&mut self, position: Position) {
+        self.current_position = position;
+    }
+
+    pub fn get_stats(&self) -> &MonsterStats {
+        &self.stats
+    }
+
+    /// Sets the stats of the monster, and its reward to the reward of its kind.
+    pub fn set_stats(&mut self, stats: MonsterStats) {
+        self.reward = stats.reward;
+        self.stats = stats;
+    }
+
+    pub fn get_patrol_index(&self) -> usize {
+        self.patrol_index
+    }
+
+    pub fn set_patrol_index(&mut self, patrol_index: usize) {
+        self.patrol_index = patrol_index;
+    }
 }
@@ -1,8 +1,12 @@
 use std::collections::HashMap;
 
-use rand::Rng;
+use rand::{Rng, RngCore};
 
-use super::{gene_type::GeneType, genes::Genes, npc_action::NpcAction};
+use super::{
+    gene_type::GeneType,
+    genes::Genes,
+    npc_action::{NpcAction, WorkType},
+};
 
 
 #[derive(Default, Clone)]
@@ -19,6 +23,9 @@ impl ActionRating {
         actions.insert(NpcAction::TreasureHunt, 0.0);
         actions.insert(NpcAction::Rest, 0.0);
         actions.insert(NpcAction::Talk, 0.0);
+        actions.insert(NpcAction::Work(WorkType::Farming(None)), 0.0);
+        actions.insert(NpcAction::Work(WorkType::Mining(None)), 0.0);
+        actions.insert(NpcAction::Work(WorkType::Merchant), 0.0);
         actions.insert(NpcAction::None, 0.0);
 
         ActionRating { actions }
@@ -59,33 +66,58 @@ impl ActionRating {
             self_preservation,
         );
 
+        let social = genes.return_type_score(GeneType::Social);
+        self.actions.insert(NpcAction::Talk, social);
+
+        // Working is the safe way to get rich
+        let work_score = (greed + self_preservation) / 2.0;
+        self.actions
+            .insert(NpcAction::Work(WorkType::Farming(None)), work_score);
+        self.actions
+            .insert(NpcAction::Work(WorkType::Mining(None)), work_score);
         self.actions
-            .insert(NpcAction::Talk, genes.return_type_score(GeneType::Social));
+            .insert(NpcAction::Work(WorkType::Merchant), (greed + social) / 2.0);
         self.actions.insert(NpcAction::None, 0.0);
     }
 
     pub fn calculate_total(&self) -> f32 {
-        self.actions.values().sum()
+        // Summed in a fixed order, float addition is not associative
+        NpcAction::iter().map(|action| self.get_rating(action)).sum()
     }
 
     pub fn get_actions(&self) -> &HashMap<NpcAction, f32> {
         &self.actions
     }
 
-    pub fn select_action(&self) -> Option<NpcAction> {
+    /// Returns the raw gene-derived rating of an action.
+    pub fn get_rating(&self, action: NpcAction) -> f32 {
+        *self.actions.get(&action).unwrap_or(&0.0)
+    }
+
+    /// Returns the rating of an action normalised over all ratings, for use as an MCTS prior.
+    pub fn get_prior(&self, action: NpcAction) -> f64 {
+        let total = self.calculate_total();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        (self.get_rating(action) / total) as f64
+    }
+
+    /// Samples an action with probability proportional to its rating.
+    pub fn select_action(&self, rng: &mut dyn RngCore) -> Option<NpcAction> {
         let total = self.calculate_total();
-        if total == 0.0 {
+        if total <= 0.0 {
             return None;
         }
 
-        let mut rng = rand::thread_rng();
         let mut cumulative_sum = 0.0;
         let rand_num: f32 = rng.gen_range(0.0..total);
 
-        for (action, value) in &self.actions {
-            cumulative_sum += *value;
+        // Walk the actions in a fixed order so the same roll always picks the same action
+        for action in NpcAction::iter() {
+            cumulative_sum += self.get_rating(action);
             if cumulative_sum >= rand_num {
-                return Some(*action);
+                return Some(action);
             }
         }
 
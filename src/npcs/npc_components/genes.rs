@@ -1,4 +1,5 @@
 use std::{collections::HashMap, sync::{Arc, Mutex}};
+use rand::RngCore;
 use super::gene_type::GeneType;
 
 
@@ -21,12 +22,10 @@ impl Genes {
         }
     }
 
-    pub fn generate() -> Self {
+    /// Rolls a new set of genes from `rng` (the Genes stream during setup).
+    pub fn generate(rng: &mut dyn RngCore) -> Self {
         use rand::distributions::{Distribution, Uniform};
 
-        // Initialize a random number generator
-        let mut rng = rand::thread_rng();
-
         // Define distribution ranges for agent attributes
         let greed_distribution = Uniform::new(0.5, 1.0);
         let aggression_distribution = Uniform::new(0.3, 0.8);
@@ -35,17 +34,19 @@ impl Genes {
 
         // Generate random values for each attribute
         let mut gene_scores = HashMap::new();
-        gene_scores.insert(GeneType::Greed, greed_distribution.sample(&mut rng));
+        gene_scores.insert(GeneType::Greed, greed_distribution.sample(rng));
         gene_scores.insert(
             GeneType::Aggression,
-            aggression_distribution.sample(&mut rng),
+            aggression_distribution.sample(rng),
         );
-        gene_scores.insert(GeneType::Social, common_distribution.sample(&mut rng));
+        gene_scores.insert(GeneType::Social, common_distribution.sample(rng));
         gene_scores.insert(
             GeneType::SelfPreservation,
-            common_distribution.sample(&mut rng),
+            common_distribution.sample(rng),
         );
-        gene_scores.insert(GeneType::Vision, vision_distribution.sample(&mut rng));
+        gene_scores.insert(GeneType::Vision, vision_distribution.sample(rng));
+        gene_scores.insert(GeneType::Stealth, common_distribution.sample(rng));
+        gene_scores.insert(GeneType::Perception, common_distribution.sample(rng));
 
         Genes {
             gene_scores: Arc::new(Mutex::new(gene_scores)),
@@ -0,0 +1,140 @@
+use std::fmt;
+
+/// The kinds of resources NPCs carry.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ResourceType {
+    Gold,
+    /// Grown on farms, agents eat it every turn.
+    Food,
+    /// Dug out of mines.
+    Ore,
+    /// Valuables taken out of treasures.
+    Loot,
+}
+
+impl ResourceType {
+    /// Every resource type, in the order they are stored and summed in.
+    pub fn all() -> [ResourceType; 4] {
+        [
+            ResourceType::Gold,
+            ResourceType::Food,
+            ResourceType::Ore,
+            ResourceType::Loot,
+        ]
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            ResourceType::Gold => 0,
+            ResourceType::Food => 1,
+            ResourceType::Ore => 2,
+            ResourceType::Loot => 3,
+        }
+    }
+
+    /// What one unit is worth to its owner, in gold.
+    /// Goods are worth less than what a merchant gets for them in a village.
+    pub fn get_value(&self) -> f64 {
+        match self {
+            ResourceType::Gold => 1.0,
+            ResourceType::Food => 0.5,
+            ResourceType::Ore => 1.0,
+            ResourceType::Loot => 1.0,
+        }
+    }
+
+    /// Gold a merchant gets for one unit in a village.
+    pub fn get_price(&self) -> u32 {
+        match self {
+            ResourceType::Gold => 1,
+            ResourceType::Food => 1,
+            ResourceType::Ore => 2,
+            ResourceType::Loot => 1,
+        }
+    }
+}
+
+impl ToString for ResourceType {
+    fn to_string(&self) -> String {
+        match self {
+            ResourceType::Gold => "Gold".to_string(),
+            ResourceType::Food => "Food".to_string(),
+            ResourceType::Ore => "Ore".to_string(),
+            ResourceType::Loot => "Loot".to_string(),
+        }
+    }
+}
+
+/// Resources an agent carries.
+/// Stored by value so that cloned game states can change them independently.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Inventory {
+    amounts: [u32; 4],
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Inventory::default()
+    }
+
+    pub fn get(&self, resource: ResourceType) -> u32 {
+        self.amounts[resource.index()]
+    }
+
+    pub fn add(&mut self, resource: ResourceType, amount: u32) {
+        self.amounts[resource.index()] += amount;
+    }
+
+    /// Removes up to `amount` of a resource and returns how much was removed.
+    pub fn remove(&mut self, resource: ResourceType, amount: u32) -> u32 {
+        let removed = amount.min(self.get(resource));
+        self.amounts[resource.index()] -= removed;
+        removed
+    }
+
+    /// Moves up to `amount` of a resource to `other` and returns how much was moved.
+    pub fn transfer(&mut self, other: &mut Inventory, resource: ResourceType, amount: u32) -> u32 {
+        let moved = self.remove(resource, amount);
+        other.add(resource, moved);
+        moved
+    }
+
+    /// Adds everything in `other` to this inventory.
+    pub fn merge(&mut self, other: &Inventory) {
+        for resource in ResourceType::all() {
+            self.add(resource, other.get(resource));
+        }
+    }
+
+    /// Takes `fraction` of every resource, rounded down, and returns what was taken.
+    pub fn take_fraction(&mut self, fraction: f32) -> Inventory {
+        let mut taken = Inventory::new();
+        for resource in ResourceType::all() {
+            let amount = (self.get(resource) as f32 * fraction.clamp(0.0, 1.0)) as u32;
+            taken.add(resource, self.remove(resource, amount));
+        }
+        taken
+    }
+
+    /// Total worth of the inventory in gold, see `ResourceType::get_value`.
+    pub fn get_value(&self) -> f64 {
+        ResourceType::all()
+            .iter()
+            .map(|resource| self.get(*resource) as f64 * resource.get_value())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.amounts.iter().all(|amount| *amount == 0)
+    }
+}
+
+impl fmt::Display for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = ResourceType::all()
+            .iter()
+            .map(|resource| format!("{} {}", resource.to_string(), self.get(*resource)))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
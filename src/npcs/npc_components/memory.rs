@@ -0,0 +1,19 @@
+use super::npc_action::NpcAction;
+
+/// Something an agent saw or heard about: `agent_id` did `action` to `target_id`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Memory {
+    pub agent_id: i32,
+    pub target_id: i32,
+    pub action: NpcAction,
+}
+
+impl Memory {
+    pub fn new(agent_id: i32, target_id: i32, action: NpcAction) -> Self {
+        Memory {
+            agent_id,
+            target_id,
+            action,
+        }
+    }
+}
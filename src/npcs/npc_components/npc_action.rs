@@ -1,18 +1,51 @@
 use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
 
 
+/// Farming and Mining hold the tile worked on. Without one the agent picks the closest
+/// Farm or Mine tile when it sets out, which is how the actions appear in the MCTS trees.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum WorkType {
-    Farming(Position),
-    Mining(Position),
+    Farming(Option<Position>),
+    Mining(Option<Position>),
+    /// Sells the goods the agent carries in a village.
     Merchant,
 }
 
+impl WorkType {
+    /// The tile type Farming and Mining are done on.
+    pub fn get_tile_type(&self) -> Option<TileType> {
+        match self {
+            WorkType::Farming(_) => Some(TileType::Farm),
+            WorkType::Mining(_) => Some(TileType::Mine),
+            WorkType::Merchant => None,
+        }
+    }
+
+    pub fn get_tile(&self) -> Option<Position> {
+        match self {
+            WorkType::Farming(tile) | WorkType::Mining(tile) => *tile,
+            WorkType::Merchant => None,
+        }
+    }
+
+    /// The same kind of work on `tile`.
+    pub fn with_tile(&self, tile: Position) -> WorkType {
+        match self {
+            WorkType::Farming(_) => WorkType::Farming(Some(tile)),
+            WorkType::Mining(_) => WorkType::Mining(Some(tile)),
+            WorkType::Merchant => WorkType::Merchant,
+        }
+    }
+}
+
 impl ToString for WorkType {
     fn to_string(&self) -> String {
         match self {
-            WorkType::Farming(position) => "Farming".to_string() + " at " + &position.to_string(),
-            WorkType::Mining(position) => "Mining".to_string() + " at " + &position.to_string(),
+            WorkType::Farming(Some(position)) => "Farming".to_string() + " at " + &position.to_string(),
+            WorkType::Farming(None) => "Farming".to_string(),
+            WorkType::Mining(Some(position)) => "Mining".to_string() + " at " + &position.to_string(),
+            WorkType::Mining(None) => "Mining".to_string(),
             WorkType::Merchant => "Merchant".to_string(),
         }
     }
@@ -31,16 +64,19 @@ pub enum NpcAction {
 }
 
 impl NpcAction {
-    /// Returns an iterator over all variants of the enum.
+    /// Returns an iterator over the actions an agent can choose.
+    /// `None` only marks the root of the MCTS trees and is left out.
     pub fn iter() -> impl Iterator<Item = NpcAction> {
-        static ACTIONS: [NpcAction; 7] = [
+        static ACTIONS: [NpcAction; 9] = [
             NpcAction::AttackAgent,
             NpcAction::AttackMonster,
             NpcAction::Steal,
             NpcAction::TreasureHunt,
             NpcAction::Rest,
             NpcAction::Talk,
-            NpcAction::None,
+            NpcAction::Work(WorkType::Farming(None)),
+            NpcAction::Work(WorkType::Mining(None)),
+            NpcAction::Work(WorkType::Merchant),
         ];
         ACTIONS.iter().copied()
     }
@@ -56,7 +92,7 @@ impl ToString for NpcAction {
             NpcAction::Rest => "Rest".to_string(),
             NpcAction::Talk => "Talk".to_string(),
             NpcAction::None => "Root".to_string(),
-            NpcAction::Work(work_type) => work_type.to_string() + &" Work ".to_string(),
+            NpcAction::Work(work_type) => work_type.to_string(),
         }
     }
 }
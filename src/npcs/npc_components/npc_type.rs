@@ -1,6 +1,6 @@
 use bevy::prelude::Component;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Component)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Component)]
 pub enum NPCType {
     Agent,
     Monster,
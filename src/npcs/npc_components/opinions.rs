@@ -1,7 +1,9 @@
-use std::{collections::HashMap, sync::{Arc, Mutex}};
+use std::collections::HashMap;
 
 
-#[derive(Clone, Debug)]
+/// Opinions of an agent about other agents, keyed by agent id.
+/// Stored by value so that cloned game states can change them independently.
+#[derive(Clone, Debug, Default)]
 pub struct Opinions {
-    pub opinion_scores: Arc<Mutex<HashMap<i32, f32>>>,
+    pub opinion_scores: HashMap<i32, f32>,
 }
@@ -1,10 +1,54 @@
 use bevy::ecs::component::Component;
+use rand::Rng;
+use serde::Deserialize;
 
+/// Reward of a treasure that was not given a value.
+pub const DEFAULT_TREASURE_REWARD: u32 = 100;
+/// Reward an agent takes out of a treasure per turn of work, unless the world file says otherwise.
+pub const DEFAULT_LOOT_PER_TURN: u32 = 25;
 
+/// How treasures are valued, looted and replaced, read from the `[treasure]` table of a world file.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TreasureConfig {
+    /// A new treasure is worth a uniform draw between `min_value` and `max_value`.
+    pub min_value: u32,
+    pub max_value: u32,
+    pub loot_per_turn: u32,
+    /// Turns after a treasure is emptied before a new one can appear.
+    pub respawn_delay: u32,
+    /// Chance per turn, once the delay is over, that a missing treasure reappears.
+    pub respawn_chance: f32,
+}
+
+impl Default for TreasureConfig {
+    fn default() -> Self {
+        TreasureConfig {
+            min_value: 50,
+            max_value: 150,
+            loot_per_turn: DEFAULT_LOOT_PER_TURN,
+            respawn_delay: 5,
+            respawn_chance: 0.2,
+        }
+    }
+}
+
+impl TreasureConfig {
+    /// A new treasure with a random value.
+    pub fn roll_treasure<R: Rng>(&self, rng: &mut R) -> Treasure {
+        let mut treasure = Treasure::new_treasure();
+        treasure.set_reward(rng.gen_range(self.min_value..=self.max_value));
+        treasure.set_loot_per_turn(self.loot_per_turn);
+        treasure
+    }
+}
+
+/// A chest agents empty over several turns of work.
 #[derive(Clone, Component)]
 pub struct Treasure {
     id: i32,
     reward: u32,
+    loot_per_turn: u32,
 }
 
 static mut T_COUNTER: i32 = 0;
@@ -18,7 +62,8 @@ impl Treasure {
         }
         Treasure {
             id: unsafe { T_COUNTER },
-            reward: 100,
+            reward: DEFAULT_TREASURE_REWARD,
+            loot_per_turn: DEFAULT_LOOT_PER_TURN,
         }
     }
 
@@ -42,6 +87,28 @@ impl Treasure {
         self.reward = self.reward.saturating_sub(reward);
     }
 
+    // Function to set the reward taken out per turn of work
+    pub fn set_loot_per_turn(&mut self, loot_per_turn: u32) {
+        self.loot_per_turn = loot_per_turn;
+    }
+
+    // Function to get the reward taken out per turn of work
+    pub fn get_loot_per_turn(&self) -> u32 {
+        self.loot_per_turn
+    }
+
+    // Function to take one turn of loot out of the treasure, returns the amount taken
+    pub fn loot(&mut self) -> u32 {
+        let amount = self.loot_per_turn.max(1).min(self.reward);
+        self.reward -= amount;
+        amount
+    }
+
+    // Function to check if the treasure has been emptied
+    pub fn is_depleted(&self) -> bool {
+        self.reward == 0
+    }
+
     // Function to get the id of the treasure
     pub fn get_id(&self) -> i32 {
         self.id
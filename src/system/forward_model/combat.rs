@@ -0,0 +1,204 @@
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::agent::{flight_or_fight, Agent};
+use crate::npcs::npc_components::gene_type::GeneType;
+use crate::npcs::npc_components::inventory::{Inventory, ResourceType};
+use crate::npcs::npc_components::memory::Memory;
+use crate::npcs::npc_components::npc_action::NpcAction;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::npc_components::npc_type::NPCType;
+use crate::npcs::npc_components::target::Target;
+
+use super::events::{GameEvent, GameEvents};
+use super::game_state::{AgentState, GameState};
+use super::rules::is_next_to_target;
+
+/// Damage of one hit by an agent with no Aggression, fighting alone.
+const BASE_DAMAGE: f32 = 10.0;
+/// Extra damage for every other agent hitting the same target this turn.
+const GROUP_BONUS: f32 = 0.25;
+/// Monsters have thicker hides than agents.
+const MONSTER_DAMAGE_SCALE: f32 = 0.75;
+/// Gold for killing a monster, on top of the loot it carried.
+const MONSTER_KILL_REWARD: u32 = 50;
+/// Gold for killing an agent, on top of half of everything it carried.
+const AGENT_KILL_REWARD: u32 = 20;
+/// Part of its inventory a killed agent loses to its killer.
+pub const KILL_SPOILS: f32 = 0.5;
+
+/// Damage one hit of `attacker` does to a target of `target` type,
+/// with `group_size` agents (the attacker included) hitting it this turn.
+pub fn attack_damage(attacker: &Agent, group_size: usize, target: Target) -> u32 {
+    let aggression = attacker.get_genes().return_type_score(GeneType::Aggression);
+    let group = 1.0 + GROUP_BONUS * group_size.saturating_sub(1) as f32;
+    let scale = if target == Target::Monster {
+        MONSTER_DAMAGE_SCALE
+    } else {
+        1.0
+    };
+    ((BASE_DAMAGE * (0.5 + aggression) * group * scale).round() as u32).max(1)
+}
+
+/// The NPC an agent is hitting this turn, if it is fighting.
+pub fn combat_target(agent: &Agent) -> Option<(Target, i32)> {
+    match (agent.get_status(), agent.get_action()) {
+        (Status::Attacking, NpcAction::AttackAgent) => {
+            Some((Target::Agent, agent.get_agent_target_id()))
+        }
+        (Status::Attacking, NpcAction::AttackMonster) => {
+            Some((Target::Monster, agent.get_monster_target_id()))
+        }
+        (Status::Retaliating, _) => match agent.get_retaliation_target() {
+            Target::Agent | Target::Monster => Some((
+                agent.get_retaliation_target(),
+                agent.get_retaliation_target_id(),
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Resolves the hit of the agent at `index`. The victim loses energy and dies at zero,
+/// an agent that survives either fights back or flees to the closest village.
+pub fn resolve_attack(state: &mut GameState, index: usize) -> GameEvents {
+    let mut events = GameEvents::new();
+    let (target, target_id) = match combat_target(&state.agents[index].agent) {
+        Some(target) => target,
+        None => return events,
+    };
+    let agent_id = state.agents[index].agent.get_id();
+    let position = state.agents[index].npc.position;
+
+    let target_position = match target {
+        Target::Agent => state
+            .agents
+            .iter()
+            .find(|other| {
+                other.agent.get_id() == target_id && other.agent.get_status() != Status::Dead
+            })
+            .map(|other| other.npc.position),
+        _ => state
+            .monsters
+            .iter()
+            .find(|other| {
+                other.monster.get_id() == target_id && other.monster.get_status() != Status::Dead
+            })
+            .map(|other| other.npc.position),
+    };
+    let target_position = match target_position {
+        Some(target_position) if is_next_to_target(position, target_position, 1) => target_position,
+        _ => return events,
+    };
+
+    // Everyone next to the target and hitting it counts towards the group
+    let group_size = state
+        .agents
+        .iter()
+        .filter(|other| {
+            combat_target(&other.agent) == Some((target, target_id))
+                && is_next_to_target(other.npc.position, target_position, 1)
+        })
+        .count();
+    let damage = attack_damage(&state.agents[index].agent, group_size, target);
+    events.push(GameEvent::Attacked {
+        agent_id,
+        target,
+        target_id,
+        damage,
+    });
+
+    let spoils = match target {
+        Target::Agent => {
+            // Both remember the fight and can tell others about it
+            let memory = Memory::new(agent_id, target_id, NpcAction::AttackAgent);
+            state.agents[index].agent.add_to_memory(memory);
+            let world = state.world.clone();
+            let victim = state
+                .agents
+                .iter_mut()
+                .find(|other| other.agent.get_id() == target_id)
+                .unwrap();
+            victim.agent.add_to_memory(memory);
+            victim.npc.energy = victim.npc.energy.saturating_sub(damage);
+
+            if victim.npc.energy == 0 {
+                victim.agent.set_status(Status::Dead);
+                victim.agent.set_path(Vec::new());
+                let mut spoils = victim.agent.get_inventory_mut().take_fraction(KILL_SPOILS);
+                spoils.add(ResourceType::Gold, AGENT_KILL_REWARD);
+                Some(spoils)
+            } else {
+                react_to_attack(victim, &world, Target::Agent, agent_id);
+                None
+            }
+        }
+        _ => {
+            let victim = state
+                .monsters
+                .iter_mut()
+                .find(|other| other.monster.get_id() == target_id)
+                .unwrap();
+            victim.npc.energy = victim.npc.energy.saturating_sub(damage);
+            // The monster goes after whoever hurt it last
+            victim.monster.set_target_id(agent_id);
+            victim.monster.set_status(Status::Attacking);
+
+            if victim.npc.energy == 0 {
+                victim.monster.set_status(Status::Dead);
+                let mut spoils = Inventory::new();
+                spoils.add(ResourceType::Gold, MONSTER_KILL_REWARD);
+                spoils.add(ResourceType::Loot, victim.monster.get_reward());
+                Some(spoils)
+            } else {
+                None
+            }
+        }
+    };
+
+    if let Some(spoils) = spoils {
+        state.agents[index].agent.get_inventory_mut().merge(&spoils);
+        events.push(GameEvent::Killed {
+            agent_id,
+            target,
+            target_id,
+            reward: spoils.get_value() as u32,
+        });
+    }
+
+    events
+}
+
+/// Lets an agent that survived a hit decide between fighting back and fleeing to the closest village.
+/// Agents already in a fight keep going.
+pub fn react_to_attack(
+    victim: &mut AgentState,
+    world: &GameWorld,
+    attacker: Target,
+    attacker_id: i32,
+) {
+    let status = victim.agent.get_status();
+    if status == Status::Retaliating || status == Status::Fleeing || status == Status::Attacking {
+        return;
+    }
+
+    victim.agent.set_retaliation_target(attacker);
+    victim.agent.set_retaliation_target_id(attacker_id);
+    let npc_type = if attacker == Target::Monster {
+        NPCType::Monster
+    } else {
+        NPCType::Agent
+    };
+    if flight_or_fight(&victim.agent, npc_type, Some(attacker_id)) {
+        if let Some((village, path)) =
+            world.path_to_closest_tiletype(victim.npc.position, TileType::Village)
+        {
+            victim.agent.set_path(path);
+            victim.agent.set_tile_target(Some(village));
+            victim.agent.set_status(Status::Fleeing);
+            return;
+        }
+    }
+    victim.agent.set_path(Vec::new());
+    victim.agent.set_status(Status::Retaliating);
+}
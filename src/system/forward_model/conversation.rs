@@ -0,0 +1,118 @@
+use crate::gameworld::position::Position;
+use crate::npcs::npc_components::gene_type::GeneType;
+use crate::npcs::npc_components::memory::Memory;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::npc_components::opinions::Opinions;
+
+use super::events::{GameEvent, GameEvents};
+use super::game_state::GameState;
+use super::rules::is_next_to_target;
+
+/// Turns a conversation lasts before both agents are free to choose a new action.
+pub const TALK_TURNS: u32 = 3;
+/// Opinion a listener loses of an agent it hears has attacked or stolen from someone.
+const GOSSIP_OPINION_LOSS: f32 = 0.1;
+/// Opinion change per turn of talking between two agents who are as social as can be.
+/// Agents who are not social at all like each other less the longer they talk.
+const RAPPORT_SCALE: f32 = 0.1;
+
+/// What a talking agent has to say, taken before anyone listens.
+struct Talker {
+    index: usize,
+    id: i32,
+    position: Position,
+    social: f32,
+    opinions: Opinions,
+    memory: Vec<Memory>,
+}
+
+/// Resolves one turn of talking for every talking agent. Each one listens to the talking agent
+/// next to it that it likes most: it takes on some of its opinions, hears the gossip it has not
+/// heard yet and likes it more or less depending on how social both of them are.
+/// Everyone hears what was said at the start of the turn, so the order of the agents does not
+/// matter, and a conversation ends for both agents on the same turn.
+pub fn resolve_talks(state: &mut GameState) -> GameEvents {
+    let mut events = GameEvents::new();
+    let talkers: Vec<Talker> = state
+        .agents
+        .iter()
+        .enumerate()
+        .filter(|(_, other)| other.agent.get_status() == Status::Talking)
+        .map(|(index, other)| Talker {
+            index,
+            id: other.agent.get_id(),
+            position: other.npc.position,
+            social: other.agent.get_genes().return_type_score(GeneType::Social),
+            opinions: other.agent.get_opinions().clone(),
+            memory: other.agent.get_memory().clone(),
+        })
+        .collect();
+
+    let mut finished = Vec::new();
+    for talker in talkers.iter() {
+        let listener = &mut state.agents[talker.index].agent;
+        let partner = talkers
+            .iter()
+            .filter(|other| other.id != talker.id && is_next_to_target(talker.position, other.position, 1))
+            // Ties go to the lowest id so the result does not depend on the agent order
+            .max_by(|a, b| {
+                listener
+                    .get_agent_opinion(a.id)
+                    .partial_cmp(&listener.get_agent_opinion(b.id))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| b.id.cmp(&a.id))
+            });
+        let partner = match partner {
+            Some(partner) => partner,
+            None => {
+                // Nobody to talk to
+                listener.set_talk_turns(0);
+                listener.set_status(Status::Idle);
+                continue;
+            }
+        };
+
+        // What the partner thinks of the listener does not change the listener's mind about itself
+        let mut opinions = partner.opinions.clone();
+        opinions.opinion_scores.remove(&talker.id);
+        listener.influence_opinions(opinions);
+
+        // Gossip, oldest first so the listener's memory keeps the order things happened in.
+        // Nobody gossips about itself and the listener knows what it did.
+        let mut gossip = 0;
+        for memory in partner.memory.iter().rev() {
+            if memory.agent_id == partner.id
+                || memory.agent_id == talker.id
+                || listener.remembers(memory)
+            {
+                continue;
+            }
+            listener.add_to_memory(*memory);
+            listener.modify_opinion(memory.agent_id, -GOSSIP_OPINION_LOSS);
+            gossip += 1;
+        }
+
+        listener.modify_opinion(partner.id, RAPPORT_SCALE * (talker.social + partner.social - 1.0));
+        events.push(GameEvent::Talked {
+            agent_id: talker.id,
+            partner_id: partner.id,
+            gossip,
+        });
+
+        let talk_turns = listener.get_talk_turns() + 1;
+        listener.set_talk_turns(talk_turns);
+        if talk_turns >= TALK_TURNS {
+            finished.push(talker.index);
+            finished.push(partner.index);
+        }
+    }
+
+    // Talked enough, both agents are free to choose a new action
+    for index in finished {
+        let agent = &mut state.agents[index].agent;
+        agent.set_talk_turns(0);
+        agent.set_status(Status::Idle);
+    }
+
+    events
+}
@@ -0,0 +1,227 @@
+use crate::gameworld::tile_types::TileType;
+use crate::npcs::npc_components::inventory::{Inventory, ResourceType};
+use crate::npcs::npc_components::npc_action::{NpcAction, WorkType};
+use crate::npcs::npc_components::npc_status::Status;
+
+use super::events::{GameEvent, GameEvents};
+use super::game_state::{AgentState, GameState};
+use super::rules::{is_next_to_target, set_finish};
+
+/// Food one agent farming alone produces per turn.
+const FARM_YIELD: f32 = 4.0;
+/// Ore one agent mining alone produces per turn. Mines are rarer and further out.
+const MINE_YIELD: f32 = 6.0;
+/// Turns of farming or mining before the agent takes its goods back to a village.
+pub const WORK_TURNS: u32 = 5;
+/// Units a merchant sells per turn.
+const SALES_PER_TURN: u32 = 10;
+/// Food a merchant keeps for itself instead of selling it.
+pub const FOOD_RESERVE: u32 = 5;
+/// Food every agent starts with.
+pub const STARTING_FOOD: u32 = 10;
+/// Energy a fed agent gets back every turn.
+const FED_RECOVERY: u32 = 1;
+/// Energy an agent without food loses every turn.
+const HUNGER_DAMAGE: u32 = 2;
+
+/// The resource a kind of work produces.
+pub fn produced_resource(work_type: WorkType) -> Option<ResourceType> {
+    match work_type {
+        WorkType::Farming(_) => Some(ResourceType::Food),
+        WorkType::Mining(_) => Some(ResourceType::Ore),
+        WorkType::Merchant => None,
+    }
+}
+
+/// Units every one of `workers` agents sharing a tile gets per turn.
+/// The tile yields more with more agents on it, but each of them gets less.
+pub fn production(work_type: WorkType, workers: usize) -> u32 {
+    let base = match work_type {
+        WorkType::Farming(_) => FARM_YIELD,
+        WorkType::Mining(_) => MINE_YIELD,
+        WorkType::Merchant => return 0,
+    };
+    ((base / (workers.max(1) as f32).sqrt()).round() as u32).max(1)
+}
+
+/// Resolves one turn of work of the agent at `index`: producing food or ore next to a Farm or Mine,
+/// or trading with the agents next to it and selling what it carries for gold in a village.
+pub fn resolve_work(state: &mut GameState, index: usize) -> GameEvents {
+    let mut events = GameEvents::new();
+    let work_type = match state.agents[index].agent.get_action() {
+        NpcAction::Work(work_type) => work_type,
+        _ => return events,
+    };
+    let agent_id = state.agents[index].agent.get_id();
+    let position = state.agents[index].npc.position;
+
+    match work_type {
+        WorkType::Farming(Some(tile)) | WorkType::Mining(Some(tile)) => {
+            if !is_next_to_target(position, tile, 1) {
+                return events;
+            }
+            // Everyone working the same tile shares its yield
+            let workers = state
+                .agents
+                .iter()
+                .filter(|other| {
+                    other.agent.get_status() == Status::Working
+                        && matches!(other.agent.get_action(), NpcAction::Work(other_work) if other_work.get_tile() == Some(tile))
+                        && is_next_to_target(other.npc.position, tile, 1)
+                })
+                .count();
+            let amount = production(work_type, workers);
+            let resource = produced_resource(work_type).unwrap();
+
+            let world = state.world.clone();
+            let agent_state = &mut state.agents[index];
+            agent_state.agent.get_inventory_mut().add(resource, amount);
+            events.push(GameEvent::Produced {
+                agent_id,
+                tile,
+                resource,
+                amount,
+            });
+
+            let work_turns = agent_state.agent.get_work_turns() + 1;
+            agent_state.agent.set_work_turns(work_turns);
+            if work_turns >= WORK_TURNS {
+                // Done for now, carry the goods back to a village
+                agent_state.agent.set_work_turns(0);
+                set_finish(&mut agent_state.agent, &agent_state.npc, &world);
+            }
+        }
+        WorkType::Merchant => {
+            if state.world.get_tile_type(position.x, position.y) != Some(TileType::Village) {
+                return events;
+            }
+            events.extend(trade(state, index));
+            let agent = &mut state.agents[index].agent;
+            let (goods, reward) = sell(agent.get_inventory_mut());
+            if goods > 0 {
+                agent.add_reward(reward);
+                events.push(GameEvent::Sold {
+                    agent_id,
+                    goods,
+                    reward,
+                });
+            } else {
+                // Nothing left to sell, the agent is free to choose a new action
+                agent.set_status(Status::Idle);
+            }
+        }
+        // The tile is chosen before the agent starts working
+        _ => {}
+    }
+
+    events
+}
+
+/// The merchant at `index` trades with every agent next to it: it buys their ore and loot with its gold
+/// at what the goods are worth, and sells food to the ones with less than `FOOD_RESERVE`.
+/// Goods and gold move between the two inventories, nothing is created.
+fn trade(state: &mut GameState, index: usize) -> GameEvents {
+    let mut events = GameEvents::new();
+    let merchant_id = state.agents[index].agent.get_id();
+    let position = state.agents[index].npc.position;
+
+    for other in 0..state.agents.len() {
+        let partner = &state.agents[other];
+        if other == index
+            || partner.agent.get_status() == Status::Dead
+            || partner.agent.get_action() == NpcAction::Work(WorkType::Merchant)
+            || !is_next_to_target(position, partner.npc.position, 1)
+        {
+            continue;
+        }
+        let partner_id = partner.agent.get_id();
+        let mut partner_inventory = partner.agent.get_inventory().clone();
+        let merchant_inventory = state.agents[index].agent.get_inventory_mut();
+
+        // Buy the partner's goods, as many as the merchant can pay for
+        for resource in [ResourceType::Ore, ResourceType::Loot] {
+            let price = resource.get_value() as u32;
+            let amount = partner_inventory
+                .get(resource)
+                .min(merchant_inventory.get(ResourceType::Gold) / price);
+            if amount == 0 {
+                continue;
+            }
+            partner_inventory.transfer(merchant_inventory, resource, amount);
+            let gold = merchant_inventory.transfer(&mut partner_inventory, ResourceType::Gold, amount * price);
+            events.push(GameEvent::Traded {
+                agent_id: partner_id,
+                partner_id: merchant_id,
+                resource,
+                amount,
+                gold,
+            });
+        }
+
+        // Sell food to a partner running low, keeping the merchant's own reserve
+        let price = ResourceType::Food.get_price();
+        let amount = FOOD_RESERVE
+            .saturating_sub(partner_inventory.get(ResourceType::Food))
+            .min(merchant_inventory.get(ResourceType::Food).saturating_sub(FOOD_RESERVE))
+            .min(partner_inventory.get(ResourceType::Gold) / price);
+        if amount > 0 {
+            merchant_inventory.transfer(&mut partner_inventory, ResourceType::Food, amount);
+            let gold = partner_inventory.transfer(merchant_inventory, ResourceType::Gold, amount * price);
+            events.push(GameEvent::Traded {
+                agent_id: merchant_id,
+                partner_id,
+                resource: ResourceType::Food,
+                amount,
+                gold,
+            });
+        }
+
+        *state.agents[other].agent.get_inventory_mut() = partner_inventory;
+    }
+
+    events
+}
+
+/// Sells up to `SALES_PER_TURN` units out of an inventory, the most valuable first,
+/// keeping `FOOD_RESERVE` food. Returns the units sold and the gold they made.
+fn sell(inventory: &mut Inventory) -> (u32, u32) {
+    let mut left = SALES_PER_TURN;
+    let mut gold = 0;
+    for resource in [ResourceType::Ore, ResourceType::Loot, ResourceType::Food] {
+        let keep = if resource == ResourceType::Food {
+            FOOD_RESERVE
+        } else {
+            0
+        };
+        let amount = inventory.get(resource).saturating_sub(keep).min(left);
+        inventory.remove(resource, amount);
+        gold += amount * resource.get_price();
+        left -= amount;
+    }
+    (SALES_PER_TURN - left, gold)
+}
+
+/// The agent eats one food. Fed agents slowly get their energy back, hungry ones lose some,
+/// hunger wears agents down but never kills them.
+pub fn eat(agent_state: &mut AgentState) -> Option<GameEvent> {
+    if agent_state.agent.get_status() == Status::Dead {
+        return None;
+    }
+    let npc = &mut agent_state.npc;
+    if agent_state
+        .agent
+        .get_inventory_mut()
+        .remove(ResourceType::Food, 1)
+        > 0
+    {
+        npc.energy = (npc.energy + FED_RECOVERY).min(npc.max_energy);
+        return None;
+    }
+
+    let energy_lost = HUNGER_DAMAGE.min(npc.energy.saturating_sub(1));
+    npc.energy -= energy_lost;
+    Some(GameEvent::Hungry {
+        agent_id: agent_state.agent.get_id(),
+        energy_lost,
+    })
+}
@@ -0,0 +1,167 @@
+use crate::gameworld::position::Position;
+use crate::npcs::npc_components::inventory::ResourceType;
+use crate::npcs::npc_components::npc_action::NpcAction;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::npc_components::target::Target;
+use crate::system::pathfinding::reservation::Occupant;
+
+/// Something that happened while stepping the `GameState`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    ActionChosen {
+        agent_id: i32,
+        action: NpcAction,
+    },
+    StatusChanged {
+        agent_id: i32,
+        from: Status,
+        to: Status,
+    },
+    Moved {
+        agent_id: i32,
+        from: Position,
+        to: Position,
+    },
+    /// The next tile of the agent's path was taken.
+    Waited {
+        agent_id: i32,
+        position: Position,
+        blocked_by: Occupant,
+    },
+    /// The agent and `other_id` wanted to swap tiles.
+    DeadlockDetected {
+        agent_id: i32,
+        other_id: i32,
+    },
+    /// The agent hit an agent or a monster.
+    Attacked {
+        agent_id: i32,
+        target: Target,
+        target_id: i32,
+        damage: u32,
+    },
+    /// The agent's hit left the target without energy.
+    Killed {
+        agent_id: i32,
+        target: Target,
+        target_id: i32,
+        reward: u32,
+    },
+    MonsterMoved {
+        monster_id: i32,
+        from: Position,
+        to: Position,
+    },
+    /// A monster hit an agent.
+    MonsterAttacked {
+        monster_id: i32,
+        agent_id: i32,
+        damage: u32,
+    },
+    KilledByMonster {
+        monster_id: i32,
+        agent_id: i32,
+    },
+    /// The agent took `reward` out of a treasure, either by looting it or as a follower of the looter.
+    TreasureCollected {
+        agent_id: i32,
+        treasure_id: i32,
+        reward: u32,
+    },
+    /// The agent took the last of a treasure, which disappears.
+    TreasureDepleted {
+        agent_id: i32,
+        treasure_id: i32,
+    },
+    /// The agent farmed or mined on `tile`.
+    Produced {
+        agent_id: i32,
+        tile: Position,
+        resource: ResourceType,
+        amount: u32,
+    },
+    /// The agent sold `goods` units in a village for `reward` gold.
+    Sold {
+        agent_id: i32,
+        goods: u32,
+        reward: u32,
+    },
+    /// The agent sold `amount` of a resource to `partner_id` for `gold`.
+    Traded {
+        agent_id: i32,
+        partner_id: i32,
+        resource: ResourceType,
+        amount: u32,
+        gold: u32,
+    },
+    /// The agent took `amount` gold and loot from `target_id`.
+    Stole {
+        agent_id: i32,
+        target_id: i32,
+        amount: u32,
+    },
+    /// `target_id` noticed the agent stealing from it, whether or not it got away with anything.
+    TheftDetected {
+        agent_id: i32,
+        target_id: i32,
+        stolen: bool,
+    },
+    /// The agent listened to `partner_id` and heard `gossip` things it did not know yet.
+    Talked {
+        agent_id: i32,
+        partner_id: i32,
+        gossip: u32,
+    },
+    /// The agent had no food left to eat.
+    Hungry {
+        agent_id: i32,
+        energy_lost: u32,
+    },
+    EnergyRecovered {
+        agent_id: i32,
+        amount: u32,
+    },
+}
+
+/// Events produced by one call to `GameState::step`, in the order they happened.
+#[derive(Clone, Debug, Default)]
+pub struct GameEvents(pub Vec<GameEvent>);
+
+impl GameEvents {
+    pub fn new() -> Self {
+        GameEvents(Vec::new())
+    }
+
+    pub fn push(&mut self, event: GameEvent) {
+        self.0.push(event);
+    }
+
+    pub fn extend(&mut self, other: GameEvents) {
+        self.0.extend(other.0);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GameEvent> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Total reward the events gave to an agent.
+    pub fn reward_for(&self, agent_id: i32) -> f64 {
+        self.iter()
+            .map(|event| match event {
+                GameEvent::TreasureCollected { agent_id: id, reward, .. }
+                | GameEvent::Killed { agent_id: id, reward, .. }
+                | GameEvent::Sold { agent_id: id, reward, .. }
+                | GameEvent::Stole { agent_id: id, amount: reward, .. }
+                    if *id == agent_id =>
+                {
+                    *reward as f64
+                }
+                _ => 0.0,
+            })
+            .sum()
+    }
+}
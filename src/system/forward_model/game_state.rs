@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use crate::gameworld::position::Position;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::agent::Agent;
+use crate::npcs::monster::Monster;
+use crate::npcs::npc_components::npc_action::NpcAction;
+use crate::npcs::npc_components::npc_base::NPCBase;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::treasure::Treasure;
+use crate::system::pathfinding::danger::DangerConfig;
+use crate::system::pathfinding::reservation::{MoveOutcome, Occupant, ReservationTable};
+
+use super::conversation;
+use super::economy;
+use super::events::{GameEvent, GameEvents};
+use super::monster_rules;
+use super::rules;
+
+/// The parts of an `NPCBase` the game rules need, without any rendering data.
+#[derive(Clone, Debug)]
+pub struct NpcSnapshot {
+    pub position: Position,
+    pub energy: u32,
+    pub max_energy: u32,
+}
+
+impl NpcSnapshot {
+    pub fn new(position: Position, energy: u32, max_energy: u32) -> Self {
+        NpcSnapshot {
+            position,
+            energy,
+            max_energy,
+        }
+    }
+
+    pub fn from_base(npc_base: &NPCBase) -> Self {
+        NpcSnapshot::new(npc_base.get_position(), npc_base.get_energy(), npc_base.get_max_energy())
+    }
+}
+
+#[derive(Clone)]
+pub struct AgentState {
+    pub agent: Agent,
+    pub npc: NpcSnapshot,
+}
+
+/// What an agent can see of another agent when it decides on its turn.
+/// Much cheaper to copy for every step than the whole `AgentState`.
+#[derive(Clone, Debug)]
+pub struct AgentView {
+    pub id: i32,
+    pub status: Status,
+    pub action: NpcAction,
+    pub npc: NpcSnapshot,
+    /// Whether it carries anything worth stealing.
+    pub has_goods: bool,
+}
+
+impl AgentView {
+    pub fn from_state(state: &AgentState) -> Self {
+        AgentView {
+            id: state.agent.get_id(),
+            status: state.agent.get_status(),
+            action: state.agent.get_action(),
+            npc: state.npc.clone(),
+            has_goods: !state.agent.get_inventory().is_empty(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MonsterState {
+    pub monster: Monster,
+    pub npc: NpcSnapshot,
+}
+
+#[derive(Clone)]
+pub struct TreasureState {
+    pub treasure: Treasure,
+    pub npc: NpcSnapshot,
+}
+
+/// Headless copy of the game that can be stepped without Bevy.
+/// The ECS systems call into the same rules, and MCTS rollouts clone it to look ahead.
+/// The world is shared between clones since the rules never change tiles.
+#[derive(Clone)]
+pub struct GameState {
+    pub world: Arc<GameWorld>,
+    pub agents: Vec<AgentState>,
+    pub monsters: Vec<MonsterState>,
+    pub treasures: Vec<TreasureState>,
+    /// Where the player stands, agents cannot walk through it.
+    pub player: Option<Position>,
+    pub turn: u32,
+    /// How agents weigh the danger around monsters when they plan a path.
+    pub danger: DangerConfig,
+}
+
+impl GameState {
+    pub fn new(world: Arc<GameWorld>) -> Self {
+        GameState {
+            world,
+            agents: Vec::new(),
+            monsters: Vec::new(),
+            treasures: Vec::new(),
+            player: None,
+            turn: 0,
+            danger: DangerConfig::default(),
+        }
+    }
+
+    pub fn add_agent(&mut self, agent: Agent, npc: NpcSnapshot) {
+        self.agents.push(AgentState { agent, npc });
+    }
+
+    pub fn add_monster(&mut self, monster: Monster, npc: NpcSnapshot) {
+        self.monsters.push(MonsterState { monster, npc });
+    }
+
+    pub fn add_treasure(&mut self, treasure: Treasure, npc: NpcSnapshot) {
+        self.treasures.push(TreasureState { treasure, npc });
+    }
+
+    pub fn get_agent(&self, agent_id: i32) -> Option<&AgentState> {
+        self.agents.iter().find(|state| state.agent.get_id() == agent_id)
+    }
+
+    pub fn get_agent_mut(&mut self, agent_id: i32) -> Option<&mut AgentState> {
+        self.agents.iter_mut().find(|state| state.agent.get_id() == agent_id)
+    }
+
+    /// Advances the game by one turn, in the order of the ExecutionPhase systems:
+    /// agents act and eat, then monsters take their turn, then agents move.
+    /// `actions` assigns a new action to idle agents; every other agent carries on with what it was doing.
+    pub fn step(&mut self, actions: &[(i32, NpcAction)]) -> GameEvents {
+        let mut events = GameEvents::new();
+
+        for (agent_id, action) in actions {
+            if let Some(state) = self.get_agent_mut(*agent_id) {
+                if state.agent.get_status() == Status::Idle {
+                    state.agent.set_action(*action);
+                    events.push(GameEvent::ActionChosen {
+                        agent_id: *agent_id,
+                        action: *action,
+                    });
+                }
+            }
+        }
+
+        events.extend(self.update_agents());
+        events.extend(self.consume_food());
+        events.extend(self.update_monsters());
+        events.extend(self.move_agents());
+        self.turn += 1;
+        events
+    }
+
+    /// Runs the status machine of every agent and resolves the actions they are performing.
+    pub fn update_agents(&mut self) -> GameEvents {
+        let mut events = GameEvents::new();
+
+        // Every agent decides based on the state at the start of the update
+        let views: Vec<AgentView> = self.agents.iter().map(AgentView::from_state).collect();
+        for state in self.agents.iter_mut() {
+            let before = state.agent.get_status();
+            rules::update_agent(
+                &mut state.agent,
+                &state.npc,
+                &self.world,
+                &views,
+                &self.monsters,
+                &self.treasures,
+                &self.danger,
+            );
+            let after = state.agent.get_status();
+            if before != after {
+                events.push(GameEvent::StatusChanged {
+                    agent_id: state.agent.get_id(),
+                    from: before,
+                    to: after,
+                });
+            }
+        }
+
+        for index in 0..self.agents.len() {
+            events.extend(rules::resolve_action(self, index));
+        }
+        events.extend(conversation::resolve_talks(self));
+
+        events
+    }
+
+    /// Runs every monster's turn, in order. Monsters move on their own, before the agents do.
+    pub fn update_monsters(&mut self) -> GameEvents {
+        let mut events = GameEvents::new();
+        for index in 0..self.monsters.len() {
+            events.extend(monster_rules::update_monster(self, index));
+        }
+        events
+    }
+
+    /// Every living agent eats, see `economy::eat`.
+    pub fn consume_food(&mut self) -> GameEvents {
+        let mut events = GameEvents::new();
+        for state in self.agents.iter_mut() {
+            if let Some(event) = economy::eat(state) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// Moves every travelling agent one tile along its path. Agents whose next tile is taken
+    /// wait or plan around it, see `ReservationTable::resolve`.
+    pub fn move_agents(&mut self) -> GameEvents {
+        let mut events = GameEvents::new();
+        let table = self.reservation_table();
+        let outcomes = table.resolve(&self.world);
+
+        for state in self.agents.iter_mut() {
+            let event = match outcomes.get(&state.agent.get_id()) {
+                Some(MoveOutcome::Move) => rules::move_agent(&mut state.agent, &mut state.npc),
+                Some(outcome) => rules::handle_blocked_move(
+                    &mut state.agent,
+                    &state.npc,
+                    &self.world,
+                    &table,
+                    *outcome,
+                ),
+                None => None,
+            };
+            if let Some(event) = event {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// The tiles of every NPC still alive and the next steps of the travelling agents.
+    pub fn reservation_table(&self) -> ReservationTable {
+        let mut table = ReservationTable::new();
+        for state in self.agents.iter() {
+            if state.agent.get_status() != Status::Dead {
+                table.occupy(state.npc.position, Occupant::Agent(state.agent.get_id()));
+            }
+        }
+        for state in self.monsters.iter() {
+            if state.monster.get_status() != Status::Dead {
+                table.occupy(state.npc.position, Occupant::Monster(state.monster.get_id()));
+            }
+        }
+        if let Some(position) = self.player {
+            table.occupy(position, Occupant::Player);
+        }
+
+        for state in self.agents.iter() {
+            if let Some(path) = rules::travelling_path(&state.agent) {
+                table.request(state.agent.get_id(), state.npc.position, &path);
+            }
+        }
+        table
+    }
+}
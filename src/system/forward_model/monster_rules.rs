@@ -0,0 +1,220 @@
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::npc_components::target::Target;
+use crate::system::pathfinding::pathfinding_calculation::a_star_search;
+
+use super::combat::{react_to_attack, KILL_SPOILS};
+use super::events::{GameEvent, GameEvents};
+use super::game_state::GameState;
+use super::rules::is_next_to_target;
+
+fn is_village(world: &GameWorld, position: Position) -> bool {
+    world.get_tile_type(position.x, position.y) == Some(TileType::Village)
+}
+
+/// Path for a monster, which never sets foot in a village.
+fn monster_path(world: &GameWorld, start: Position, goal: Position) -> Vec<Position> {
+    a_star_search(world, start, goal, &|position| {
+        if is_village(world, position) {
+            None
+        } else {
+            Some(0.0)
+        }
+    })
+}
+
+/// The corners of the monster's patrol: its spawn moved by the patrol radius in each direction.
+fn patrol_points(world: &GameWorld, home: Position, radius: i32) -> Vec<Position> {
+    [(0, -radius), (radius, 0), (0, radius), (-radius, 0)]
+        .iter()
+        .map(|(dx, dy)| Position::new(home.x + dx, home.y + dy))
+        .filter(|position| {
+            world
+                .get_tile_type(position.x, position.y)
+                .map_or(false, |tile_type| {
+                    tile_type.get_travel_weight() > 0.0 && tile_type != TileType::Village
+                })
+        })
+        .collect()
+}
+
+/// Whether the agent at `agent_index` is a valid target for the monster at `index`:
+/// alive, outside the villages and not further from the monster's spawn than its leash.
+fn can_hunt(state: &GameState, index: usize, agent_index: usize) -> bool {
+    let monster = &state.monsters[index].monster;
+    let agent = &state.agents[agent_index];
+    agent.agent.get_status() != Status::Dead
+        && !is_village(&state.world, agent.npc.position)
+        && is_next_to_target(
+            monster.get_start_position(),
+            agent.npc.position,
+            monster.get_stats().leash_radius,
+        )
+}
+
+/// Takes one step towards `goal` if the next tile is free. Returns whether the monster moved.
+fn step_towards(
+    state: &mut GameState,
+    index: usize,
+    goal: Position,
+    events: &mut GameEvents,
+) -> bool {
+    let from = state.monsters[index].npc.position;
+    if from == goal {
+        return false;
+    }
+    let next = match monster_path(&state.world, from, goal).first() {
+        Some(next) => *next,
+        None => return false,
+    };
+
+    // One NPC per tile, the monster waits if someone is in the way
+    let taken = state
+        .agents
+        .iter()
+        .any(|other| other.agent.get_status() != Status::Dead && other.npc.position == next)
+        || state
+            .monsters
+            .iter()
+            .any(|other| other.monster.get_status() != Status::Dead && other.npc.position == next)
+        || state.player == Some(next);
+    if taken {
+        return false;
+    }
+
+    let monster_state = &mut state.monsters[index];
+    monster_state.npc.position = next;
+    monster_state.monster.set_current_position(next);
+    events.push(GameEvent::MonsterMoved {
+        monster_id: monster_state.monster.get_id(),
+        from,
+        to: next,
+    });
+    true
+}
+
+/// Sends the monster back to its spawn, it ignores agents until it gets there.
+fn leash(state: &mut GameState, index: usize, events: &mut GameEvents) {
+    let monster = &mut state.monsters[index].monster;
+    monster.clear_target();
+    monster.set_status(Status::Finished);
+    let home = monster.get_start_position();
+    step_towards(state, index, home, events);
+}
+
+/// Runs one turn of the monster at `index`: it patrols around its spawn, hunts agents that come
+/// within its aggro radius, hits them when next to them and walks home once they leave its leash.
+pub fn update_monster(state: &mut GameState, index: usize) -> GameEvents {
+    let mut events = GameEvents::new();
+    let monster = state.monsters[index].monster.clone();
+    let stats = monster.get_stats().clone();
+    let home = monster.get_start_position();
+    let position = state.monsters[index].npc.position;
+
+    match monster.get_status() {
+        Status::Dead => return events,
+        Status::Finished => {
+            if position == home {
+                state.monsters[index].monster.set_status(Status::Idle);
+            } else {
+                step_towards(state, index, home, &mut events);
+            }
+            return events;
+        }
+        _ => {}
+    }
+
+    // Keep the current target while it can be hunted, otherwise look for the closest agent in range
+    let target_index = if monster.has_target() {
+        match state
+            .agents
+            .iter()
+            .position(|other| other.agent.get_id() == monster.get_target_id())
+        {
+            Some(agent_index) if can_hunt(state, index, agent_index) => Some(agent_index),
+            _ => {
+                leash(state, index, &mut events);
+                return events;
+            }
+        }
+    } else {
+        (0..state.agents.len())
+            .filter(|agent_index| {
+                can_hunt(state, index, *agent_index)
+                    && is_next_to_target(
+                        position,
+                        state.agents[*agent_index].npc.position,
+                        stats.aggro_radius,
+                    )
+            })
+            .min_by_key(|agent_index| {
+                let other = &state.agents[*agent_index];
+                let dx = (other.npc.position.x - position.x).abs();
+                let dy = (other.npc.position.y - position.y).abs();
+                (dx.max(dy), other.agent.get_id())
+            })
+    };
+
+    let agent_index = match target_index {
+        Some(agent_index) => agent_index,
+        None => {
+            // Nobody around, walk to the next corner of the patrol
+            let points = patrol_points(&state.world, home, stats.patrol_radius);
+            if points.is_empty() {
+                return events;
+            }
+            let mut patrol_index = monster.get_patrol_index() % points.len();
+            if position == points[patrol_index] {
+                patrol_index = (patrol_index + 1) % points.len();
+            }
+            if !step_towards(state, index, points[patrol_index], &mut events) {
+                // Try another corner next turn
+                patrol_index = (patrol_index + 1) % points.len();
+            }
+            state.monsters[index].monster.set_patrol_index(patrol_index);
+            return events;
+        }
+    };
+
+    let agent_id = state.agents[agent_index].agent.get_id();
+    let monster_id = monster.get_id();
+    state.monsters[index].monster.set_target_id(agent_id);
+    state.monsters[index].monster.set_status(Status::Attacking);
+
+    let target_position = state.agents[agent_index].npc.position;
+    if !is_next_to_target(position, target_position, 1) {
+        step_towards(state, index, target_position, &mut events);
+        return events;
+    }
+
+    // Hit the agent
+    let world = state.world.clone();
+    let victim = &mut state.agents[agent_index];
+    victim.npc.energy = victim.npc.energy.saturating_sub(stats.damage);
+    events.push(GameEvent::MonsterAttacked {
+        monster_id,
+        agent_id,
+        damage: stats.damage,
+    });
+
+    if victim.npc.energy == 0 {
+        victim.agent.set_status(Status::Dead);
+        victim.agent.set_path(Vec::new());
+        // The monster keeps what it takes from the agent, whoever kills it gets it as loot
+        let spoils = victim.agent.get_inventory_mut().take_fraction(KILL_SPOILS);
+        state.monsters[index]
+            .monster
+            .add_reward(spoils.get_value() as u32);
+        events.push(GameEvent::KilledByMonster {
+            monster_id,
+            agent_id,
+        });
+        leash(state, index, &mut events);
+    } else {
+        react_to_attack(victim, &world, Target::Monster, monster_id);
+    }
+
+    events
+}
@@ -0,0 +1,845 @@
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::agent::Agent;
+use crate::npcs::npc_components::gene_type::GeneType;
+use crate::npcs::npc_components::inventory::ResourceType;
+use crate::npcs::npc_components::npc_action::{NpcAction, WorkType};
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::npc_components::target::Target;
+use crate::system::pathfinding::danger::{DangerConfig, DangerMap};
+use crate::system::pathfinding::pathfinding_calculation::{
+    a_star_avoiding, a_star_pathfinding, a_star_with_current_path, a_star_with_danger,
+};
+use crate::system::pathfinding::reservation::{MoveOutcome, Occupant, ReservationTable};
+
+use super::combat;
+use super::economy;
+use super::theft;
+use super::events::{GameEvent, GameEvents};
+use super::game_state::{AgentView, GameState, MonsterState, NpcSnapshot, TreasureState};
+
+/// Energy an agent gets back for every turn spent recovering in a village.
+const RECOVERY_PER_TURN: u32 = 10;
+/// Turns an agent queues behind another travelling agent before planning around it.
+const MAX_WAIT_TURNS: u32 = 3;
+
+/// Runs one turn of an agent's status machine.
+/// `agents`, `monsters` and `treasures` describe the rest of the game at the start of the turn.
+pub fn update_agent(
+    agent: &mut Agent,
+    npc: &NpcSnapshot,
+    world: &GameWorld,
+    agents: &[AgentView],
+    monsters: &[MonsterState],
+    treasures: &[TreasureState],
+    danger: &DangerConfig,
+) {
+    // Match on the agent's current status
+    match agent.get_status() {
+        Status::Idle => {
+            handle_idle_actions(agent, npc, world, agents, monsters, treasures);
+        }
+        Status::Moving => {
+            handle_moving_actions(agent, npc, world, agents, monsters, treasures, danger);
+        }
+        Status::Attacking => {
+            // Check for the target agent or monster
+            let target_type = agent.get_target();
+
+            match target_type {
+                Target::Agent => {
+                    let target_id = agent.get_agent_target_id();
+                    if let Some(target) = agents
+                        .iter()
+                        .find(|state| state.id == target_id as i32)
+                    {
+                        if !is_next_to_target(npc.position, target.npc.position, 1) {
+                            let path = a_star_pathfinding(world, npc.position, target.npc.position);
+                            agent.set_path(path);
+                            agent.set_status(Status::Moving);
+                        } else if target.npc.energy == 0
+                            || target.status == Status::Dead
+                        {
+                            set_finish(agent, npc, world);
+                        }
+                    } else {
+                        set_finish(agent, npc, world);
+                    }
+                }
+                Target::Monster => {
+                    let target_id = agent.get_monster_target_id();
+                    if let Some(target) = monsters
+                        .iter()
+                        .find(|state| state.monster.get_id() == target_id as i32)
+                    {
+                        if !is_next_to_target(npc.position, target.npc.position, 1) {
+                            let path = a_star_pathfinding(world, npc.position, target.npc.position);
+                            agent.set_path(path);
+                            agent.set_status(Status::Moving);
+                        } else if target.npc.energy == 0
+                            || target.monster.get_status() == Status::Dead
+                        {
+                            set_finish(agent, npc, world);
+                        }
+                    } else {
+                        set_finish(agent, npc, world);
+                    }
+                }
+                _ => {
+                    agent.set_status(Status::RequiresInstruction);
+                }
+            }
+        }
+        Status::Finished => {
+            if let Some(village_position) = agent.get_tile_target() {
+                handle_path_recalculation_and_monster_avoidance(
+                    agent,
+                    world,
+                    monsters,
+                    danger,
+                    village_position,
+                    npc.position,
+                );
+            } else {
+                agent.set_status(Status::RequiresInstruction);
+            }
+        }
+        Status::Working => {
+            match agent.get_action() {
+                NpcAction::Steal => {
+                    let target_id = agent.get_agent_target_id();
+                    if let Some(target) = agents
+                        .iter()
+                        .find(|state| state.id == target_id as i32)
+                    {
+                        if !is_next_to_target(npc.position, target.npc.position, 1) {
+                            let path = a_star_pathfinding(world, npc.position, target.npc.position);
+                            agent.set_path(path);
+                            agent.set_status(Status::Moving);
+                        } else if target.npc.energy == 0
+                            || target.status == Status::Dead
+                        {
+                            set_finish(agent, npc, world);
+                        }
+                    } else {
+                        set_finish(agent, npc, world);
+                    }
+                }
+                NpcAction::TreasureHunt | NpcAction::Work(_) => {
+                    // Resolved by resolve_action
+                }
+                _ => {
+                    agent.set_status(Status::RequiresInstruction);
+                }
+            }
+        }
+        Status::Dead => {}
+        Status::Following => {
+            let leader_id = agent.get_leader_id();
+            if let Some(leader) = agents.iter().find(|state| state.id == leader_id) {
+                if leader.action != agent.get_action()
+                    || npc.energy == 0
+                    || leader.status == Status::Dead
+                {
+                    agent.set_status(Status::Idle);
+                    handle_idle_actions(agent, npc, world, agents, monsters, treasures);
+                }
+            } else {
+                agent.set_status(Status::Idle);
+                handle_idle_actions(agent, npc, world, agents, monsters, treasures);
+            }
+        }
+        Status::Fleeing => {
+            // Running to the closest village, move_agent makes the agent idle once it arrives
+            if travelling_path(agent).is_none() {
+                agent.set_status(Status::Idle);
+                handle_idle_actions(agent, npc, world, agents, monsters, treasures);
+            }
+        }
+        Status::Retaliating => {
+            let retaliation_target_id = agent.get_retaliation_target_id();
+            let retaliation_target_type = agent.get_retaliation_target();
+
+            let (retaliation_target, finished_retaliating) = match retaliation_target_type {
+                Target::Agent => {
+                    if let Some(target) = agents
+                        .iter()
+                        .find(|state| state.id == retaliation_target_id as i32)
+                    {
+                        let is_dead = target.status == Status::Dead;
+                        (Some(&target.npc), is_dead)
+                    } else {
+                        (None, true)
+                    }
+                }
+                Target::Monster => {
+                    if let Some(target) = monsters
+                        .iter()
+                        .find(|state| state.monster.get_id() == retaliation_target_id as i32)
+                    {
+                        let is_dead = target.monster.get_status() == Status::Dead;
+                        (Some(&target.npc), is_dead)
+                    } else {
+                        (None, true)
+                    }
+                }
+                _ => (None, true),
+            };
+
+            let mut finished_retaliating = finished_retaliating;
+
+            if let Some(target_npc) = retaliation_target {
+                if is_next_to_target(npc.position, target_npc.position, 1) {
+                    if target_npc.energy == 0 {
+                        finished_retaliating = true;
+                    }
+                } else {
+                    finished_retaliating = true;
+                }
+            } else {
+                finished_retaliating = true;
+            }
+
+            if finished_retaliating {
+                agent.set_status(Status::Idle);
+                handle_idle_actions(agent, npc, world, agents, monsters, treasures);
+            }
+        }
+        Status::Recovering => {
+            if npc.energy >= npc.max_energy {
+                agent.set_status(Status::Idle);
+                handle_idle_actions(agent, npc, world, agents, monsters, treasures);
+            }
+        }
+        Status::RequiresInstruction => {
+            // Nothing left to do for the current action, the agent gets a new one
+            agent.set_status(Status::Idle);
+        }
+        Status::Talking => {
+            // Resolved by conversation::resolve_talks
+        }
+    }
+}
+
+/// Applies the effect of the action the agent at `index` is performing this turn.
+pub fn resolve_action(state: &mut GameState, index: usize) -> GameEvents {
+    let mut events = GameEvents::new();
+    let agent_id = state.agents[index].agent.get_id();
+    let position = state.agents[index].npc.position;
+
+    match (state.agents[index].agent.get_status(), state.agents[index].agent.get_action()) {
+        (Status::Working, NpcAction::TreasureHunt) => {
+            let treasure_id = state.agents[index].agent.get_treasure_target_id();
+            let treasure_index = state.treasures.iter().position(|treasure| {
+                treasure.treasure.get_id() == treasure_id
+                    && is_next_to_target(position, treasure.npc.position, 1)
+            });
+
+            if let Some(treasure_index) = treasure_index {
+                let loot = state.treasures[treasure_index].treasure.loot();
+                events.extend(share_loot(state, index, treasure_id, loot));
+
+                if !state.treasures[treasure_index].treasure.is_depleted() {
+                    // Keep working on it next turn
+                    return events;
+                }
+                state.treasures.remove(treasure_index);
+                events.push(GameEvent::TreasureDepleted {
+                    agent_id,
+                    treasure_id,
+                });
+            }
+
+            // The treasure is empty or someone else emptied it first, head back to the village
+            let world = state.world.clone();
+            let agent_state = &mut state.agents[index];
+            set_finish(&mut agent_state.agent, &agent_state.npc, &world);
+        }
+        (Status::Working, NpcAction::Steal) => {
+            events.extend(theft::resolve_steal(state, index));
+        }
+        (Status::Working, NpcAction::Work(_)) => {
+            events.extend(economy::resolve_work(state, index));
+        }
+        (Status::Attacking, _) | (Status::Retaliating, _) => {
+            events.extend(combat::resolve_attack(state, index));
+        }
+        (Status::Recovering, _) => {
+            let npc = &mut state.agents[index].npc;
+            let amount = RECOVERY_PER_TURN.min(npc.max_energy.saturating_sub(npc.energy));
+            if amount > 0 {
+                npc.energy += amount;
+                events.push(GameEvent::EnergyRecovered { agent_id, amount });
+            }
+        }
+        _ => {}
+    }
+
+    events
+}
+
+/// Splits one turn of loot between the agent at `index` and the living agents following it.
+/// The leader keeps what does not divide evenly.
+fn share_loot(state: &mut GameState, index: usize, treasure_id: i32, loot: u32) -> GameEvents {
+    let mut events = GameEvents::new();
+    let leader_id = state.agents[index].agent.get_id();
+    let followers: Vec<usize> = (0..state.agents.len())
+        .filter(|other| {
+            let agent = &state.agents[*other].agent;
+            *other != index
+                && agent.get_status() == Status::Following
+                && agent.get_leader_id() == leader_id
+        })
+        .collect();
+
+    let share = loot / (followers.len() as u32 + 1);
+    let leader_share = loot - share * followers.len() as u32;
+    for (other, reward) in std::iter::once((index, leader_share))
+        .chain(followers.into_iter().map(|other| (other, share)))
+    {
+        if reward == 0 {
+            continue;
+        }
+        let agent = &mut state.agents[other].agent;
+        agent.get_inventory_mut().add(ResourceType::Loot, reward);
+        events.push(GameEvent::TreasureCollected {
+            agent_id: agent.get_id(),
+            treasure_id,
+            reward,
+        });
+    }
+    events
+}
+
+/// The path of an agent that moves this turn. Agents heading home after finishing an action
+/// or fleeing from a fight move as well.
+pub fn travelling_path(agent: &Agent) -> Option<Vec<Position>> {
+    let status = agent.get_status();
+    if status != Status::Moving && status != Status::Finished && status != Status::Fleeing {
+        return None;
+    }
+    agent.get_path().filter(|path| !path.is_empty())
+}
+
+/// Moves an agent one tile along its path.
+pub fn move_agent(agent: &mut Agent, npc: &mut NpcSnapshot) -> Option<GameEvent> {
+    let mut path = travelling_path(agent)?;
+
+    // Pop the first position in the path
+    let from = npc.position;
+    let next_position = path.remove(0);
+    npc.position = next_position;
+    agent.set_wait_turns(0);
+
+    // If the path is empty, set the agent's status to Idle
+    let arrived = path.is_empty();
+    agent.set_path(path);
+    if arrived {
+        agent.set_status(Status::Idle);
+    }
+
+    Some(GameEvent::Moved {
+        agent_id: agent.get_id(),
+        from,
+        to: next_position,
+    })
+}
+
+/// Handles an agent whose next step was refused by the reservation table.
+/// An agent queues behind a travelling agent for a few turns and plans around anything else.
+/// In a deadlock the agent with the higher id steps aside, goes round the other agent,
+/// or backs off along the other agent's route when there is no room for either.
+pub fn handle_blocked_move(
+    agent: &mut Agent,
+    npc: &NpcSnapshot,
+    world: &GameWorld,
+    table: &ReservationTable,
+    outcome: MoveOutcome,
+) -> Option<GameEvent> {
+    let agent_id = agent.get_id();
+    let goal = *travelling_path(agent)?.last()?;
+
+    match outcome {
+        MoveOutcome::Move => None,
+        MoveOutcome::Wait { blocked_by } => {
+            let waited = agent.get_wait_turns() + 1;
+            agent.set_wait_turns(waited);
+
+            let queueing = matches!(blocked_by, Occupant::Agent(id) if table.is_travelling(id));
+            if !queueing || waited >= MAX_WAIT_TURNS {
+                let blocked = table.reserved_by_others(world, agent_id);
+                let path = a_star_avoiding(world, npc.position, goal, &blocked);
+                if !path.is_empty() && !blocked.contains(&goal) {
+                    agent.set_path(path);
+                } else if waited >= MAX_WAIT_TURNS {
+                    // The goal itself is taken, give up so the agent is given a new action
+                    agent.set_wait_turns(0);
+                    agent.set_path(Vec::new());
+                    agent.set_status(Status::Idle);
+                }
+            }
+
+            Some(GameEvent::Waited {
+                agent_id,
+                position: npc.position,
+                blocked_by,
+            })
+        }
+        MoveOutcome::Deadlock { with } => {
+            if agent_id > with {
+                // Step onto a free tile off the other agent's route, then carry on to the goal
+                let other_tiles = table.planned_tiles(with);
+                let movement = world.get_movement();
+                let side = movement
+                    .neighbors(world, npc.position)
+                    .into_iter()
+                    .filter(|position| {
+                        table.is_free(world, *position) && !other_tiles.contains(position)
+                    })
+                    .min_by(|a, b| {
+                        movement
+                            .heuristic(*a, goal)
+                            .partial_cmp(&movement.heuristic(*b, goal))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    });
+                let blocked = table.reserved_by_others(world, agent_id);
+                let around = a_star_avoiding(world, npc.position, goal, &blocked);
+                if let Some(side) = side {
+                    let mut path = vec![side];
+                    path.extend(a_star_pathfinding(world, side, goal));
+                    agent.set_path(path);
+                } else if !around.is_empty() && !blocked.contains(&goal) {
+                    agent.set_path(around);
+                } else if let Some(back) = other_tiles
+                    .first()
+                    .and_then(|other| back_off_tile(world, table, npc.position, *other))
+                {
+                    // No room to pass, give way along the other agent's route until there is
+                    let mut path = vec![back];
+                    path.extend(a_star_pathfinding(world, back, goal));
+                    agent.set_path(path);
+                }
+            }
+
+            Some(GameEvent::DeadlockDetected {
+                agent_id,
+                other_id: with,
+            })
+        }
+    }
+}
+
+/// The free tile next to `position` furthest from `other`, the tile of the agent in the way.
+fn back_off_tile(
+    world: &GameWorld,
+    table: &ReservationTable,
+    position: Position,
+    other: Position,
+) -> Option<Position> {
+    let movement = world.get_movement();
+    movement
+        .neighbors(world, position)
+        .into_iter()
+        .filter(|tile| *tile != other && table.is_free(world, *tile))
+        .max_by(|a, b| {
+            movement
+                .heuristic(*a, other)
+                .partial_cmp(&movement.heuristic(*b, other))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+}
+
+/// Sends the agent back to the closest village once it is done with its action.
+pub(super) fn set_finish(agent: &mut Agent, npc: &NpcSnapshot, world: &GameWorld) {
+    if let Some((target_village, path)) =
+        world.path_to_closest_tiletype(npc.position, TileType::Village)
+    {
+        agent.set_path(path);
+        agent.set_status(Status::Finished);
+        agent.set_tile_target(Some(target_village));
+    } else {
+        agent.set_status(Status::RequiresInstruction);
+    }
+}
+
+fn handle_idle_actions(
+    agent: &mut Agent,
+    npc: &NpcSnapshot,
+    world: &GameWorld,
+    agents: &[AgentView],
+    monsters: &[MonsterState],
+    treasures: &[TreasureState],
+) {
+    let agent_position = npc.position;
+
+    match agent.get_action() {
+        NpcAction::AttackAgent => {
+            if let Some(target_id) = agent.find_best_agent() {
+                if let Some(target) = agents.iter().find(|state| {
+                    state.id == target_id as i32 && state.status != Status::Dead
+                }) {
+                    agent.set_target(Target::Agent);
+                    if is_next_to_target(agent_position, target.npc.position, 1) {
+                        agent.set_status(Status::Attacking);
+                        agent.set_agent_target_id(target_id);
+                    } else {
+                        let path = a_star_pathfinding(world, agent_position, target.npc.position);
+                        agent.set_path(path);
+                        agent.set_status(Status::Moving);
+                        agent.set_agent_target_id(target_id);
+                    }
+                }
+            }
+        }
+        NpcAction::AttackMonster => {
+            if let Some(target) = monsters
+                .iter()
+                .filter(|state| state.monster.get_status() != Status::Dead)
+                .min_by_key(|state| {
+                    let dx = (state.npc.position.x - agent_position.x).abs();
+                    let dy = (state.npc.position.y - agent_position.y).abs();
+                    dx + dy
+                })
+            {
+                agent.set_target(Target::Monster);
+                if is_next_to_target(agent_position, target.npc.position, 1) {
+                    agent.set_status(Status::Attacking);
+                    agent.set_monster_target_id(target.monster.get_id());
+                } else {
+                    let path = a_star_pathfinding(world, agent_position, target.npc.position);
+                    agent.set_path(path);
+                    agent.set_status(Status::Moving);
+                    agent.set_monster_target_id(target.monster.get_id());
+                }
+            }
+        }
+        NpcAction::Steal => {
+            // The agent it likes least, or else the closest one carrying anything
+            let alive: Vec<&AgentView> = agents
+                .iter()
+                .filter(|state| {
+                    state.id != agent.get_id()
+                        && state.status != Status::Dead
+                })
+                .collect();
+            let target = agent
+                .find_worst_agent()
+                .and_then(|target_id| alive.iter().find(|state| state.id == target_id))
+                .or_else(|| {
+                    alive
+                        .iter()
+                        .filter(|state| state.has_goods)
+                        .min_by_key(|state| {
+                            let dx = (state.npc.position.x - agent_position.x).abs();
+                            let dy = (state.npc.position.y - agent_position.y).abs();
+                            (dx.max(dy), state.id)
+                        })
+                });
+            if let Some(target) = target {
+                agent.set_agent_target_id(target.id);
+                if is_next_to_target(agent_position, target.npc.position, 1) {
+                    agent.set_status(Status::Working);
+                } else {
+                    let path = a_star_pathfinding(world, agent_position, target.npc.position);
+                    agent.set_path(path);
+                    agent.set_status(Status::Moving);
+                }
+            }
+        }
+        NpcAction::TreasureHunt => {
+            if let Some(target) = treasures.iter().min_by_key(|state| {
+                let dx = (state.npc.position.x - agent_position.x).abs();
+                let dy = (state.npc.position.y - agent_position.y).abs();
+                dx + dy
+            }) {
+                if is_next_to_target(agent_position, target.npc.position, 1) {
+                    agent.set_status(Status::Working);
+                    agent.set_treasure_target_id(target.treasure.get_id());
+                } else {
+                    let path = a_star_pathfinding(world, agent_position, target.npc.position);
+                    agent.set_path(path);
+                    agent.set_status(Status::Moving);
+                    agent.set_treasure_target_id(target.treasure.get_id());
+                }
+            }
+        }
+        NpcAction::Rest | NpcAction::Talk => {
+            if let Some((village_position, path)) =
+                world.path_to_closest_tiletype(agent_position, TileType::Village)
+            {
+                if is_next_to_target(agent_position, village_position, 0) {
+                    let action = agent.get_action();
+                    agent.set_status(if action == NpcAction::Rest {
+                        Status::Recovering
+                    } else {
+                        Status::Talking
+                    });
+                } else {
+                    agent.set_path(path);
+                    agent.set_status(Status::Moving);
+                }
+            }
+        }
+        NpcAction::Work(work_type) => match work_type {
+            WorkType::Farming(tile) | WorkType::Mining(tile) => {
+                // Pick the closest tile to work on if the action did not come with one
+                let tile = match tile.or_else(|| {
+                    world.find_closest_tiletype(agent_position, work_type.get_tile_type()?)
+                }) {
+                    Some(tile) => tile,
+                    None => return,
+                };
+                agent.set_action(NpcAction::Work(work_type.with_tile(tile)));
+                agent.set_work_turns(0);
+
+                if is_next_to_target(agent_position, tile, 1) {
+                    agent.set_status(Status::Working);
+                } else {
+                    let path = a_star_pathfinding(world, agent_position, tile);
+                    agent.set_path(path);
+                    agent.set_status(Status::Moving);
+                }
+            }
+            WorkType::Merchant => {
+                if let Some((village_position, path)) =
+                    world.path_to_closest_tiletype(agent_position, TileType::Village)
+                {
+                    if is_next_to_target(agent_position, village_position, 0) {
+                        agent.set_status(Status::Working);
+                    } else {
+                        agent.set_path(path);
+                        agent.set_status(Status::Moving);
+                    }
+                }
+            }
+        },
+        NpcAction::None => {
+            // No action chosen yet, the agent waits for one
+        }
+    }
+}
+
+fn handle_moving_actions(
+    agent: &mut Agent,
+    npc: &NpcSnapshot,
+    world: &GameWorld,
+    agents: &[AgentView],
+    monsters: &[MonsterState],
+    treasures: &[TreasureState],
+    danger: &DangerConfig,
+) {
+    let agent_position = npc.position;
+
+    match agent.get_action() {
+        NpcAction::AttackAgent => {
+            let target_id = agent.get_agent_target_id();
+            if let Some(target) = agents
+                .iter()
+                .find(|state| state.id == target_id as i32)
+            {
+                if is_next_to_target(agent_position, target.npc.position, 1) {
+                    agent.set_status(Status::Attacking);
+                    agent.set_path(Vec::new()); // Clear the path
+                } else {
+                    handle_path_recalculation_and_monster_avoidance(
+                        agent,
+                        world,
+                        monsters,
+                        danger,
+                        target.npc.position,
+                        agent_position,
+                    );
+                }
+            }
+        }
+        NpcAction::AttackMonster => {
+            let target_id = agent.get_monster_target_id();
+            if let Some(target) = monsters
+                .iter()
+                .find(|state| state.monster.get_id() == target_id as i32)
+            {
+                if is_next_to_target(agent_position, target.npc.position, 1) {
+                    agent.set_status(Status::Attacking);
+                    agent.set_path(Vec::new()); // Clear the path
+                } else {
+                    handle_path_recalculation_and_monster_avoidance(
+                        agent,
+                        world,
+                        monsters,
+                        danger,
+                        target.npc.position,
+                        agent_position,
+                    );
+                }
+            }
+        }
+        NpcAction::Steal => {
+            let target_id = agent.get_agent_target_id();
+            if let Some(target) = agents
+                .iter()
+                .find(|state| state.id == target_id as i32)
+            {
+                if is_next_to_target(agent_position, target.npc.position, 1) {
+                    agent.set_status(Status::Working); // Stealing is resolved like work
+                    agent.set_path(Vec::new());
+                } else {
+                    handle_path_recalculation_and_monster_avoidance(
+                        agent,
+                        world,
+                        monsters,
+                        danger,
+                        target.npc.position,
+                        agent_position,
+                    );
+                }
+            }
+        }
+        NpcAction::TreasureHunt => {
+            let target_id = agent.get_treasure_target_id();
+            if let Some(target) = treasures
+                .iter()
+                .find(|state| state.treasure.get_id() == target_id as i32)
+            {
+                if is_next_to_target(agent_position, target.npc.position, 1) {
+                    agent.set_status(Status::Working); // Treasure hunting is treated as working
+                    agent.set_path(Vec::new());
+                } else {
+                    handle_path_recalculation_and_monster_avoidance(
+                        agent,
+                        world,
+                        monsters,
+                        danger,
+                        target.npc.position,
+                        agent_position,
+                    );
+                }
+            } else {
+                // Emptied before the agent got there
+                set_finish(agent, npc, world);
+            }
+        }
+        NpcAction::Rest | NpcAction::Talk => {
+            if let Some(village_position) =
+                world.find_closest_tiletype(agent_position, TileType::Village)
+            {
+                if is_next_to_target(agent_position, village_position, 0) {
+                    let action = agent.get_action();
+                    agent.set_status(if action == NpcAction::Rest {
+                        Status::Recovering
+                    } else {
+                        Status::Talking
+                    });
+                } else {
+                    handle_path_recalculation_and_monster_avoidance(
+                        agent,
+                        world,
+                        monsters,
+                        danger,
+                        village_position,
+                        agent_position,
+                    );
+                }
+            }
+        }
+        NpcAction::Work(work_type) => match work_type {
+            WorkType::Farming(None) | WorkType::Mining(None) => {
+                agent.set_status(Status::Idle);
+            }
+            WorkType::Farming(Some(target_position)) | WorkType::Mining(Some(target_position)) => {
+                if is_next_to_target(agent_position, target_position, 1) {
+                    agent.set_status(Status::Working);
+                    agent.set_path(Vec::new());
+                } else {
+                    handle_path_recalculation_and_monster_avoidance(
+                        agent,
+                        world,
+                        monsters,
+                        danger,
+                        target_position,
+                        agent_position,
+                    );
+                }
+            }
+            WorkType::Merchant => {
+                if let Some(village_position) =
+                    world.find_closest_tiletype(agent_position, TileType::Village)
+                {
+                    if is_next_to_target(agent_position, village_position, 0) {
+                        agent.set_status(Status::Working);
+                    } else {
+                        handle_path_recalculation_and_monster_avoidance(
+                            agent,
+                            world,
+                            monsters,
+                            danger,
+                            village_position,
+                            agent_position,
+                        );
+                    }
+                }
+            }
+        },
+        NpcAction::None => {
+            agent.set_status(Status::Idle);
+        }
+    }
+}
+
+/// Keeps the path to the target up to date and routes it around the danger of nearby monsters,
+/// weighted by the agent's SelfPreservation gene.
+fn handle_path_recalculation_and_monster_avoidance(
+    agent: &mut Agent,
+    world: &GameWorld,
+    monsters: &[MonsterState],
+    danger: &DangerConfig,
+    target_position: Position,
+    agent_position: Position,
+) {
+    // Recalculate path if the last position is not the target
+    if let Some(path) = agent.get_path() {
+        let mut recalculated_path = path;
+        if recalculated_path.last() != Some(&target_position) {
+            recalculated_path = a_star_with_current_path(world, recalculated_path, target_position);
+            agent.set_path(recalculated_path.clone()); // Set the recalculated path
+        }
+
+        // A monster the agent is heading for is the target, not something to route around
+        let monster_positions: Vec<Position> = monsters
+            .iter()
+            .filter(|state| state.monster.get_status() != Status::Dead)
+            .map(|state| state.npc.position)
+            .filter(|position| *position != target_position)
+            .collect();
+        if monster_positions.is_empty() {
+            return;
+        }
+
+        let self_preservation = agent
+            .get_genes()
+            .return_type_score(GeneType::SelfPreservation);
+        let danger_map = DangerMap::compute(world, &monster_positions, danger, self_preservation);
+
+        // Only search again when the next steps lead into danger
+        if recalculated_path
+            .iter()
+            .take(3)
+            .any(|position| danger_map.is_dangerous(*position))
+        {
+            let new_path = a_star_with_danger(world, agent_position, target_position, &danger_map);
+            if !new_path.is_empty() {
+                agent.set_path(new_path); // Update the path
+            }
+        }
+    }
+}
+
+pub fn is_next_to_target(
+    current_position: Position,
+    target_position: Position,
+    distance_in_tiles: i32,
+) -> bool {
+    let dx = (current_position.x - target_position.x).abs();
+    let dy = (current_position.y - target_position.y).abs();
+    dx <= distance_in_tiles && dy <= distance_in_tiles
+}
@@ -0,0 +1,128 @@
+use crate::npcs::npc_components::gene_type::GeneType;
+use crate::npcs::npc_components::inventory::ResourceType;
+use crate::npcs::npc_components::memory::Memory;
+use crate::npcs::npc_components::npc_action::NpcAction;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::npc_components::target::Target;
+
+use super::combat::react_to_attack;
+use super::events::{GameEvent, GameEvents};
+use super::game_state::GameState;
+use super::rules::{is_next_to_target, set_finish};
+
+/// A thief this much less stealthy than its victim is perceptive is caught before it takes anything.
+const CAUGHT_MARGIN: f32 = 0.25;
+/// Part of the victim's gold and loot taken by a thief exactly as stealthy as the victim is perceptive.
+const BASE_STEAL_FRACTION: f32 = 0.25;
+/// Opinion the victim loses of a thief it noticed.
+const VICTIM_OPINION_LOSS: f32 = 0.2;
+/// Opinion the victim's followers lose of the thief.
+const FOLLOWER_OPINION_LOSS: f32 = 0.1;
+
+/// How a theft attempt went, decided by the thief's Stealth against the victim's Perception.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TheftOutcome {
+    /// Part of the victim's gold and loot taken, 0 if the thief was caught.
+    pub fraction: f32,
+    pub detected: bool,
+}
+
+/// A thief more stealthy than its victim is perceptive goes unnoticed and takes more,
+/// a slightly clumsier one still gets away with something but is seen.
+pub fn theft_outcome(stealth: f32, perception: f32) -> TheftOutcome {
+    let margin = stealth - perception;
+    let fraction = if margin > -CAUGHT_MARGIN {
+        (BASE_STEAL_FRACTION + margin / 2.0).clamp(0.1, 0.5)
+    } else {
+        0.0
+    };
+    TheftOutcome {
+        fraction,
+        detected: margin < 0.0,
+    }
+}
+
+/// Resolves the theft of the agent at `index` on the agent next to it. A victim that notices
+/// remembers the thief, thinks less of it and either fights back or flees.
+/// Whatever happens the thief heads back to the village afterwards.
+pub fn resolve_steal(state: &mut GameState, index: usize) -> GameEvents {
+    let mut events = GameEvents::new();
+    let agent_id = state.agents[index].agent.get_id();
+    let target_id = state.agents[index].agent.get_agent_target_id();
+    let position = state.agents[index].npc.position;
+
+    let victim_index = match state.agents.iter().position(|other| {
+        other.agent.get_id() == target_id
+            && other.agent.get_id() != agent_id
+            && other.agent.get_status() != Status::Dead
+            && is_next_to_target(position, other.npc.position, 1)
+    }) {
+        Some(victim_index) => victim_index,
+        None => {
+            // The victim got away or died, give up
+            let world = state.world.clone();
+            let thief = &mut state.agents[index];
+            set_finish(&mut thief.agent, &thief.npc, &world);
+            return events;
+        }
+    };
+
+    let stealth = state.agents[index]
+        .agent
+        .get_genes()
+        .return_type_score(GeneType::Stealth);
+    let perception = state.agents[victim_index]
+        .agent
+        .get_genes()
+        .return_type_score(GeneType::Perception);
+    let outcome = theft_outcome(stealth, perception);
+
+    // Take the valuables
+    let mut stolen = 0;
+    if outcome.fraction > 0.0 {
+        let mut victim_inventory = state.agents[victim_index].agent.get_inventory().clone();
+        let thief_inventory = state.agents[index].agent.get_inventory_mut();
+        for resource in [ResourceType::Gold, ResourceType::Loot] {
+            let amount = (victim_inventory.get(resource) as f32 * outcome.fraction) as u32;
+            stolen += victim_inventory.transfer(thief_inventory, resource, amount);
+        }
+        *state.agents[victim_index].agent.get_inventory_mut() = victim_inventory;
+        state.agents[index]
+            .agent
+            .add_to_memory(Memory::new(agent_id, target_id, NpcAction::Steal));
+        events.push(GameEvent::Stole {
+            agent_id,
+            target_id,
+            amount: stolen,
+        });
+    }
+
+    if outcome.detected {
+        events.push(GameEvent::TheftDetected {
+            agent_id,
+            target_id,
+            stolen: stolen > 0,
+        });
+
+        let world = state.world.clone();
+        let victim = &mut state.agents[victim_index];
+        victim
+            .agent
+            .add_to_memory(Memory::new(agent_id, target_id, NpcAction::Steal));
+        victim.agent.modify_opinion(agent_id, -VICTIM_OPINION_LOSS);
+        react_to_attack(victim, &world, Target::Agent, agent_id);
+
+        for other in state.agents.iter_mut() {
+            if other.agent.get_status() == Status::Following
+                && other.agent.get_leader_id() == target_id
+            {
+                other.agent.modify_opinion(agent_id, -FOLLOWER_OPINION_LOSS);
+            }
+        }
+    }
+
+    let world = state.world.clone();
+    let thief = &mut state.agents[index];
+    set_finish(&mut thief.agent, &thief.npc, &world);
+    events
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use clap::ValueEnum;
+
+use crate::system::mcst_tree::simulation_tree::SimulationTree;
+use crate::TurnState;
+
+/// Limits on how much search is done in one MCTS phase. `None` means unlimited.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BudgetLimits {
+    pub iterations: Option<u32>,
+    pub time: Option<Duration>,
+    pub max_nodes: Option<usize>,
+}
+
+impl Default for BudgetLimits {
+    fn default() -> Self {
+        BudgetLimits {
+            iterations: Some(50),
+            time: None,
+            max_nodes: None,
+        }
+    }
+}
+
+/// Whether the limits apply to every agent's tree separately or to all trees together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BudgetScope {
+    /// Each tree stops on its own iterations, nodes and the time spent simulating it.
+    PerAgent,
+    /// The phase stops once the iterations and nodes of all trees, or the phase's wall-clock time, reach the limit.
+    Global,
+}
+
+/// Search budget of a turn, replacing the old fixed iteration counts.
+#[derive(Resource, Clone, Debug)]
+pub struct MctsBudget {
+    pub scope: BudgetScope,
+    pub limits: BudgetLimits,
+    pub agent_limits: HashMap<i32, BudgetLimits>,
+    /// Nodes deeper than this are scored without being expanded.
+    pub max_depth: u32,
+}
+
+impl Default for MctsBudget {
+    fn default() -> Self {
+        MctsBudget {
+            scope: BudgetScope::PerAgent,
+            limits: BudgetLimits::default(),
+            agent_limits: HashMap::new(),
+            max_depth: 256,
+        }
+    }
+}
+
+impl MctsBudget {
+    /// Returns the limits of an agent's tree, falling back to the shared limits.
+    pub fn limits_for(&self, agent_id: i32) -> &BudgetLimits {
+        match self.scope {
+            BudgetScope::PerAgent => self.agent_limits.get(&agent_id).unwrap_or(&self.limits),
+            BudgetScope::Global => &self.limits,
+        }
+    }
+}
+
+/// How much of the budget has been used in the current MCTS phase.
+#[derive(Resource, Default, Debug)]
+pub struct MctsUsage {
+    pub phase_start: Option<Instant>,
+    pub phase_time: Duration,
+    pub iterations: HashMap<i32, u32>,
+    pub time: HashMap<i32, Duration>,
+}
+
+impl MctsUsage {
+    /// Starts a new phase.
+    pub fn reset(&mut self) {
+        self.phase_start = Some(Instant::now());
+        self.phase_time = Duration::ZERO;
+        self.iterations.clear();
+        self.time.clear();
+    }
+
+    pub fn add_iteration(&mut self, agent_id: i32) {
+        *self.iterations.entry(agent_id).or_insert(0) += 1;
+    }
+
+    pub fn add_time(&mut self, agent_id: i32, time: Duration) {
+        *self.time.entry(agent_id).or_insert(Duration::ZERO) += time;
+    }
+
+    pub fn get_iterations(&self, agent_id: i32) -> u32 {
+        *self.iterations.get(&agent_id).unwrap_or(&0)
+    }
+
+    pub fn get_time(&self, agent_id: i32) -> Duration {
+        *self.time.get(&agent_id).unwrap_or(&Duration::ZERO)
+    }
+
+    pub fn total_iterations(&self) -> u32 {
+        self.iterations.values().sum()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.phase_start.map(|start| start.elapsed()).unwrap_or(self.phase_time)
+    }
+
+    /// Returns which limit an agent's tree has reached, if any.
+    pub fn exhausted_by(
+        &self,
+        budget: &MctsBudget,
+        agent_id: i32,
+        simulation_tree: &SimulationTree,
+    ) -> Option<&'static str> {
+        let limits = budget.limits_for(agent_id);
+        let (iterations, time, nodes) = match budget.scope {
+            BudgetScope::PerAgent => (
+                self.get_iterations(agent_id),
+                self.get_time(agent_id),
+                simulation_tree.get_tree(agent_id).map_or(0, |tree| tree.node_count()),
+            ),
+            BudgetScope::Global => (
+                self.total_iterations(),
+                self.elapsed(),
+                simulation_tree.trees.values().map(|tree| tree.node_count()).sum(),
+            ),
+        };
+
+        if limits.iterations.map_or(false, |limit| iterations >= limit) {
+            Some("iterations")
+        } else if limits.time.map_or(false, |limit| time >= limit) {
+            Some("time")
+        } else if limits.max_nodes.map_or(false, |limit| nodes >= limit) {
+            Some("nodes")
+        } else {
+            None
+        }
+    }
+}
+
+/// Ends the MCTS phase once every tree has used up its budget.
+pub fn check_budget_system(
+    budget: Res<MctsBudget>,
+    mut usage: ResMut<MctsUsage>,
+    simulation_tree: Res<SimulationTree>,
+    mut next_state: ResMut<NextState<TurnState>>,
+) {
+    let finished = simulation_tree
+        .trees
+        .keys()
+        .all(|agent_id| usage.exhausted_by(&budget, *agent_id, &simulation_tree).is_some());
+
+    if finished {
+        // Freeze the phase time so the report shows the search time only
+        usage.phase_time = usage.elapsed();
+        usage.phase_start = None;
+        println!(
+            "Finished MCTS phase after {} iterations in {:.1} ms",
+            usage.total_iterations(),
+            usage.phase_time.as_secs_f64() * 1000.0
+        );
+        next_state.set(TurnState::ExecutionPhase);
+    }
+}
+
+/// Prints how much of the budget each agent used this turn.
+pub fn report_budget_system(
+    budget: Res<MctsBudget>,
+    usage: Res<MctsUsage>,
+    simulation_tree: Res<SimulationTree>,
+) {
+    println!(
+        "MCTS budget ({:?}): {:?}, max depth {}",
+        budget.scope, budget.limits, budget.max_depth
+    );
+
+    let mut agent_ids: Vec<i32> = simulation_tree.trees.keys().copied().collect();
+    agent_ids.sort();
+    for agent_id in agent_ids {
+        println!(
+            "  Agent {}: {} iterations, {} nodes, {:.1} ms simulating, stopped by {}",
+            agent_id,
+            usage.get_iterations(agent_id),
+            simulation_tree.get_tree(agent_id).map_or(0, |tree| tree.node_count()),
+            usage.get_time(agent_id).as_secs_f64() * 1000.0,
+            usage
+                .exhausted_by(&budget, agent_id, &simulation_tree)
+                .unwrap_or("phase end")
+        );
+    }
+}
+
+/// Parses budget limits of the form `iterations:100,time:50,nodes:5000`, where time is in milliseconds.
+/// Limits that are left out are unlimited.
+pub fn parse_budget_limits(value: &str) -> Result<BudgetLimits, String> {
+    let mut limits = BudgetLimits {
+        iterations: None,
+        time: None,
+        max_nodes: None,
+    };
+
+    for part in value.split(',') {
+        let (key, amount) = part
+            .split_once(':')
+            .ok_or_else(|| format!("expected <limit>:<amount>, got '{}'", part))?;
+        let amount = amount
+            .trim()
+            .parse::<u64>()
+            .map_err(|_| format!("invalid amount '{}'", amount))?;
+        match key.trim() {
+            "iterations" => limits.iterations = Some(amount as u32),
+            "time" => limits.time = Some(Duration::from_millis(amount)),
+            "nodes" => limits.max_nodes = Some(amount as usize),
+            other => return Err(format!("unknown limit '{}'", other)),
+        }
+    }
+
+    Ok(limits)
+}
+
+/// Parses an `--agent-budget` value of the form `<agent id>=<limits>`, e.g. `3=iterations:200,time:20`.
+pub fn parse_agent_budget(value: &str) -> Result<(i32, BudgetLimits), String> {
+    let (id, limits) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected <agent id>=<limits>, got '{}'", value))?;
+    let id = id
+        .trim()
+        .parse::<i32>()
+        .map_err(|_| format!("invalid agent id '{}'", id))?;
+    Ok((id, parse_budget_limits(limits)?))
+}
@@ -1,42 +1,68 @@
-use bevy::ecs::system::{Query, Res, ResMut};
+use bevy::ecs::system::{Query, Res, ResMut, Resource};
 
 use crate::{
-    system::{mcst_tree::{mcst_node::NodeType, simulation_tree::SimulationTree}, mcst_system::selection::select_best_action_using_uct},
-    npcs::{agent::Agent, npc_components::npc_action::NpcAction},
-    MCSTFlag, // Import the helper function
+    system::{mcst_tree::simulation_tree::SimulationTree, mcst_system::selection::select_best_action},
+    system::random::{RngStream, RngStreams},
+    npcs::{agent::Agent, npc_components::{action_rating::ActionRating, npc_action::NpcAction}},
 };
 
+/// How strongly the gene-derived `ActionRating` seeds newly expanded ActionNodes.
+/// Each new child starts with `virtual_visits` visits of `rating * virtual_reward_scale`.
+/// Ratings go from 0 to 1 and the virtual reward is in the unit of the rollout rewards,
+/// gold divided by `RolloutConfig::reward_scale`, so a scale of 1 puts the favourite action
+/// on par with a rollout that found a treasure.
+#[derive(Resource, Clone)]
+pub struct ActionPriorConfig {
+    pub virtual_visits: usize,
+    pub virtual_reward_scale: f64,
+}
+
+impl Default for ActionPriorConfig {
+    fn default() -> Self {
+        ActionPriorConfig {
+            virtual_visits: 1,
+            virtual_reward_scale: 1.0,
+        }
+    }
+}
+
 pub fn expansion_system(
-    mcst_flag: Res<MCSTFlag>,
+    prior_config: Res<ActionPriorConfig>,
+    mut rng_streams: ResMut<RngStreams>,
     mut simulation_tree: ResMut<SimulationTree>,
-    mut agents: Query<&mut Agent>,
+    agents: Query<&Agent>,
 ) {
-    //If not in MCST phase, skip this step
-    if !mcst_flag.0 {
-        return;
-    }
-    
     for (agent_id, tree) in simulation_tree.trees.iter_mut() {
         // Find the corresponding agent for the current tree.
-        if let Some(mut agent) = agents.iter_mut().find(|a| a.get_id() == *agent_id) {
+        if let Some(agent) = agents.iter().find(|a| a.get_id() == *agent_id) {
             // Check if the tree is ready for expansion.
             if tree.is_ready_for_expansion() {
-                if let Some(current_node) = tree.get_current_node().cloned() {
-                    let mut current_node_lock = current_node.lock().unwrap();
+                if let Some(current_node) = tree.get_current_node() {
+                    // Gene-derived ratings act as priors for the new children.
+                    let mut action_rating = ActionRating::new();
+                    action_rating.generate_ratings(agent.get_genes().clone());
 
                     // Expand the current node by adding all possible actions as children.
                     for action in NpcAction::iter() {
-                        if !current_node_lock.has_child(&NodeType::ActionNode { action }) {
-                            current_node_lock.expand(action);
+                        // Only seed children that did not exist yet, otherwise the virtual visits pile up
+                        let is_new = tree.get_node(current_node).get_action_child(action).is_none();
+                        let child = tree.expand(current_node, action);
+                        if is_new {
+                            let virtual_reward =
+                                action_rating.get_rating(action) as f64 * prior_config.virtual_reward_scale;
+                            tree.seed_prior(
+                                child,
+                                action_rating.get_prior(action),
+                                prior_config.virtual_visits,
+                                virtual_reward,
+                            );
                         }
                     }
 
-                    // Drop the lock on the current node before performing UCT selection.
-                    drop(current_node_lock);
-
-                    // Perform UCT selection for the next action using the helper function.
+                    // Select the next action using the tree's selection policy.
+                    let rng = rng_streams.agent_stream(RngStream::Selection, *agent_id);
                     if let Some(best_action_node) =
-                        select_best_action_using_uct(&mut agent, &current_node.lock().unwrap(), *agent_id)
+                        select_best_action(tree, current_node, *agent_id, rng)
                     {
                         tree.set_current_node(best_action_node);
                     } else {
@@ -46,8 +72,9 @@ pub fn expansion_system(
                         );
                     }
 
-                    // Mark the tree as no longer ready for expansion.
+                    // Mark the tree as no longer ready for expansion and simulate the new node.
                     tree.set_ready_for_expansion(false);
+                    tree.set_ready_for_rollout(true);
                 }
             }
         }
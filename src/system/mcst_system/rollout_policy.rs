@@ -0,0 +1,100 @@
+use bevy::prelude::Resource;
+use clap::ValueEnum;
+use rand::{Rng, RngCore};
+
+use crate::npcs::npc_components::{action_rating::ActionRating, genes::Genes, npc_action::NpcAction};
+use crate::npcs::treasure::DEFAULT_TREASURE_REWARD;
+
+/// Default policy used to pick actions during the rollout (simulation) phase.
+pub trait RolloutPolicy: Send + Sync {
+    /// Short name used when reporting results.
+    fn name(&self) -> &'static str;
+
+    /// Picks the next action of the simulated agent.
+    fn choose_action(&self, rng: &mut dyn RngCore) -> NpcAction;
+}
+
+/// Samples actions in proportion to the agent's gene-derived `ActionRating`,
+/// so an aggressive agent keeps attacking in its rollouts and a social one keeps talking.
+pub struct GeneWeightedRollout {
+    action_rating: ActionRating,
+}
+
+impl GeneWeightedRollout {
+    pub fn new(genes: &Genes) -> Self {
+        let mut action_rating = ActionRating::new();
+        action_rating.generate_ratings(genes.clone());
+        GeneWeightedRollout { action_rating }
+    }
+}
+
+impl RolloutPolicy for GeneWeightedRollout {
+    fn name(&self) -> &'static str {
+        "gene-weighted"
+    }
+
+    fn choose_action(&self, rng: &mut dyn RngCore) -> NpcAction {
+        // Agents whose ratings are all zero fall back to resting
+        self.action_rating.select_action(rng).unwrap_or(NpcAction::Rest)
+    }
+}
+
+/// Samples every action with equal probability, ignoring the agent's genes.
+pub struct UniformRollout;
+
+impl RolloutPolicy for UniformRollout {
+    fn name(&self) -> &'static str {
+        "uniform"
+    }
+
+    fn choose_action(&self, rng: &mut dyn RngCore) -> NpcAction {
+        let actions: Vec<NpcAction> = NpcAction::iter().collect();
+        actions[rng.gen_range(0..actions.len())]
+    }
+}
+
+/// Built-in rollout policies that can be chosen from the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RolloutPolicyKind {
+    GeneWeighted,
+    Uniform,
+}
+
+impl RolloutPolicyKind {
+    /// Builds the rollout policy for an agent with the given genes.
+    pub fn build(&self, genes: &Genes) -> Box<dyn RolloutPolicy> {
+        match self {
+            RolloutPolicyKind::GeneWeighted => Box::new(GeneWeightedRollout::new(genes)),
+            RolloutPolicyKind::Uniform => Box::new(UniformRollout),
+        }
+    }
+}
+
+/// Settings for the rollout phase.
+/// `horizon` is the number of simulated turns a rollout plays out before it is scored.
+/// `reward_scale` is the gold a rollout has to make to score 1, the rewards stored in the tree
+/// are in that unit so they compare with the gene-derived priors and the exploration constants.
+#[derive(Resource, Clone)]
+pub struct RolloutConfig {
+    pub policy: RolloutPolicyKind,
+    pub horizon: u32,
+    pub reward_scale: f64,
+}
+
+impl RolloutConfig {
+    /// Turns the gold a rollout made into the reward stored in the tree.
+    pub fn normalise_reward(&self, gold: f64) -> f64 {
+        gold / self.reward_scale
+    }
+}
+
+impl Default for RolloutConfig {
+    fn default() -> Self {
+        RolloutConfig {
+            policy: RolloutPolicyKind::GeneWeighted,
+            horizon: 50,
+            // Finding a whole treasure is as good as the agent's favourite action
+            reward_scale: DEFAULT_TREASURE_REWARD as f64,
+        }
+    }
+}
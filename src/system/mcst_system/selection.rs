@@ -1,10 +1,11 @@
-use bevy::ecs::system::{Query, Res, ResMut};
+use bevy::ecs::system::{Res, ResMut};
 
-use crate::{
-    system::mcst_tree::{mcst_node::{Node, NodeType}, simulation_tree::SimulationTree},
-    npcs::{agent::Agent, npc_components::npc_status::Status},
-    MCSTFlag,
-};
+use rand::RngCore;
+
+use crate::system::mcst_tree::{mcst_node::NodeId, mcst_tree::MCTSTree, simulation_tree::SimulationTree};
+use crate::system::random::{RngStream, RngStreams};
+
+use super::budget::{MctsBudget, MctsUsage};
 
 /*To run this program we have two phases
     We start off with the MCST phase, which will attempt to create MCST action desicion trees for every agent
@@ -38,90 +39,94 @@ use crate::{
             The result is added to each node's total reward and visits, and then we go to the next node in the queue. This is done until we reach the root node, and then we stop.
 */
 /*
-    Check if the agent is currently idle. If its not, continue
-    Check if the npc action is currently none, send an error if it isnt because it should never be idle with npc_action not none
-    Check if the flag for the end of the selection phase is ended
-        If it is, then we select an action at random
+    Only runs during TurnState::MCTSPhase. The search never changes the agent itself,
+    the best action is committed when the ExecutionPhase starts.
     In the current tree tied to the agent, we get the current node
-        1. Check for if a global end condition is met. For now if depth is 256 for the current node we stop the selection phase
+        1. Check for if a global end condition is met. If the current node is deeper than the budget's max depth we stop the selection phase
         2. We will select an action using the select_action() method
         3. We check if the current node is a leaf node. If it is, we mark the mcst tree with the flag that simulation phase has ended
 
     We assume that other systems will handle when the agent is idle, when the total simulation will end, etc
    */
 pub fn selection_system(
-    mcst_flag: Res<MCSTFlag>,
+    budget: Res<MctsBudget>,
+    usage: Res<MctsUsage>,
+    mut rng_streams: ResMut<RngStreams>,
     mut simulation_tree: ResMut<SimulationTree>,
-    mut agents: Query<&mut Agent>,
 ) {
-    //If not in MCST phase, skip this step
-    if !mcst_flag.0 {
-        return;
-    }
+    // Trees that used up their budget do not start another iteration
+    let exhausted: Vec<i32> = simulation_tree
+        .trees
+        .keys()
+        .copied()
+        .filter(|agent_id| usage.exhausted_by(&budget, *agent_id, &simulation_tree).is_some())
+        .collect();
 
-    for mut agent in agents.iter_mut() {
-        let agent_id = agent.get_id();
+    for (agent_id, tree) in simulation_tree.trees.iter_mut() {
+        if exhausted.contains(agent_id) {
+            continue;
+        }
 
-        // 1. Check if the agent is currently idle. If not, continue.
-        if agent.get_status() != Status::Idle {
+        // Wait until the current iteration has been expanded, simulated and backpropagated
+        if tree.is_ready_for_expansion() || tree.is_ready_for_rollout() {
             continue;
         }
 
-        // 2. Get the MCTS tree for the current agent.
-        if let Some(tree) = simulation_tree.get_tree_mut(agent_id) {
-            let mut set_ready_for_expansion = false;
-            let mut set_in_selection_phase = false;
-            let mut new_current_node = None;
-
-            if let Some(current_node) = tree.get_current_node() {
-                let current_node_lock = current_node.lock().unwrap();
-
-                // 3.a. Check for a global end condition (e.g., depth of 256).
-                if current_node_lock.depth >= 256 {
-                    set_in_selection_phase = false;
-                } else if current_node_lock.children.is_empty() {
-                    // 3.b. If the current node has no children, mark it for expansion.
-                    set_ready_for_expansion = true;
-                    set_in_selection_phase = false;
-                } else {
-                    // 3.c. Otherwise, select the best action using UCT.
-                    new_current_node = select_best_action_using_uct(&mut agent, &current_node_lock, agent_id);
-                }
-            }
+        let mut set_ready_for_expansion = false;
+        let mut set_ready_for_rollout = false;
+        let mut set_in_selection_phase = false;
+        let mut new_current_node = None;
 
-            // Apply changes to the tree outside the scope of current_node_lock.
-            if set_ready_for_expansion {
-                tree.set_ready_for_expansion(true);
-            }
-            if !set_in_selection_phase {
-                tree.set_in_selection_phase(false);
-            }
-            if let Some(new_node) = new_current_node {
-                tree.set_current_node(new_node);
+        if let Some(current_node) = tree.get_current_node() {
+            let node = tree.get_node(current_node);
+
+            // 1.a. Check for a global end condition (the budget's max depth).
+            if node.depth >= budget.max_depth {
+                // Score the node as it is instead of expanding it further.
+                set_ready_for_rollout = true;
+                set_in_selection_phase = false;
+            } else if node.is_leaf() {
+                // 1.b. If the current node has no children, mark it for expansion.
+                set_ready_for_expansion = true;
+                set_in_selection_phase = false;
+            } else {
+                // 1.c. Otherwise, select the best action using the tree's selection policy.
+                let rng = rng_streams.agent_stream(RngStream::Selection, *agent_id);
+                new_current_node = select_best_action(tree, current_node, *agent_id, rng);
             }
         }
+
+        // Apply changes to the tree once the node is no longer borrowed.
+        if set_ready_for_expansion {
+            tree.set_ready_for_expansion(true);
+        }
+        if set_ready_for_rollout {
+            tree.set_ready_for_rollout(true);
+        }
+        if !set_in_selection_phase {
+            tree.set_in_selection_phase(false);
+        }
+        if let Some(new_node) = new_current_node {
+            tree.set_current_node(new_node);
+        }
     }
 }
 
-/// Helper function to select the best action using UCT.
-/// Returns the selected action node if successful.
-pub fn select_best_action_using_uct(
-    agent: &mut Agent,
-    current_node_lock: &std::sync::MutexGuard<Node>,
+/// Helper function to select the best action using the tree's selection policy.
+/// Returns the id of the selected action node if successful.
+pub fn select_best_action(
+    tree: &MCTSTree,
+    current_node: NodeId,
     agent_id: i32,
-) -> Option<std::sync::Arc<std::sync::Mutex<Node>>> {
-    if let Some(best_action_node) = current_node_lock.select_action(1.414) {
-        if let Ok(best_action_node_lock) = best_action_node.lock() {
-            if let NodeType::ActionNode { action } = best_action_node_lock.node_type {
-                agent.set_action(action);
-            }
-        }
-        Some(best_action_node)
-    } else {
+    rng: &mut dyn RngCore,
+) -> Option<NodeId> {
+    let best_action_node = tree.select_action(current_node, rng);
+    if best_action_node.is_none() {
         eprintln!(
-            "Agent {}: Failed to select an action using UCT.",
-            agent_id
+            "Agent {}: Failed to select an action using {}.",
+            agent_id,
+            tree.get_policy().name()
         );
-        None
     }
-}
\ No newline at end of file
+    best_action_node
+}
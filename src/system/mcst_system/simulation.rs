@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use bevy::prelude::*;
+use rand::RngCore;
+
+use crate::npcs::agent::Agent;
+use crate::npcs::monster::Monster;
+use crate::npcs::npc_components::npc_action::NpcAction;
+use crate::npcs::npc_components::npc_base::NPCBase;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::player::Player;
+use crate::npcs::treasure::Treasure;
+use crate::system::forward_model::game_state::{GameState, NpcSnapshot};
+use crate::system::mcst_tree::simulation_tree::SimulationTree;
+use crate::system::pathfinding::danger::DangerConfig;
+use crate::system::random::{RngStream, RngStreams};
+use crate::WorldSim;
+
+use super::budget::MctsUsage;
+use super::rollout_policy::{RolloutConfig, RolloutPolicy};
+
+/// Plays out a clone of `state` for `horizon` turns.
+/// The searching agent plays the actions of `plan` in order, each one the next time it is idle,
+/// then falls back to its rollout policy like every other idle agent. Returns how much the worth of the searching agent's
+/// inventory changed, so food eaten and goods lost count against it.
+pub fn rollout(
+    state: &GameState,
+    agent_id: i32,
+    plan: &[NpcAction],
+    policies: &[(i32, Box<dyn RolloutPolicy>)],
+    horizon: u32,
+    rng: &mut dyn RngCore,
+) -> f64 {
+    let mut state = state.clone();
+    let mut plan = plan.iter().copied();
+    let wealth = |state: &GameState| {
+        state
+            .get_agent(agent_id)
+            .map_or(0.0, |agent_state| agent_state.agent.get_inventory().get_value())
+    };
+    let start_wealth = wealth(&state);
+
+    for _ in 0..horizon {
+        let mut actions = Vec::new();
+        for agent_state in state.agents.iter() {
+            if agent_state.agent.get_status() != Status::Idle {
+                continue;
+            }
+            let id = agent_state.agent.get_id();
+            let planned = if id == agent_id { plan.next() } else { None };
+            let action = if let Some(action) = planned {
+                action
+            } else if let Some((_, policy)) = policies.iter().find(|(policy_id, _)| *policy_id == id) {
+                policy.choose_action(rng)
+            } else {
+                continue;
+            };
+            actions.push((id, action));
+        }
+
+        state.step(&actions);
+
+        // Nothing more to gain once the agent is dead
+        match state.get_agent(agent_id) {
+            Some(agent_state) if agent_state.agent.get_status() != Status::Dead => {}
+            _ => break,
+        }
+    }
+
+    wealth(&state) - start_wealth
+}
+
+/// Runs a rollout for every tree that has just expanded a node and stores the reward
+/// for the backpropagation phase.
+#[allow(clippy::too_many_arguments)]
+pub fn simulation_system(
+    rollout_config: Res<RolloutConfig>,
+    world_sim: Res<WorldSim>,
+    danger: Res<DangerConfig>,
+    mut usage: ResMut<MctsUsage>,
+    mut rng_streams: ResMut<RngStreams>,
+    mut simulation_tree: ResMut<SimulationTree>,
+    agents: Query<(&Agent, &NPCBase)>,
+    monsters: Query<(&Monster, &NPCBase)>,
+    treasures: Query<(&Treasure, &NPCBase)>,
+    player: Query<&NPCBase, With<Player>>,
+) {
+    if !simulation_tree.trees.values().any(|tree| tree.is_ready_for_rollout()) {
+        return;
+    }
+
+    // Snapshot the game once, every rollout of this frame starts from a clone of it
+    let mut state = GameState::new(Arc::new(world_sim.get_world().clone()));
+    state.danger = *danger;
+    state.player = player.get_single().ok().map(|npc_base| npc_base.get_position());
+    for (agent, npc_base) in agents.iter() {
+        state.add_agent(agent.clone(), NpcSnapshot::from_base(npc_base));
+    }
+    for (monster, npc_base) in monsters.iter() {
+        state.add_monster(monster.clone(), NpcSnapshot::from_base(npc_base));
+    }
+    for (treasure, npc_base) in treasures.iter() {
+        state.add_treasure(treasure.clone(), NpcSnapshot::from_base(npc_base));
+    }
+
+    let policies: Vec<(i32, Box<dyn RolloutPolicy>)> = agents
+        .iter()
+        .map(|(agent, _)| (agent.get_id(), rollout_config.policy.build(agent.get_genes())))
+        .collect();
+
+    for (agent, _) in agents.iter() {
+        let agent_id = agent.get_id();
+        let tree = match simulation_tree.get_tree_mut(agent_id) {
+            Some(tree) => tree,
+            None => continue,
+        };
+
+        if !tree.is_ready_for_rollout() {
+            continue;
+        }
+
+        // Replay the actions leading to the node being scored, not just its own action
+        let plan = match tree.get_current_node().map(|node| tree.get_action_path(node)) {
+            Some(plan) if !plan.is_empty() => plan,
+            _ => vec![agent.get_action()],
+        };
+
+        let start = Instant::now();
+        let reward = rollout(
+            &state,
+            agent_id,
+            &plan,
+            &policies,
+            rollout_config.horizon,
+            rng_streams.agent_stream(RngStream::Rollout, agent_id),
+        );
+
+        usage.add_time(agent_id, start.elapsed());
+
+        tree.set_rollout_reward(rollout_config.normalise_reward(reward));
+        tree.set_ready_for_rollout(false);
+    }
+}
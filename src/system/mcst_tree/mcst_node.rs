@@ -1,9 +1,11 @@
-use std::sync::{Arc, Mutex};
 use crate::npcs::npc_components::npc_action::NpcAction;
 use crate::npcs::npc_components::npc_type::NPCType;
 use std::collections::HashMap;
 
-#[derive(Clone, PartialEq, Eq)]
+/// Index of a node inside the arena owned by its `MCTSTree`.
+pub type NodeId = usize;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum NodeType {
     ActionNode {
         action: NpcAction,
@@ -28,203 +30,167 @@ impl NodeType {
     }
 }
 
+/// A single entry of the tree arena. Parent and children are stored as ids
+/// into the owning `MCTSTree`, so nodes never hold references to each other.
+/// Rewards are signed, so losses such as damage taken or theft suffered lower the mean.
+/// Virtual visits seeded from priors count towards `visits` and the mean only,
+/// the variance and the reward range come from real rollouts.
 #[derive(Clone)]
 pub struct Node {
     pub node_type: NodeType,
     pub visits: usize,
-    pub total_reward: u32,
+    pub total_reward: f64,
+    pub sum_squared_reward: f64,
+    pub min_reward: f64,
+    pub max_reward: f64,
+    pub virtual_visits: usize,
+    pub virtual_reward: f64,
+    pub prior: f64,
     pub depth: u32,
-    pub parent: Option<Arc<Mutex<Node>>>,
-    pub children: Vec<Arc<Mutex<Node>>>,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    child_lookup: HashMap<NodeType, NodeId>,
 }
 
 impl Node {
     /// Creates a new node.
-    pub fn new(node_type: NodeType, depth: u32, parent: Option<Arc<Mutex<Node>>>) -> Self {
+    pub fn new(node_type: NodeType, depth: u32, parent: Option<NodeId>) -> Self {
         Node {
             node_type,
             visits: 0,
-            total_reward: 0,
+            total_reward: 0.0,
+            sum_squared_reward: 0.0,
+            min_reward: f64::INFINITY,
+            max_reward: f64::NEG_INFINITY,
+            virtual_visits: 0,
+            virtual_reward: 0.0,
+            prior: 0.0,
             depth,
             parent,
             children: Vec::new(),
+            child_lookup: HashMap::new(),
         }
     }
 
-    /// Returns the children of the node.
-    pub fn get_children(&self) -> &Vec<Arc<Mutex<Node>>> {
-        &self.children
+    /// Records one visit with the given reward.
+    pub fn record_reward(&mut self, reward: f64) {
+        self.visits += 1;
+        self.total_reward += reward;
+        self.sum_squared_reward += reward * reward;
+        self.min_reward = self.min_reward.min(reward);
+        self.max_reward = self.max_reward.max(reward);
     }
 
-    /// Checks if the node has a specific child.
-    pub fn has_child(&self, node_type: &NodeType) -> bool {
-        self.children.iter().any(|child| {
-            let child_lock = child.lock().unwrap();
-            &child_lock.node_type == node_type
-        })
+    /// Returns the mean reward over all visits, or 0 if the node was never visited.
+    pub fn mean_reward(&self) -> f64 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.total_reward / self.visits as f64
+        }
     }
 
-    /// Checks if the node has a parent.
-    pub fn has_parent(&self) -> bool {
-        self.parent.is_some()
+    /// Returns the number of visits that came from rollouts rather than priors.
+    pub fn real_visits(&self) -> usize {
+        self.visits - self.virtual_visits
     }
 
-    /// Returns a reference to the parent node, if it exists.
-    pub fn get_parent(&self) -> Option<&Arc<Mutex<Node>>> {
-        self.parent.as_ref()
+    /// Returns the population variance of the rewards of the real visits of this node.
+    pub fn reward_variance(&self) -> f64 {
+        let visits = self.real_visits();
+        if visits == 0 {
+            return 0.0;
+        }
+        let mean = (self.total_reward - self.virtual_reward) / visits as f64;
+        (self.sum_squared_reward / visits as f64 - mean * mean).max(0.0)
     }
 
-    /// Removes the parent reference from the node.
-    pub fn remove_parent(&mut self) {
-        self.parent = None;
+    /// Sets the prior probability of selecting this node, used by policies such as PUCT.
+    pub fn set_prior(&mut self, prior: f64) {
+        self.prior = prior;
     }
 
-    /// Adds a child node to the current node.
-    /// Updates the depth of the child based on the parent's depth and the child type.
-    /// Returns an error if a similar node type already exists.
-    pub fn add_child(&mut self, child: Arc<Mutex<Node>>) -> Result<(), &'static str> {
-        let mut child_lock = child.lock().unwrap();
-
-        // Ensure no duplicate child node types exist.
-        if self.has_child(&child_lock.node_type) {
-            return Err("A similar node type already exists as a child.");
-        }
-
-        // Update the depth of the child node.
-        if let NodeType::ActionNode { .. } = child_lock.node_type {
-            child_lock.depth = self.depth + 1;
-        } else {
-            child_lock.depth = self.depth;
-        }
-
-        drop(child_lock);
-        self.children.push(child);
-        Ok(())
+    /// Adds `visits` virtual visits of `reward` to the visit count and the mean.
+    pub fn add_virtual_visits(&mut self, visits: usize, reward: f64) {
+        self.visits += visits;
+        self.total_reward += reward * visits as f64;
+        self.virtual_visits += visits;
+        self.virtual_reward += reward * visits as f64;
     }
 
-    /// Calculates the height of the tree starting from this node.
-    pub fn calculate_height(&self) -> u32 {
-        if self.children.is_empty() {
-            1
-        } else {
-            1 + self.children.iter().map(|child| {
-                let child_lock = child.lock().unwrap();
-                child_lock.calculate_height()
-            }).max().unwrap_or(0)
-        }
+    /// Seeds a freshly expanded node with its prior and `virtual_visits` virtual visits
+    /// of `virtual_reward`, so the search starts biased towards the agent's preferences.
+    pub fn seed_prior(&mut self, prior: f64, virtual_visits: usize, virtual_reward: f64) {
+        self.prior = prior;
+        self.add_virtual_visits(virtual_visits, virtual_reward);
     }
 
-    /// Backpropagates the reward up the tree, averaging rewards based on visits.
-    pub fn backpropagate(&mut self, reward: u32) {
-        self.visits += 1;
-        self.total_reward += reward;
-
-        if let Some(parent) = &self.parent {
-            let mut parent_lock = parent.lock().unwrap();
-            parent_lock.backpropagate(reward / self.visits as u32);
-        }
+    /// Returns the ids of the children of the node.
+    pub fn get_children(&self) -> &Vec<NodeId> {
+        &self.children
     }
 
+    /// Checks if the node has a specific child.
+    pub fn has_child(&self, node_type: &NodeType) -> bool {
+        self.child_lookup.contains_key(node_type)
+    }
 
-    /// Recursively calculates the Monte Carlo score for each action and applies UCT.
-    pub fn calculate_monte_carlo(&self, exploration_constant: f64) -> Option<NpcAction> {
-        // Map to store total rewards and visit counts for each action
-        let mut action_rewards: HashMap<NpcAction, (u32, usize)> = HashMap::new();
-
-        // Recursively traverse children
-        for child in &self.children {
-            let child_lock = child.lock().unwrap();
-
-            // If the child is an ActionNode, propagate its reward and visits
-            if let NodeType::ActionNode { action } = child_lock.node_type {
-                let total_reward = child_lock.total_reward;
-                let visits = child_lock.visits;
-
-                // Update the action_rewards map
-                let entry = action_rewards.entry(action).or_insert((0, 0));
-                entry.0 += total_reward; // Accumulate rewards
-                entry.1 += visits;       // Accumulate visits
-            } else {
-                // Recursively calculate rewards for non-ActionNodes
-                child_lock.calculate_monte_carlo(exploration_constant);
-            }
-        }
+    /// Returns the id of the child with the given node type, if it exists.
+    pub fn get_child(&self, node_type: &NodeType) -> Option<NodeId> {
+        self.child_lookup.get(node_type).copied()
+    }
 
-        // Calculate UCT scores for each action
-        let mut best_action = None;
-        let mut best_score = f64::MIN;
-
-        for (action, (total_reward, visits)) in action_rewards {
-            let uct_score = if visits == 0 {
-                // Assign a very high score to unexplored actions
-                f64::INFINITY
-            } else {
-                // Calculate average reward and UCT score
-                let average_reward = total_reward as f64 / visits as f64;
-                let parent_visits = self.visits.max(1); // Avoid division by zero
-                average_reward
-                    + exploration_constant * ((parent_visits as f64).ln() / visits as f64).sqrt()
-            };
-
-            if uct_score > best_score {
-                best_score = uct_score;
-                best_action = Some(action);
-            }
-        }
+    /// Returns the id of the ActionNode child for the given action, if it exists.
+    pub fn get_action_child(&self, action: NpcAction) -> Option<NodeId> {
+        self.get_child(&NodeType::ActionNode { action })
+    }
 
-        best_action
+    /// Registers a child id under its node type.
+    pub(crate) fn push_child(&mut self, node_type: NodeType, child: NodeId) {
+        self.child_lookup.insert(node_type, child);
+        self.children.push(child);
     }
 
-    /// Selects the best action based on Monte Carlo scores and UCT, and returns the corresponding child node.
-    pub fn select_action(&self, exploration_constant: f64) -> Option<Arc<Mutex<Node>>> {
-        // Calculate the best action using Monte Carlo
-        let best_action = self.calculate_monte_carlo(exploration_constant)?;
+    /// Moves a child to a new node type key after its type has changed.
+    pub(crate) fn rekey_child(&mut self, old_type: &NodeType, node_type: NodeType, child: NodeId) {
+        self.child_lookup.remove(old_type);
+        self.child_lookup.insert(node_type, child);
+    }
 
-        // Find the child node corresponding to the best action
-        for child in &self.children {
-            let child_lock = child.lock().unwrap();
-            if let NodeType::ActionNode { action } = &child_lock.node_type {
-                if *action == best_action {
-                    return Some(child.clone());
-                }
+    /// Replaces the child ids after the arena has been compacted.
+    pub(crate) fn remap_children(&mut self, remap: &[Option<NodeId>]) {
+        self.children = self
+            .children
+            .iter()
+            .filter_map(|child| remap[*child])
+            .collect();
+        for child in self.child_lookup.values_mut() {
+            if let Some(new_id) = remap[*child] {
+                *child = new_id;
             }
         }
-
-        None
     }
 
-    /// Expands the current node by adding a new child node with the given action.
-    pub fn expand(&mut self, action: NpcAction) -> Arc<Mutex<Node>> {
-        let new_node = Arc::new(Mutex::new(Node::new(
-            NodeType::ActionNode { action },
-            self.depth + 1,
-            Some(Arc::new(Mutex::new(self.clone()))),
-        )));
-        self.children.push(new_node.clone());
-        new_node
+    /// Checks if the node has a parent.
+    pub fn has_parent(&self) -> bool {
+        self.parent.is_some()
     }
 
-    /// Recursively updates the depth of the current node and its children.
-    /// Increments the depth only if the current node is an ActionNode.
-    pub fn update_depth(&mut self, parent_depth: u32) {
-        // Update the depth based on the node type.
-        if let NodeType::ActionNode { .. } = self.node_type {
-            self.depth = parent_depth + 1; // Increment depth for ActionNode.
-        } else {
-            self.depth = parent_depth; // Inherit depth for InformationNode.
-        }
+    /// Returns the id of the parent node, if it exists.
+    pub fn get_parent(&self) -> Option<NodeId> {
+        self.parent
+    }
 
-        // Recursively update the depth of children.
-        for child in &self.children {
-            let mut child_lock = child.lock().unwrap();
-            child_lock.update_depth(self.depth);
-        }
+    /// Removes the parent reference from the node.
+    pub fn remove_parent(&mut self) {
+        self.parent = None;
     }
-    
-    pub fn set_node_type(&mut self, node_type: NodeType) {
-        self.node_type = node_type;
+
+    /// Checks if the node has no children.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
     }
-    
+
     pub fn get_node_type(&self) -> &NodeType {
         &self.node_type
     }
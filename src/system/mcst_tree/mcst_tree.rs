@@ -1,105 +1,287 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use super::mcst_node::{Node, NodeType};
+use rand::RngCore;
 
+use crate::npcs::npc_components::npc_action::NpcAction;
+
+use super::mcst_node::{Node, NodeId, NodeType};
+use super::selection_policy::{SelectionPolicy, Uct};
+
+/// Monte Carlo search tree stored as an arena of nodes.
+/// Nodes refer to their parent and children by `NodeId`, which is an index into `nodes`.
+#[derive(Clone)]
 pub struct MCTSTree {
-    pub root: Option<Arc<Mutex<Node>>>,
-    pub current_node: Option<Arc<Mutex<Node>>>,
+    nodes: Vec<Node>,
+    root: NodeId,
+    current_node: Option<NodeId>,
+    policy: Arc<dyn SelectionPolicy>,
     pub in_selection_phase: bool,
     pub ready_for_expansion: bool,
-}
-
-impl Clone for MCTSTree {
-    fn clone(&self) -> Self {
-        MCTSTree {
-            root: self.root.as_ref().map(|node| Arc::clone(node)),
-            current_node: self.current_node.as_ref().map(|node| Arc::clone(node)),
-            in_selection_phase: self.in_selection_phase,
-            ready_for_expansion: self.ready_for_expansion,
-        }
-    }
+    pub ready_for_rollout: bool,
+    rollout_reward: Option<f64>,
 }
 
 impl MCTSTree {
     /// Creates a new MCTSTree with a "null" root node.
     pub fn new() -> Self {
-        let null_node = Arc::new(Mutex::new(Node::new(
-            NodeType::NullNode,
-            0,
-            None,
-        )));
         MCTSTree {
-            root: Some(null_node.clone()),
-            current_node: Some(null_node),
+            nodes: vec![Node::new(NodeType::NullNode, 0, None)],
+            root: 0,
+            current_node: Some(0),
+            policy: Arc::new(Uct { exploration_constant: 1.414 }),
             in_selection_phase: true, // Start in the selection phase
             ready_for_expansion: false, // Initially not ready for expansion
+            ready_for_rollout: false,
+            rollout_reward: None,
         }
     }
 
-    /// Sets the root node of the tree and removes its parent reference.
-    /// Recursively updates the depth of the tree starting from the root.
-    pub fn set_root(&mut self, node: Arc<Mutex<Node>>) {
-        let mut root_lock = node.lock().unwrap();
+    /// Returns the node with the given id.
+    pub fn get_node(&self, node_id: NodeId) -> &Node {
+        &self.nodes[node_id]
+    }
+
+    /// Returns a mutable reference to the node with the given id.
+    pub fn get_node_mut(&mut self, node_id: NodeId) -> &mut Node {
+        &mut self.nodes[node_id]
+    }
+
+    /// Returns the number of nodes stored in the tree.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Changes the type of a node and keeps the parent's child lookup in sync.
+    pub fn set_node_type(&mut self, node_id: NodeId, node_type: NodeType) {
+        let old_type = std::mem::replace(&mut self.nodes[node_id].node_type, node_type.clone());
+        if let Some(parent) = self.nodes[node_id].parent {
+            self.nodes[parent].rekey_child(&old_type, node_type, node_id);
+        }
+    }
+
+    /// Adds a child node of the given type under `parent`.
+    /// Updates the depth of the child based on the parent's depth and the child type.
+    /// Returns an error if a similar node type already exists.
+    pub fn add_child(&mut self, parent: NodeId, node_type: NodeType) -> Result<NodeId, &'static str> {
+        // Ensure no duplicate child node types exist.
+        if self.nodes[parent].has_child(&node_type) {
+            return Err("A similar node type already exists as a child.");
+        }
 
+        // Update the depth of the child node.
+        let parent_depth = self.nodes[parent].depth;
+        let depth = if let NodeType::ActionNode { .. } = node_type {
+            parent_depth + 1
+        } else {
+            parent_depth
+        };
+
+        let child = self.nodes.len();
+        self.nodes.push(Node::new(node_type.clone(), depth, Some(parent)));
+        self.nodes[parent].push_child(node_type, child);
+        Ok(child)
+    }
+
+    /// Expands a node by adding an ActionNode child for the given action.
+    /// Returns the existing child if the action has already been expanded.
+    pub fn expand(&mut self, node_id: NodeId, action: NpcAction) -> NodeId {
+        if let Some(child) = self.nodes[node_id].get_action_child(action) {
+            return child;
+        }
+        self.add_child(node_id, NodeType::ActionNode { action })
+            .expect("Action child was checked to be absent")
+    }
+
+    /// Backpropagates the reward from a node up to the root.
+    /// Every node on the path records the full reward so its mean stays comparable with its siblings.
+    pub fn backpropagate(&mut self, node_id: NodeId, reward: f64) {
+        let mut current = Some(node_id);
+        while let Some(id) = current {
+            let node = &mut self.nodes[id];
+            node.record_reward(reward);
+            current = node.parent;
+        }
+    }
+
+    /// Seeds a node with its prior and virtual visits, see `Node::seed_prior`.
+    /// The ancestors count the virtual visits too, so a parent has seen every visit of its children.
+    pub fn seed_prior(&mut self, node_id: NodeId, prior: f64, virtual_visits: usize, virtual_reward: f64) {
+        self.nodes[node_id].seed_prior(prior, virtual_visits, virtual_reward);
+        let mut current = self.nodes[node_id].parent;
+        while let Some(id) = current {
+            let node = &mut self.nodes[id];
+            node.add_virtual_visits(virtual_visits, virtual_reward);
+            current = node.parent;
+        }
+    }
+
+    /// Returns the actions of the ActionNodes on the path from the root down to a node,
+    /// in the order they would be played.
+    /// The root's own action has already been committed, so it is not part of the path.
+    pub fn get_action_path(&self, node_id: NodeId) -> Vec<NpcAction> {
+        let mut actions = Vec::new();
+        let mut current = Some(node_id);
+        while let Some(id) = current {
+            if id == self.root {
+                break;
+            }
+            if let NodeType::ActionNode { action } = self.nodes[id].node_type {
+                actions.push(action);
+            }
+            current = self.nodes[id].parent;
+        }
+        actions.reverse();
+        actions
+    }
+
+    /// Selects the child of a node to descend into using the tree's selection policy.
+    pub fn select_action(&self, node_id: NodeId, rng: &mut dyn RngCore) -> Option<NodeId> {
+        self.policy.select_child(self, node_id, rng)
+    }
+
+    /// Returns the ActionNode child of a node with the most visits, which is the action
+    /// the search is most confident in.
+    pub fn get_best_child(&self, node_id: NodeId) -> Option<NodeId> {
+        self.nodes[node_id]
+            .children
+            .iter()
+            .copied()
+            .filter(|child| matches!(self.nodes[*child].node_type, NodeType::ActionNode { .. }))
+            .max_by(|a, b| {
+                let (a, b) = (&self.nodes[*a], &self.nodes[*b]);
+                a.visits
+                    .cmp(&b.visits)
+                    .then(a.mean_reward().partial_cmp(&b.mean_reward()).unwrap_or(std::cmp::Ordering::Equal))
+            })
+    }
+
+    /// Clears the per-iteration flags so a new search starts from the root.
+    pub fn reset_search(&mut self) {
+        self.current_node = Some(self.root);
+        self.in_selection_phase = true;
+        self.ready_for_expansion = false;
+        self.ready_for_rollout = false;
+        self.rollout_reward = None;
+    }
+
+    /// Returns the selection policy used by this tree.
+    pub fn get_policy(&self) -> &Arc<dyn SelectionPolicy> {
+        &self.policy
+    }
+
+    /// Sets the selection policy used by this tree.
+    pub fn set_policy(&mut self, policy: Arc<dyn SelectionPolicy>) {
+        self.policy = policy;
+    }
+
+    /// Calculates the height of the subtree starting from a node.
+    pub fn calculate_height(&self, node_id: NodeId) -> u32 {
+        let mut height = 0;
+        let mut stack = vec![(node_id, 1)];
+        while let Some((id, level)) = stack.pop() {
+            height = height.max(level);
+            for child in &self.nodes[id].children {
+                stack.push((*child, level + 1));
+            }
+        }
+        height
+    }
+
+    /// Updates the depth of a node and its descendants.
+    /// Increments the depth only for ActionNodes.
+    pub fn update_depth(&mut self, node_id: NodeId, parent_depth: u32) {
+        let mut stack = vec![(node_id, parent_depth)];
+        while let Some((id, depth_above)) = stack.pop() {
+            let node = &mut self.nodes[id];
+            if let NodeType::ActionNode { .. } = node.node_type {
+                node.depth = depth_above + 1; // Increment depth for ActionNode.
+            } else {
+                node.depth = depth_above; // Inherit depth for InformationNode.
+            }
+            let depth = node.depth;
+            for child in &node.children {
+                stack.push((*child, depth));
+            }
+        }
+    }
+
+    /// Sets the root node of the tree and removes its parent reference.
+    /// Nodes outside the new root's subtree are dropped and the arena is compacted,
+    /// so the cost is proportional to the size of the kept subtree.
+    pub fn set_root(&mut self, node_id: NodeId) {
         // Ensure the root node is not an InformationNode.
-        if let NodeType::InformationNode { .. } = root_lock.node_type {
+        if let NodeType::InformationNode { .. } = self.nodes[node_id].node_type {
             panic!("The root node cannot be an InformationNode.");
         }
 
-        // Remove the parent reference and set the root.
-        root_lock.remove_parent();
-        root_lock.update_depth(1); // Start depth from 1.
-        drop(root_lock);
+        // Collect the subtree in breadth-first order so the new root gets id 0.
+        let mut order = vec![node_id];
+        let mut index = 0;
+        while index < order.len() {
+            order.extend(self.nodes[order[index]].children.iter().copied());
+            index += 1;
+        }
+
+        let mut remap: Vec<Option<NodeId>> = vec![None; self.nodes.len()];
+        for (new_id, old_id) in order.iter().enumerate() {
+            remap[*old_id] = Some(new_id);
+        }
 
-        self.root = Some(node.clone());
-        self.current_node = Some(node);
+        let mut old_nodes: Vec<Option<Node>> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.nodes = order
+            .iter()
+            .map(|old_id| {
+                let mut node = old_nodes[*old_id].take().expect("Node visited twice");
+                node.parent = node.parent.and_then(|parent| remap[parent]);
+                node.remap_children(&remap);
+                node
+            })
+            .collect();
+
+        // Remove the parent reference and set the root.
+        self.root = 0;
+        self.nodes[0].remove_parent();
+        self.update_depth(0, 1); // Start depth from 1.
+        self.current_node = Some(0);
     }
 
-    /// Returns a reference to the root node of the tree.
-    pub fn get_root(&self) -> Option<&Arc<Mutex<Node>>> {
-        self.root.as_ref()
+    /// Returns the id of the root node of the tree.
+    pub fn get_root(&self) -> NodeId {
+        self.root
     }
 
-    /// Returns a reference to the current node of the tree.
-    pub fn get_current_node(&self) -> Option<&Arc<Mutex<Node>>> {
-        self.current_node.as_ref()
+    /// Returns the id of the current node of the tree.
+    pub fn get_current_node(&self) -> Option<NodeId> {
+        self.current_node
     }
 
     /// Sets the current node of the tree.
-    pub fn set_current_node(&mut self, node: Arc<Mutex<Node>>) {
-        self.current_node = Some(node);
+    pub fn set_current_node(&mut self, node_id: NodeId) {
+        self.current_node = Some(node_id);
     }
 
     /// Resets the current node to the root node.
     pub fn reset_current_node_to_root(&mut self) {
-        if let Some(root) = &self.root {
-            self.current_node = Some(root.clone());
-        }
+        self.current_node = Some(self.root);
     }
 
     /// Checks if the tree has a root node.
     pub fn has_root(&self) -> bool {
-        self.root.is_some()
+        !self.nodes.is_empty()
     }
 
     /// Calculates the height of the tree starting from the root node.
     pub fn get_height(&self) -> u32 {
-        if let Some(root) = &self.root {
-            let root_lock = root.lock().unwrap();
-            root_lock.calculate_height()
-        } else {
-            0
-        }
+        self.calculate_height(self.root)
     }
 
     /// Initializes the tree with a "null" root node.
     pub fn initialize_tree(&mut self) {
-        let null_node = Arc::new(Mutex::new(Node::new(
-            NodeType::NullNode, 
-            0,
-            None,
-        )));
-        self.set_root(null_node);
+        self.nodes = vec![Node::new(NodeType::NullNode, 0, None)];
+        self.set_root(0);
     }
 
     /// Wipes the reference to the current node, effectively removing it.
@@ -109,9 +291,7 @@ impl MCTSTree {
 
     /// Sets the root node as the current node.
     pub fn set_root_current_node(&mut self) {
-        if let Some(root) = &self.root {
-            self.current_node = Some(root.clone());
-        }
+        self.current_node = Some(self.root);
     }
 
     /// Checks if the tree is in the selection phase.
@@ -133,4 +313,24 @@ impl MCTSTree {
     pub fn set_ready_for_expansion(&mut self, ready: bool) {
         self.ready_for_expansion = ready;
     }
+
+    /// Checks if the current node is waiting for a rollout.
+    pub fn is_ready_for_rollout(&self) -> bool {
+        self.ready_for_rollout
+    }
+
+    /// Sets the ready-for-rollout flag.
+    pub fn set_ready_for_rollout(&mut self, ready: bool) {
+        self.ready_for_rollout = ready;
+    }
+
+    /// Stores the reward of the last rollout until it is backpropagated.
+    pub fn set_rollout_reward(&mut self, reward: f64) {
+        self.rollout_reward = Some(reward);
+    }
+
+    /// Takes the reward of the last rollout, leaving none behind.
+    pub fn take_rollout_reward(&mut self) -> Option<f64> {
+        self.rollout_reward.take()
+    }
 }
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bevy::prelude::Resource;
+use clap::ValueEnum;
+use rand::{Rng, RngCore};
+
+use super::mcst_node::{Node, NodeId, NodeType};
+use super::mcst_tree::MCTSTree;
+
+/// Tree policy used during the selection phase to decide which child to descend into.
+pub trait SelectionPolicy: Send + Sync {
+    /// Short name used when reporting results.
+    fn name(&self) -> &'static str;
+
+    /// Scores a child of `parent`. Higher scores are selected first.
+    fn score(&self, parent: &Node, child: &Node) -> f64;
+
+    /// Selects the ActionNode child of `node_id` with the highest score.
+    fn select_child(&self, tree: &MCTSTree, node_id: NodeId, _rng: &mut dyn RngCore) -> Option<NodeId> {
+        let parent = tree.get_node(node_id);
+        let mut best_child = None;
+        let mut best_score = f64::NEG_INFINITY;
+
+        for child_id in parent.get_children() {
+            let child = tree.get_node(*child_id);
+            if let NodeType::ActionNode { .. } = child.node_type {
+                let score = self.score(parent, child);
+                if best_child.is_none() || score > best_score {
+                    best_score = score;
+                    best_child = Some(*child_id);
+                }
+            }
+        }
+
+        best_child
+    }
+}
+
+/// Plain UCT (UCB1 applied to trees).
+pub struct Uct {
+    pub exploration_constant: f64,
+}
+
+impl SelectionPolicy for Uct {
+    fn name(&self) -> &'static str {
+        "uct"
+    }
+
+    fn score(&self, parent: &Node, child: &Node) -> f64 {
+        if child.visits == 0 {
+            // Assign a very high score to unexplored actions
+            return f64::INFINITY;
+        }
+        let parent_visits = parent.visits.max(1) as f64; // Avoid ln(0)
+        child.mean_reward()
+            + self.exploration_constant * (parent_visits.ln() / child.visits as f64).sqrt()
+    }
+}
+
+/// UCB1-Tuned, which bounds the exploration term by the observed reward variance.
+/// The 1/4 bound of the original formula assumes rewards in [0, 1], so it is scaled
+/// by the squared reward range seen at the parent.
+pub struct Ucb1Tuned {
+    pub exploration_constant: f64,
+}
+
+impl SelectionPolicy for Ucb1Tuned {
+    fn name(&self) -> &'static str {
+        "ucb1-tuned"
+    }
+
+    fn score(&self, parent: &Node, child: &Node) -> f64 {
+        if child.visits == 0 {
+            return f64::INFINITY;
+        }
+        let parent_visits = parent.visits.max(1) as f64;
+        let visits = child.visits as f64;
+        let log_term = parent_visits.ln() / visits;
+
+        let range = parent.max_reward - parent.min_reward;
+        let variance_bound = if range.is_finite() && range > 0.0 {
+            0.25 * range * range
+        } else {
+            0.25
+        };
+        let variance = child.reward_variance() + (2.0 * log_term).sqrt();
+
+        child.mean_reward()
+            + self.exploration_constant * (log_term * variance.min(variance_bound)).sqrt()
+    }
+}
+
+/// PUCT as used by AlphaZero. Children are weighted by the prior stored on the node,
+/// which expansion derives from the agent's `ActionRating`.
+pub struct Puct {
+    pub exploration_constant: f64,
+}
+
+impl SelectionPolicy for Puct {
+    fn name(&self) -> &'static str {
+        "puct"
+    }
+
+    fn score(&self, parent: &Node, child: &Node) -> f64 {
+        let parent_visits = parent.visits as f64;
+        child.mean_reward()
+            + self.exploration_constant * child.prior * parent_visits.sqrt()
+                / (1.0 + child.visits as f64)
+    }
+}
+
+/// Epsilon-greedy: explores a uniformly random child with probability `epsilon`,
+/// otherwise exploits the child with the best mean reward.
+pub struct EpsilonGreedy {
+    pub epsilon: f64,
+}
+
+impl SelectionPolicy for EpsilonGreedy {
+    fn name(&self) -> &'static str {
+        "epsilon-greedy"
+    }
+
+    fn score(&self, _parent: &Node, child: &Node) -> f64 {
+        if child.visits == 0 {
+            f64::INFINITY
+        } else {
+            child.mean_reward()
+        }
+    }
+
+    fn select_child(&self, tree: &MCTSTree, node_id: NodeId, rng: &mut dyn RngCore) -> Option<NodeId> {
+        let action_children: Vec<NodeId> = tree
+            .get_node(node_id)
+            .get_children()
+            .iter()
+            .copied()
+            .filter(|child| matches!(tree.get_node(*child).node_type, NodeType::ActionNode { .. }))
+            .collect();
+
+        if action_children.is_empty() {
+            return None;
+        }
+
+        if rng.gen::<f64>() < self.epsilon {
+            return Some(action_children[rng.gen_range(0..action_children.len())]);
+        }
+
+        let parent = tree.get_node(node_id);
+        action_children.into_iter().max_by(|a, b| {
+            self.score(parent, tree.get_node(*a))
+                .partial_cmp(&self.score(parent, tree.get_node(*b)))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+}
+
+/// Built-in policies that can be chosen from the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SelectionPolicyKind {
+    Uct,
+    Ucb1Tuned,
+    Puct,
+    EpsilonGreedy,
+}
+
+impl SelectionPolicyKind {
+    /// Builds the policy with the given parameters.
+    pub fn build(&self, exploration_constant: f64, epsilon: f64) -> Arc<dyn SelectionPolicy> {
+        match self {
+            SelectionPolicyKind::Uct => Arc::new(Uct { exploration_constant }),
+            SelectionPolicyKind::Ucb1Tuned => Arc::new(Ucb1Tuned { exploration_constant }),
+            SelectionPolicyKind::Puct => Arc::new(Puct { exploration_constant }),
+            SelectionPolicyKind::EpsilonGreedy => Arc::new(EpsilonGreedy { epsilon }),
+        }
+    }
+}
+
+/// Which selection policy each agent's tree uses.
+#[derive(Resource, Clone)]
+pub struct SelectionPolicyConfig {
+    pub default_policy: SelectionPolicyKind,
+    pub agent_policies: HashMap<i32, SelectionPolicyKind>,
+    pub exploration_constant: f64,
+    pub epsilon: f64,
+}
+
+impl Default for SelectionPolicyConfig {
+    fn default() -> Self {
+        SelectionPolicyConfig {
+            default_policy: SelectionPolicyKind::Uct,
+            agent_policies: HashMap::new(),
+            exploration_constant: 1.414,
+            epsilon: 0.1,
+        }
+    }
+}
+
+impl SelectionPolicyConfig {
+    /// Returns the policy kind assigned to an agent, falling back to the default.
+    pub fn kind_for(&self, agent_id: i32) -> SelectionPolicyKind {
+        *self.agent_policies.get(&agent_id).unwrap_or(&self.default_policy)
+    }
+
+    /// Builds the policy assigned to an agent.
+    pub fn policy_for(&self, agent_id: i32) -> Arc<dyn SelectionPolicy> {
+        self.kind_for(agent_id).build(self.exploration_constant, self.epsilon)
+    }
+}
+
+/// Parses an `--agent-policy` value of the form `<agent id>=<policy>`, e.g. `3=puct`.
+pub fn parse_agent_policy(value: &str) -> Result<(i32, SelectionPolicyKind), String> {
+    let (id, kind) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected <agent id>=<policy>, got '{}'", value))?;
+    let id = id
+        .trim()
+        .parse::<i32>()
+        .map_err(|_| format!("invalid agent id '{}'", id))?;
+    let kind = SelectionPolicyKind::from_str(kind.trim(), true)?;
+    Ok((id, kind))
+}
@@ -1,12 +1,13 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use bevy::ecs::system::Resource;
 
 use super::mcst_tree::MCTSTree;
 
+/// Trees are kept in agent id order so every system walks them in the same order.
 #[derive(Resource, Default)]
 pub struct SimulationTree {
-    pub trees: HashMap<i32, MCTSTree>,
+    pub trees: BTreeMap<i32, MCTSTree>,
 }
 
 impl SimulationTree {
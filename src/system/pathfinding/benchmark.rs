@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::gameworld::position::Position;
+use crate::gameworld::world::GameWorld;
+
+use super::hierarchical::{HierarchicalGraph, CLUSTER_SIZE};
+use super::pathfinding_calculation::plain_a_star_pathfinding;
+
+/// Timings of plain A* against the hierarchical search on the same queries.
+#[derive(Debug, Default)]
+pub struct PathfindingBenchmark {
+    pub queries: usize,
+    pub clusters: usize,
+    pub graph_nodes: usize,
+    pub build_time: Duration,
+    pub plain_time: Duration,
+    pub hierarchical_time: Duration,
+    /// Mean of the hierarchical path cost divided by the plain path cost.
+    pub mean_cost_ratio: f32,
+    /// Queries plain A* solved but the hierarchical search did not.
+    pub failures: usize,
+}
+
+fn path_cost(world: &GameWorld, start: Position, path: &[Position]) -> f32 {
+    let movement = world.get_movement();
+    let mut previous = start;
+    let mut cost = 0.0;
+    for position in path {
+        cost += movement
+            .step_cost(world, previous, *position)
+            .unwrap_or(0.0);
+        previous = *position;
+    }
+    cost
+}
+
+/// Runs `queries` searches between random passable tiles further apart than a cluster,
+/// once with plain A* and once through a freshly built hierarchical graph.
+pub fn run_pathfinding_benchmark<R: Rng>(
+    world: &GameWorld,
+    queries: usize,
+    rng: &mut R,
+) -> PathfindingBenchmark {
+    let passable: Vec<Position> = world
+        .iter_tiles()
+        .filter(|(_, tile)| tile.get_tile_type().get_travel_weight() > 0.0)
+        .map(|(position, _)| position)
+        .collect();
+    let mut benchmark = PathfindingBenchmark::default();
+    if passable.len() < 2 {
+        return benchmark;
+    }
+
+    let mut pairs = Vec::new();
+    // Give up on long pairs if the world is too small to have enough of them
+    for _ in 0..queries * 100 {
+        if pairs.len() == queries {
+            break;
+        }
+        let start = *passable.choose(rng).unwrap();
+        let goal = *passable.choose(rng).unwrap();
+        if (start.x - goal.x).abs().max((start.y - goal.y).abs()) > CLUSTER_SIZE {
+            pairs.push((start, goal));
+        }
+    }
+
+    let started = Instant::now();
+    let graph = HierarchicalGraph::build(world, CLUSTER_SIZE);
+    benchmark.build_time = started.elapsed();
+    benchmark.clusters = graph.cluster_count();
+    benchmark.graph_nodes = graph.node_count();
+
+    let mut ratios = Vec::new();
+    for (start, goal) in pairs {
+        let started = Instant::now();
+        let plain = plain_a_star_pathfinding(world, start, goal);
+        benchmark.plain_time += started.elapsed();
+
+        let started = Instant::now();
+        let hierarchical = graph.find_path(world, start, goal);
+        benchmark.hierarchical_time += started.elapsed();
+
+        benchmark.queries += 1;
+        if plain.is_empty() {
+            continue;
+        }
+        match hierarchical {
+            Some(path) if !path.is_empty() => {
+                ratios.push(path_cost(world, start, &path) / path_cost(world, start, &plain))
+            }
+            _ => benchmark.failures += 1,
+        }
+    }
+
+    if !ratios.is_empty() {
+        benchmark.mean_cost_ratio = ratios.iter().sum::<f32>() / ratios.len() as f32;
+    }
+    benchmark
+}
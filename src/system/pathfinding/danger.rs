@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Resource;
+use clap::ValueEnum;
+
+use crate::gameworld::position::Position;
+use crate::gameworld::world::GameWorld;
+
+/// How the path search treats tiles near monsters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DangerMode {
+    /// Danger is added to the travel cost, so a long detour can still lose against a short risky path.
+    Soft,
+    /// Tiles with at least `block_threshold` danger cannot be entered, unless they hold the goal
+    /// or no other path exists.
+    Hard,
+}
+
+/// Shape of the danger radiated by monsters.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct DangerConfig {
+    pub mode: DangerMode,
+    /// Tiles further than this from a monster carry no danger.
+    pub radius: i32,
+    /// Danger of a monster's own tile for an agent with full SelfPreservation.
+    pub peak_cost: f32,
+    /// Part of the danger left one tile further away.
+    pub decay: f32,
+    /// Danger from which a tile is blocked in the hard mode.
+    pub block_threshold: f32,
+}
+
+impl Default for DangerConfig {
+    fn default() -> Self {
+        DangerConfig {
+            mode: DangerMode::Soft,
+            radius: 4,
+            peak_cost: 8.0,
+            decay: 0.5,
+            block_threshold: 1.0,
+        }
+    }
+}
+
+/// Danger cost of every tile near a monster, as seen by one agent.
+#[derive(Clone, Debug)]
+pub struct DangerMap {
+    mode: DangerMode,
+    block_threshold: f32,
+    costs: HashMap<Position, f32>,
+}
+
+impl DangerMap {
+    /// Radiates danger around every monster, decaying with the distance in steps
+    /// and scaled by the agent's SelfPreservation gene.
+    pub fn compute(
+        world: &GameWorld,
+        monsters: &[Position],
+        config: &DangerConfig,
+        self_preservation: f32,
+    ) -> Self {
+        let mut costs: HashMap<Position, f32> = HashMap::new();
+        let scale = config.peak_cost * self_preservation.max(0.0);
+
+        if scale > 0.0 {
+            for monster in monsters {
+                for dy in -config.radius..=config.radius {
+                    for dx in -config.radius..=config.radius {
+                        let position = Position::new(monster.x + dx, monster.y + dy);
+                        if !world.is_valid_position(position.x, position.y) {
+                            continue;
+                        }
+                        let distance = dx.abs().max(dy.abs());
+                        *costs.entry(position).or_insert(0.0) +=
+                            scale * config.decay.powi(distance);
+                    }
+                }
+            }
+        }
+
+        DangerMap {
+            mode: config.mode,
+            block_threshold: config.block_threshold,
+            costs,
+        }
+    }
+
+    pub fn get_mode(&self) -> DangerMode {
+        self.mode
+    }
+
+    /// The same danger used as a soft cost.
+    pub fn to_soft(&self) -> Self {
+        DangerMap {
+            mode: DangerMode::Soft,
+            ..self.clone()
+        }
+    }
+
+    pub fn get_cost(&self, position: Position) -> f32 {
+        self.costs.get(&position).copied().unwrap_or(0.0)
+    }
+
+    pub fn is_dangerous(&self, position: Position) -> bool {
+        self.get_cost(position) > 0.0
+    }
+
+    pub fn is_blocked(&self, position: Position) -> bool {
+        self.mode == DangerMode::Hard && self.get_cost(position) >= self.block_threshold
+    }
+
+    /// Extra cost of entering `position` on the way to `goal`, `None` if it is blocked.
+    /// The goal itself is never blocked, so a target inside the danger can still be reached.
+    pub fn penalty(&self, position: Position, goal: Position) -> Option<f32> {
+        match self.mode {
+            DangerMode::Hard if position != goal && self.is_blocked(position) => None,
+            DangerMode::Hard => Some(0.0),
+            DangerMode::Soft => Some(self.get_cost(position)),
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::gameworld::position::Position;
+use crate::gameworld::world::GameWorld;
+
+use super::pathfinding_calculation::{a_star_search, get_neighbors};
+
+/// Width and height of a cluster in tiles.
+pub const CLUSTER_SIZE: i32 = 16;
+/// Entrances at least this wide get a transition at both ends instead of one in the middle.
+const WIDE_ENTRANCE: i32 = 6;
+
+/// A rectangle of tiles that is searched on its own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cluster {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Cluster {
+    pub fn contains(&self, position: Position) -> bool {
+        position.x >= self.x
+            && position.y >= self.y
+            && position.x < self.x + self.width
+            && position.y < self.y + self.height
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct GraphNode {
+    node: usize,
+    cost: f32,
+    estimated_total_cost: f32,
+}
+
+impl Eq for GraphNode {}
+
+impl Ord for GraphNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reverse the order so the BinaryHeap pops the cheapest node first
+        other
+            .estimated_total_cost
+            .partial_cmp(&self.estimated_total_cost)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for GraphNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn is_passable(world: &GameWorld, x: i32, y: i32) -> bool {
+    world
+        .get_tile_type(x, y)
+        .map_or(false, |tile_type| tile_type.get_travel_weight() > 0.0)
+}
+
+/// Travel cost from `source` to every tile of `cluster` it can reach without leaving the cluster.
+fn cluster_costs(world: &GameWorld, cluster: &Cluster, source: Position) -> HashMap<Position, f32> {
+    let movement = world.get_movement();
+    let mut costs = HashMap::from([(source, 0.0)]);
+    let mut open_set = BinaryHeap::from([GraphNode {
+        node: 0,
+        cost: 0.0,
+        estimated_total_cost: 0.0,
+    }]);
+    let mut positions = vec![source];
+
+    while let Some(GraphNode { node, cost, .. }) = open_set.pop() {
+        let position = positions[node];
+        if cost > costs[&position] {
+            continue;
+        }
+        for neighbor in get_neighbors(world, position) {
+            if !cluster.contains(neighbor) {
+                continue;
+            }
+            let step = match movement.step_cost(world, position, neighbor) {
+                Some(step) => step,
+                None => continue,
+            };
+            let tentative = cost + step;
+            if tentative < *costs.get(&neighbor).unwrap_or(&f32::INFINITY) {
+                costs.insert(neighbor, tentative);
+                positions.push(neighbor);
+                open_set.push(GraphNode {
+                    node: positions.len() - 1,
+                    cost: tentative,
+                    estimated_total_cost: tentative,
+                });
+            }
+        }
+    }
+
+    costs
+}
+
+/// The world cut into clusters, with the entrances between neighbouring clusters as nodes of a small graph.
+/// Nodes in the same cluster are joined by their travel cost inside the cluster, the two sides of an
+/// entrance by the cost of the step across. Long searches run on this graph and are then refined
+/// into tiles one cluster at a time (HPA*). The paths are close to, but not always, the shortest.
+pub struct HierarchicalGraph {
+    cluster_size: i32,
+    clusters: Vec<Cluster>,
+    columns: i32,
+    origin: Position,
+    nodes: Vec<Position>,
+    node_index: HashMap<Position, usize>,
+    cluster_nodes: Vec<Vec<usize>>,
+    edges: Vec<Vec<(usize, f32)>>,
+}
+
+impl HierarchicalGraph {
+    pub fn build(world: &GameWorld, cluster_size: i32) -> Self {
+        let origin = Position::new(world.get_width_mind(), world.get_height_min());
+        let width = world.get_width_max() - origin.x + 1;
+        let height = world.get_height_max() - origin.y + 1;
+        let columns = (width + cluster_size - 1) / cluster_size;
+        let rows = (height + cluster_size - 1) / cluster_size;
+
+        let mut clusters = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = origin.x + column * cluster_size;
+                let y = origin.y + row * cluster_size;
+                clusters.push(Cluster {
+                    x,
+                    y,
+                    width: cluster_size.min(origin.x + width - x),
+                    height: cluster_size.min(origin.y + height - y),
+                });
+            }
+        }
+
+        let mut graph = HierarchicalGraph {
+            cluster_size,
+            cluster_nodes: vec![Vec::new(); clusters.len()],
+            clusters,
+            columns,
+            origin,
+            nodes: Vec::new(),
+            node_index: HashMap::new(),
+            edges: Vec::new(),
+        };
+
+        // Entrances on the right and bottom border of every cluster
+        for index in 0..graph.clusters.len() {
+            let cluster = graph.clusters[index];
+            let right = cluster.x + cluster.width - 1;
+            let bottom = cluster.y + cluster.height - 1;
+            let border_right: Vec<(Position, Position)> = (cluster.y..=bottom)
+                .map(|y| (Position::new(right, y), Position::new(right + 1, y)))
+                .collect();
+            let border_bottom: Vec<(Position, Position)> = (cluster.x..=right)
+                .map(|x| (Position::new(x, bottom), Position::new(x, bottom + 1)))
+                .collect();
+            graph.add_entrances(world, &border_right);
+            graph.add_entrances(world, &border_bottom);
+        }
+
+        // Join the nodes of every cluster
+        for index in 0..graph.clusters.len() {
+            let cluster = graph.clusters[index];
+            let nodes = graph.cluster_nodes[index].clone();
+            for from in nodes.iter() {
+                let costs = cluster_costs(world, &cluster, graph.nodes[*from]);
+                for to in nodes.iter() {
+                    if from == to {
+                        continue;
+                    }
+                    if let Some(cost) = costs.get(&graph.nodes[*to]) {
+                        graph.edges[*from].push((*to, *cost));
+                    }
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// Adds a transition for every run of tiles that is open on both sides of a border.
+    fn add_entrances(&mut self, world: &GameWorld, border: &[(Position, Position)]) {
+        let open = |(inside, outside): &(Position, Position)| {
+            is_passable(world, inside.x, inside.y) && is_passable(world, outside.x, outside.y)
+        };
+
+        let mut start = 0;
+        while start < border.len() {
+            if !open(&border[start]) {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end + 1 < border.len() && open(&border[end + 1]) {
+                end += 1;
+            }
+
+            if (end - start + 1) as i32 >= WIDE_ENTRANCE {
+                self.add_transition(world, border[start]);
+                self.add_transition(world, border[end]);
+            } else {
+                self.add_transition(world, border[(start + end) / 2]);
+            }
+            start = end + 1;
+        }
+    }
+
+    fn add_transition(&mut self, world: &GameWorld, (inside, outside): (Position, Position)) {
+        let movement = world.get_movement();
+        let a = self.add_node(inside);
+        let b = self.add_node(outside);
+        if let Some(cost) = movement.step_cost(world, inside, outside) {
+            self.edges[a].push((b, cost));
+        }
+        if let Some(cost) = movement.step_cost(world, outside, inside) {
+            self.edges[b].push((a, cost));
+        }
+    }
+
+    fn add_node(&mut self, position: Position) -> usize {
+        if let Some(index) = self.node_index.get(&position) {
+            return *index;
+        }
+        let index = self.nodes.len();
+        let cluster = self.cluster_of(position);
+        self.nodes.push(position);
+        self.node_index.insert(position, index);
+        self.edges.push(Vec::new());
+        self.cluster_nodes[cluster].push(index);
+        index
+    }
+
+    fn cluster_of(&self, position: Position) -> usize {
+        let column = (position.x - self.origin.x) / self.cluster_size;
+        let row = (position.y - self.origin.y) / self.cluster_size;
+        (row * self.columns + column) as usize
+    }
+
+    pub fn get_cluster_size(&self) -> i32 {
+        self.cluster_size
+    }
+
+    pub fn cluster_count(&self) -> usize {
+        self.clusters.len()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Path from `start` to `goal` without the start, like `a_star_pathfinding`.
+    /// `None` when the graph finds no path.
+    pub fn find_path(
+        &self,
+        world: &GameWorld,
+        start: Position,
+        goal: Position,
+    ) -> Option<Vec<Position>> {
+        if !world.is_valid_position(start.x, start.y) || !world.is_valid_position(goal.x, goal.y) {
+            return None;
+        }
+        let movement = world.get_movement();
+        let start_cluster = self.cluster_of(start);
+        let goal_cluster = self.cluster_of(goal);
+
+        // Connect the start and the goal to the nodes of their clusters, as two extra nodes
+        let start_node = self.nodes.len();
+        let goal_node = start_node + 1;
+        let position_of = |node: usize| match node {
+            node if node == start_node => start,
+            node if node == goal_node => goal,
+            node => self.nodes[node],
+        };
+        let start_costs = cluster_costs(world, &self.clusters[start_cluster], start);
+        let mut start_edges: Vec<(usize, f32)> = self.cluster_nodes[start_cluster]
+            .iter()
+            .filter_map(|node| {
+                start_costs
+                    .get(&self.nodes[*node])
+                    .map(|cost| (*node, *cost))
+            })
+            .collect();
+        // The goal may be reachable without leaving the start cluster
+        if let Some(cost) = start_costs.get(&goal) {
+            start_edges.push((goal_node, *cost));
+        }
+        let goal_costs = cluster_costs(world, &self.clusters[goal_cluster], goal);
+        let goal_edges: HashMap<usize, f32> = self.cluster_nodes[goal_cluster]
+            .iter()
+            .filter_map(|node| {
+                goal_costs
+                    .get(&self.nodes[*node])
+                    .map(|cost| (*node, *cost))
+            })
+            .collect();
+
+        // A* on the graph
+        let mut g_score: HashMap<usize, f32> = HashMap::from([(start_node, 0.0)]);
+        let mut came_from: HashMap<usize, usize> = HashMap::new();
+        let mut open_set = BinaryHeap::from([GraphNode {
+            node: start_node,
+            cost: 0.0,
+            estimated_total_cost: movement.heuristic(start, goal),
+        }]);
+        let mut found = false;
+
+        while let Some(GraphNode { node, cost, .. }) = open_set.pop() {
+            if node == goal_node {
+                found = true;
+                break;
+            }
+            if cost > g_score[&node] {
+                continue;
+            }
+
+            let edges = if node == start_node {
+                &start_edges
+            } else {
+                &self.edges[node]
+            };
+            let exit = goal_edges.get(&node).map(|cost| (goal_node, *cost));
+            for (next, step) in edges.iter().copied().chain(exit) {
+                let tentative = cost + step;
+                if tentative < *g_score.get(&next).unwrap_or(&f32::INFINITY) {
+                    g_score.insert(next, tentative);
+                    came_from.insert(next, node);
+                    open_set.push(GraphNode {
+                        node: next,
+                        cost: tentative,
+                        estimated_total_cost: tentative
+                            + movement.heuristic(position_of(next), goal),
+                    });
+                }
+            }
+        }
+        if !found {
+            return None;
+        }
+
+        let mut waypoints = vec![goal];
+        let mut current = goal_node;
+        while let Some(previous) = came_from.get(&current) {
+            waypoints.push(position_of(*previous));
+            current = *previous;
+        }
+        waypoints.reverse();
+
+        // Refine every leg of the graph path into tiles
+        let mut path = Vec::new();
+        for leg in waypoints.windows(2) {
+            let (from, to) = (leg[0], leg[1]);
+            if from == to {
+                continue;
+            }
+            let from_cluster = self.cluster_of(from);
+            if from_cluster != self.cluster_of(to) {
+                // The step across an entrance
+                path.push(to);
+                continue;
+            }
+            let cluster = self.clusters[from_cluster];
+            let refined = a_star_search(world, from, to, &|position| {
+                if cluster.contains(position) {
+                    Some(0.0)
+                } else {
+                    None
+                }
+            });
+            if refined.is_empty() {
+                return None;
+            }
+            path.extend(refined);
+        }
+
+        Some(path)
+    }
+}
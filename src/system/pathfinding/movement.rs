@@ -0,0 +1,117 @@
+use clap::ValueEnum;
+
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+
+/// Which neighbouring tiles can be reached in one step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Connectivity {
+    /// Only up, down, left and right.
+    Four,
+    /// The four orthogonal tiles and the four diagonal ones.
+    Eight,
+}
+
+/// Whether a diagonal step may pass the corner of an impassable tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CornerCutting {
+    /// Diagonal steps ignore the two tiles beside them.
+    Allow,
+    /// At least one of the two tiles beside a diagonal step must be passable,
+    /// so nobody squeezes between two lakes.
+    NoSqueeze,
+    /// Both tiles beside a diagonal step must be passable.
+    Forbid,
+}
+
+const ORTHOGONAL: [(i32, i32); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+/// How units move over the tiles. Entering a tile costs its travel weight,
+/// a diagonal step costs √2 times the weight of the tile it enters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovementModel {
+    pub connectivity: Connectivity,
+    pub corner_cutting: CornerCutting,
+}
+
+impl Default for MovementModel {
+    fn default() -> Self {
+        MovementModel {
+            connectivity: Connectivity::Eight,
+            corner_cutting: CornerCutting::NoSqueeze,
+        }
+    }
+}
+
+fn is_passable(world: &GameWorld, x: i32, y: i32) -> bool {
+    world
+        .get_tile_type(x, y)
+        .map_or(false, |tile_type| tile_type.get_travel_weight() > 0.0)
+}
+
+impl MovementModel {
+    pub fn new(connectivity: Connectivity, corner_cutting: CornerCutting) -> Self {
+        MovementModel {
+            connectivity,
+            corner_cutting,
+        }
+    }
+
+    /// Passable tiles that can be reached from `position` in one step,
+    /// orthogonal ones first.
+    pub fn neighbors(&self, world: &GameWorld, position: Position) -> Vec<Position> {
+        let mut neighbors: Vec<Position> = ORTHOGONAL
+            .iter()
+            .map(|(dx, dy)| Position::new(position.x + dx, position.y + dy))
+            .filter(|neighbor| is_passable(world, neighbor.x, neighbor.y))
+            .collect();
+
+        if self.connectivity == Connectivity::Eight {
+            for (dx, dy) in DIAGONAL {
+                let neighbor = Position::new(position.x + dx, position.y + dy);
+                if !is_passable(world, neighbor.x, neighbor.y) {
+                    continue;
+                }
+                let beside_x = is_passable(world, position.x + dx, position.y);
+                let beside_y = is_passable(world, position.x, position.y + dy);
+                let allowed = match self.corner_cutting {
+                    CornerCutting::Allow => true,
+                    CornerCutting::NoSqueeze => beside_x || beside_y,
+                    CornerCutting::Forbid => beside_x && beside_y,
+                };
+                if allowed {
+                    neighbors.push(neighbor);
+                }
+            }
+        }
+
+        neighbors
+    }
+
+    /// Cost of stepping from `from` to the neighbouring tile `to`, `None` if `to` is impassable.
+    pub fn step_cost(&self, world: &GameWorld, from: Position, to: Position) -> Option<f32> {
+        let weight = world.get_tile_type(to.x, to.y)?.get_travel_weight();
+        if weight <= 0.0 {
+            return None;
+        }
+        if from.x != to.x && from.y != to.y {
+            Some(weight * std::f32::consts::SQRT_2)
+        } else {
+            Some(weight)
+        }
+    }
+
+    /// Lower bound on the cost from `a` to `b`: the octile distance (Manhattan with
+    /// four-connected movement) scaled by the cheapest travel weight.
+    pub fn heuristic(&self, a: Position, b: Position) -> f32 {
+        let dx = (a.x - b.x).abs() as f32;
+        let dy = (a.y - b.y).abs() as f32;
+        let distance = match self.connectivity {
+            Connectivity::Four => dx + dy,
+            Connectivity::Eight => dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy),
+        };
+        distance * TileType::min_travel_weight()
+    }
+}
@@ -0,0 +1,210 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+
+use super::hierarchical::{HierarchicalGraph, CLUSTER_SIZE};
+use super::pathfinding_calculation::get_neighbors;
+
+/// Travel cost from every tile to the closest tile of one type, computed with a multi-source Dijkstra.
+/// Costs are counted the same way as `a_star_pathfinding`, with the step costs of the world's movement model.
+pub struct DistanceField {
+    tile_type: TileType,
+    distances: HashMap<Position, f32>,
+    nearest: HashMap<Position, Position>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct FieldNode {
+    position: Position,
+    cost: f32,
+}
+
+impl Eq for FieldNode {}
+
+impl Ord for FieldNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reverse the order so the BinaryHeap pops the cheapest node first
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                (other.position.y, other.position.x).cmp(&(self.position.y, self.position.x))
+            })
+    }
+}
+
+impl PartialOrd for FieldNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl DistanceField {
+    pub fn compute(world: &GameWorld, tile_type: TileType) -> Self {
+        let mut distances = HashMap::new();
+        let mut nearest = HashMap::new();
+        let mut open_set = BinaryHeap::new();
+        let movement = world.get_movement();
+
+        let sources: Vec<Position> = world
+            .iter_tiles()
+            .filter(|(_, tile)| tile.get_tile_type() == tile_type)
+            .map(|(position, _)| position)
+            .collect();
+        for source in sources {
+            distances.insert(source, 0.0);
+            nearest.insert(source, source);
+            open_set.push(FieldNode {
+                position: source,
+                cost: 0.0,
+            });
+        }
+
+        // Searching outwards from the targets, stepping from `position` back to `neighbor`
+        // means walking neighbor -> position, which costs the step into `position`
+        while let Some(FieldNode { position, cost }) = open_set.pop() {
+            if cost > *distances.get(&position).unwrap_or(&f32::INFINITY) {
+                continue;
+            }
+            let target = nearest[&position];
+            for neighbor in get_neighbors(world, position) {
+                let step = match movement.step_cost(world, neighbor, position) {
+                    Some(step) => step,
+                    None => continue,
+                };
+                let tentative = cost + step;
+                if tentative < *distances.get(&neighbor).unwrap_or(&f32::INFINITY) {
+                    distances.insert(neighbor, tentative);
+                    nearest.insert(neighbor, target);
+                    open_set.push(FieldNode {
+                        position: neighbor,
+                        cost: tentative,
+                    });
+                }
+            }
+        }
+
+        DistanceField {
+            tile_type,
+            distances,
+            nearest,
+        }
+    }
+
+    pub fn get_tile_type(&self) -> TileType {
+        self.tile_type
+    }
+
+    /// Travel cost to the closest target, `None` if no target can be reached.
+    pub fn get_distance(&self, position: Position) -> Option<f32> {
+        self.distances.get(&position).copied()
+    }
+
+    /// The closest target by travel cost.
+    pub fn get_nearest(&self, position: Position) -> Option<Position> {
+        self.nearest.get(&position).copied()
+    }
+
+    /// Path to the closest target, without the start and ending on the target,
+    /// like the paths of `a_star_pathfinding`.
+    pub fn path_from(&self, world: &GameWorld, start: Position) -> Option<Vec<Position>> {
+        let mut current = start;
+        let mut remaining = self.get_distance(start)?;
+        let mut path = Vec::new();
+        let movement = world.get_movement();
+
+        while remaining > 0.0 {
+            // Follow the neighbour the distance was computed through
+            let next = get_neighbors(world, current)
+                .into_iter()
+                .filter_map(|neighbor| {
+                    let distance = self.get_distance(neighbor)?;
+                    Some((neighbor, distance + movement.step_cost(world, current, neighbor)?))
+                })
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))?;
+            path.push(next.0);
+            current = next.0;
+            remaining = self.get_distance(current)?;
+        }
+
+        Some(path)
+    }
+}
+
+/// Caches one distance field per tile type and the hierarchical graph of the world.
+/// Both are computed on first use and dropped when a tile of the world changes.
+#[derive(Default)]
+pub struct PathService {
+    fields: RwLock<HashMap<TileType, Arc<DistanceField>>>,
+    hierarchy: RwLock<Option<Arc<HierarchicalGraph>>>,
+}
+
+impl PathService {
+    pub fn new() -> Self {
+        PathService::default()
+    }
+
+    /// Returns the cached distance field to `tile_type`, computing it if needed.
+    pub fn distance_field(&self, world: &GameWorld, tile_type: TileType) -> Arc<DistanceField> {
+        if let Some(field) = self.fields.read().unwrap().get(&tile_type) {
+            return field.clone();
+        }
+
+        let field = Arc::new(DistanceField::compute(world, tile_type));
+        self.fields
+            .write()
+            .unwrap()
+            .insert(tile_type, field.clone());
+        field
+    }
+
+    /// Returns the cached hierarchical graph, building it if needed.
+    pub fn hierarchy(&self, world: &GameWorld) -> Arc<HierarchicalGraph> {
+        if let Some(graph) = self.hierarchy.read().unwrap().as_ref() {
+            return graph.clone();
+        }
+
+        let graph = Arc::new(HierarchicalGraph::build(world, CLUSTER_SIZE));
+        *self.hierarchy.write().unwrap() = Some(graph.clone());
+        graph
+    }
+
+    /// Path through the hierarchical graph, `None` for searches short enough for plain A*
+    /// or when the graph finds no path.
+    pub fn hierarchical_path(
+        &self,
+        world: &GameWorld,
+        start: Position,
+        goal: Position,
+    ) -> Option<Vec<Position>> {
+        // Small worlds never pay back the cost of building the graph
+        if world.get_width_max() - world.get_width_mind() < 2 * CLUSTER_SIZE
+            && world.get_height_max() - world.get_height_min() < 2 * CLUSTER_SIZE
+        {
+            return None;
+        }
+        if (start.x - goal.x).abs().max((start.y - goal.y).abs()) <= CLUSTER_SIZE {
+            return None;
+        }
+        self.hierarchy(world).find_path(world, start, goal)
+    }
+
+    /// Drops every cached field and the graph, called whenever a tile changes.
+    pub fn invalidate(&self) {
+        self.fields.write().unwrap().clear();
+        *self.hierarchy.write().unwrap() = None;
+    }
+
+    pub fn cached_fields(&self) -> usize {
+        self.fields.read().unwrap().len()
+    }
+
+    pub fn has_hierarchy(&self) -> bool {
+        self.hierarchy.read().unwrap().is_some()
+    }
+}
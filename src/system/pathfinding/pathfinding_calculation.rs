@@ -1,21 +1,77 @@
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::cmp::Ordering;
-use crate::gameworld::tile_types::TileType;
 use crate::gameworld::world::GameWorld;
 use crate::gameworld::position::Position;
+use super::danger::{DangerMap, DangerMode};
 
-/// A* pathfinding algorithm
+/// A* pathfinding algorithm. Long searches on large worlds go through the hierarchical graph
+/// of the path service, short ones or those the graph cannot answer use plain A*.
 pub fn a_star_pathfinding(
     world: &GameWorld,
     start: Position,
     goal: Position,
+) -> Vec<Position> {
+    if let Some(path) = world.get_path_service().hierarchical_path(world, start, goal) {
+        return path;
+    }
+    plain_a_star_pathfinding(world, start, goal)
+}
+
+/// A* over every tile, without the hierarchical graph.
+pub fn plain_a_star_pathfinding(
+    world: &GameWorld,
+    start: Position,
+    goal: Position,
+) -> Vec<Position> {
+    a_star_search(world, start, goal, &|_| Some(0.0))
+}
+
+/// A* pathfinding over the world plus the danger around monsters.
+/// In the hard mode a goal walled off by blocked tiles is reached by paying the danger instead.
+pub fn a_star_with_danger(
+    world: &GameWorld,
+    start: Position,
+    goal: Position,
+    danger: &DangerMap,
+) -> Vec<Position> {
+    let path = a_star_search(world, start, goal, &|position| danger.penalty(position, goal));
+    if path.is_empty() && danger.get_mode() == DangerMode::Hard {
+        let soft = danger.to_soft();
+        return a_star_search(world, start, goal, &|position| soft.penalty(position, goal));
+    }
+    path
+}
+
+/// A* pathfinding algorithm that never enters the `blocked` tiles, except for the goal
+pub fn a_star_avoiding(
+    world: &GameWorld,
+    start: Position,
+    goal: Position,
+    blocked: &HashSet<Position>,
+) -> Vec<Position> {
+    a_star_search(world, start, goal, &|position| {
+        if position != goal && blocked.contains(&position) {
+            None
+        } else {
+            Some(0.0)
+        }
+    })
+}
+
+/// A* search where `extra_cost` adds a non-negative cost to entering a tile, `None` blocks it
+pub(crate) fn a_star_search(
+    world: &GameWorld,
+    start: Position,
+    goal: Position,
+    extra_cost: &dyn Fn(Position) -> Option<f32>,
 ) -> Vec<Position> {
     // Priority queue for open nodes
     let mut open_set = BinaryHeap::new();
+    let movement = world.get_movement();
     open_set.push(Node {
         position: start,
         cost: 0.0,
-        estimated_total_cost: heuristic(start, goal),
+        estimated_total_cost: movement.heuristic(start, goal),
     });
 
     // Maps to store the cost of reaching a node and the path to it
@@ -30,34 +86,33 @@ pub fn a_star_pathfinding(
             return reconstruct_path(came_from, current.position);
         }
 
-        // Get neighbors of the current position
+        // Get neighbors of the current position, impassable tiles are already left out
         for neighbor in get_neighbors(world, current.position) {
-            // Extract x and y from the neighbor position
-            let (x, y) = (neighbor.x, neighbor.y);
-
-            // Get the tile type for the neighbor
-            if let Some(tile_type) = world.get_tile_type(x, y) {
-                let travel_weight = tile_type.get_travel_weight();
+            let step_cost = match movement.step_cost(world, current.position, neighbor) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
 
-                // Skip impassable tiles
-                if travel_weight == 0.0 {
-                    continue;
-                }
+            // The extra cost is never negative, so the heuristic stays admissible
+            let extra = match extra_cost(neighbor) {
+                Some(extra) => extra,
+                None => continue,
+            };
 
-                let tentative_g_score = g_score.get(&current.position).unwrap_or(&f32::INFINITY)
-                    + travel_weight;
+            let tentative_g_score = g_score.get(&current.position).unwrap_or(&f32::INFINITY)
+                + step_cost
+                + extra;
 
-                if tentative_g_score < *g_score.get(&neighbor).unwrap_or(&f32::INFINITY) {
-                    // Update the path and cost
-                    came_from.insert(neighbor, current.position);
-                    g_score.insert(neighbor, tentative_g_score);
+            if tentative_g_score < *g_score.get(&neighbor).unwrap_or(&f32::INFINITY) {
+                // Update the path and cost
+                came_from.insert(neighbor, current.position);
+                g_score.insert(neighbor, tentative_g_score);
 
-                    open_set.push(Node {
-                        position: neighbor,
-                        cost: tentative_g_score,
-                        estimated_total_cost: tentative_g_score + heuristic(neighbor, goal),
-                    });
-                }
+                open_set.push(Node {
+                    position: neighbor,
+                    cost: tentative_g_score,
+                    estimated_total_cost: tentative_g_score + movement.heuristic(neighbor, goal),
+                });
             }
         }
     }
@@ -95,10 +150,11 @@ pub fn a_star_with_current_path(
 
     // Priority queue for open nodes
     let mut open_set = BinaryHeap::new();
+    let movement = world.get_movement();
     open_set.push(Node {
         position: recalculate_start,
         cost: 0.0,
-        estimated_total_cost: heuristic(recalculate_start, goal),
+        estimated_total_cost: movement.heuristic(recalculate_start, goal),
     });
 
     // Maps to store the cost of reaching a node and the path to it
@@ -116,18 +172,15 @@ pub fn a_star_with_current_path(
             return reusable_path;
         }
 
-        // Get neighbors of the current position
+        // Get neighbors of the current position, impassable tiles are already left out
         for neighbor in get_neighbors(world, current.position) {
-            let travel_weight = world.get_tile_type(neighbor.x, neighbor.y)
-                .map_or(f32::INFINITY, |tile| tile.get_travel_weight());
-
-            // Skip impassable tiles
-            if travel_weight == 0.0 {
-                continue;
-            }
+            let step_cost = match movement.step_cost(world, current.position, neighbor) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
 
             let tentative_g_score = g_score.get(&current.position).unwrap_or(&f32::INFINITY)
-                + travel_weight;
+                + step_cost;
 
             if tentative_g_score < *g_score.get(&neighbor).unwrap_or(&f32::INFINITY) {
                 // Update the path and cost
@@ -137,7 +190,7 @@ pub fn a_star_with_current_path(
                 open_set.push(Node {
                     position: neighbor,
                     cost: tentative_g_score,
-                    estimated_total_cost: tentative_g_score + heuristic(neighbor, goal),
+                    estimated_total_cost: tentative_g_score + movement.heuristic(neighbor, goal),
                 });
             }
         }
@@ -177,6 +230,7 @@ pub fn recalculate_path_around_position(
     }
 
     // If the starting position is within the avoid area, find a valid position outside
+    let movement = world.get_movement();
     let mut adjusted_start = start;
     if avoid_positions.contains(&start) {
         let mut radius = 1;
@@ -204,14 +258,15 @@ pub fn recalculate_path_around_position(
                 }
             }
 
-            // If valid candidates are found, select the one closest to the start
+            // If valid candidates are found, select the one closest to the start under the movement model
             if !candidates.is_empty() {
                 adjusted_start = candidates
                     .into_iter()
-                    .min_by_key(|pos| {
-                        let dx = (pos.x - start.x).abs();
-                        let dy = (pos.y - start.y).abs();
-                        dx + dy
+                    .min_by(|a, b| {
+                        movement
+                            .heuristic(*a, start)
+                            .partial_cmp(&movement.heuristic(*b, start))
+                            .unwrap_or(Ordering::Equal)
                     })
                     .unwrap();
                 found_valid_position = true;
@@ -273,6 +328,7 @@ pub fn recalculate_path_around_multiple_positions(
     }
 
     // If the starting position is within the avoid area, find a valid position outside
+    let movement = world.get_movement();
     let mut adjusted_start = start;
     if avoid_set.contains(&start) {
         let mut radius = 1;
@@ -302,14 +358,15 @@ pub fn recalculate_path_around_multiple_positions(
                 }
             }
 
-            // If valid candidates are found, select the one closest to the start
+            // If valid candidates are found, select the one closest to the start under the movement model
             if !candidates.is_empty() {
                 adjusted_start = candidates
                     .into_iter()
-                    .min_by_key(|pos| {
-                        let dx = (pos.x - start.x).abs();
-                        let dy = (pos.y - start.y).abs();
-                        dx + dy
+                    .min_by(|a, b| {
+                        movement
+                            .heuristic(*a, start)
+                            .partial_cmp(&movement.heuristic(*b, start))
+                            .unwrap_or(Ordering::Equal)
                     })
                     .unwrap();
                 found_valid_position = true;
@@ -340,10 +397,6 @@ pub fn recalculate_path_around_multiple_positions(
     a_star_pathfinding(world, adjusted_start, goal)
 }
 
-/// Heuristic function for A* (Manhattan distance)
-fn heuristic(a: Position, b: Position) -> f32 {
-    ((a.x - b.x).abs() + (a.y - b.y).abs()) as f32
-}
 
 /// Reconstruct the path from the `came_from` map
 fn reconstruct_path(
@@ -359,27 +412,9 @@ fn reconstruct_path(
     path
 }
 
-/// Get valid neighbors of a position, including diagonals
-fn get_neighbors(world: &GameWorld, position: Position) -> Vec<Position> {
-    let directions = [
-        Position { x: 0, y: 1 },   // Up
-        Position { x: 1, y: 0 },   // Right
-        Position { x: 0, y: -1 },  // Down
-        Position { x: -1, y: 0 },  // Left
-        Position { x: 1, y: 1 },   // Up-Right (Diagonal)
-        Position { x: 1, y: -1 },  // Down-Right (Diagonal)
-        Position { x: -1, y: -1 }, // Down-Left (Diagonal)
-        Position { x: -1, y: 1 },  // Up-Left (Diagonal)
-    ];
-
-    directions
-        .iter()
-        .map(|dir| Position {
-            x: position.x + dir.x,
-            y: position.y + dir.y,
-        })
-        .filter(|neighbor| world.is_within_bounds(*neighbor))
-        .collect()
+/// Get the passable neighbors of a position allowed by the world's movement model
+pub(crate) fn get_neighbors(world: &GameWorld, position: Position) -> Vec<Position> {
+    world.get_movement().neighbors(world, position)
 }
 
 /// Node structure for the priority queue
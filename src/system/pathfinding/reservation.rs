@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+
+/// Steps of an agent's path reserved ahead of it, the first one is taken this turn.
+pub const RESERVED_STEPS: usize = 3;
+
+/// An NPC standing on a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Occupant {
+    Agent(i32),
+    Monster(i32),
+    Player,
+}
+
+/// What happens to the step an agent asked to take this turn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveOutcome {
+    Move,
+    /// The tile is taken, the agent stays where it is this turn.
+    Wait {
+        blocked_by: Occupant,
+    },
+    /// Two agents want each other's tile, they would have to pass through each other.
+    Deadlock {
+        with: i32,
+    },
+}
+
+/// Where every NPC stands at the start of the turn and the steps the travelling agents ask for.
+/// A tile holds one NPC at a time, except villages which hold any number.
+#[derive(Default)]
+pub struct ReservationTable {
+    occupied: HashMap<Position, Vec<Occupant>>,
+    requests: BTreeMap<i32, (Position, Vec<Position>)>,
+}
+
+fn is_shared(world: &GameWorld, position: Position) -> bool {
+    world.get_tile_type(position.x, position.y) == Some(TileType::Village)
+}
+
+impl ReservationTable {
+    pub fn new() -> Self {
+        ReservationTable::default()
+    }
+
+    pub fn occupy(&mut self, position: Position, occupant: Occupant) {
+        self.occupied.entry(position).or_default().push(occupant);
+    }
+
+    /// Reserves the next steps of an agent standing on `from`.
+    pub fn request(&mut self, agent_id: i32, from: Position, path: &[Position]) {
+        if path.is_empty() {
+            return;
+        }
+        let steps = path.iter().take(RESERVED_STEPS).copied().collect();
+        self.requests.insert(agent_id, (from, steps));
+    }
+
+    pub fn get_occupants(&self, position: Position) -> &[Occupant] {
+        self.occupied
+            .get(&position)
+            .map_or(&[], |occupants| occupants.as_slice())
+    }
+
+    /// Whether an agent asked to move this turn.
+    pub fn is_travelling(&self, agent_id: i32) -> bool {
+        self.requests.contains_key(&agent_id)
+    }
+
+    /// The tile of an agent and the steps it reserved.
+    pub fn planned_tiles(&self, agent_id: i32) -> Vec<Position> {
+        self.requests
+            .get(&agent_id)
+            .map_or(Vec::new(), |(from, steps)| {
+                let mut tiles = vec![*from];
+                tiles.extend(steps.iter().copied());
+                tiles
+            })
+    }
+
+    /// Whether a tile can be stepped on this turn: nobody stands there and nobody moves there.
+    pub fn is_free(&self, world: &GameWorld, position: Position) -> bool {
+        is_shared(world, position)
+            || (self.get_occupants(position).is_empty()
+                && !self
+                    .requests
+                    .values()
+                    .any(|(_, steps)| steps[0] == position))
+    }
+
+    /// Tiles another agent should plan around: everyone else's tile and the steps they reserved.
+    /// Villages are left out since they are never full.
+    pub fn reserved_by_others(&self, world: &GameWorld, agent_id: i32) -> HashSet<Position> {
+        let mut reserved: HashSet<Position> = self
+            .occupied
+            .iter()
+            .filter(|(_, occupants)| {
+                occupants
+                    .iter()
+                    .any(|occupant| *occupant != Occupant::Agent(agent_id))
+            })
+            .map(|(position, _)| *position)
+            .collect();
+        for (id, (_, steps)) in self.requests.iter() {
+            if *id != agent_id {
+                reserved.extend(steps.iter().copied());
+            }
+        }
+        reserved.retain(|position| !is_shared(world, *position));
+        reserved
+    }
+
+    /// Decides which requested steps can be taken. Agents with a lower id win a contested tile,
+    /// an agent may follow one that leaves its tile this turn, and two agents swapping tiles deadlock.
+    pub fn resolve(&self, world: &GameWorld) -> BTreeMap<i32, MoveOutcome> {
+        let mut outcomes: BTreeMap<i32, MoveOutcome> = self
+            .requests
+            .keys()
+            .map(|id| (*id, MoveOutcome::Move))
+            .collect();
+
+        // Swaps
+        for (id, (from, steps)) in self.requests.iter() {
+            let to = steps[0];
+            if is_shared(world, to) || is_shared(world, *from) {
+                continue;
+            }
+            let other = self
+                .requests
+                .iter()
+                .find(|(other_id, (other_from, other_steps))| {
+                    *other_id != id && *other_from == to && other_steps[0] == *from
+                });
+            if let Some((other_id, _)) = other {
+                outcomes.insert(*id, MoveOutcome::Deadlock { with: *other_id });
+            }
+        }
+
+        // Blocked moves can block the moves behind them, repeat until nothing changes
+        loop {
+            let mut changed = false;
+            let mut claimed: HashMap<Position, i32> = HashMap::new();
+
+            for (id, (_, steps)) in self.requests.iter() {
+                if outcomes[id] != MoveOutcome::Move {
+                    continue;
+                }
+                let to = steps[0];
+                if is_shared(world, to) {
+                    continue;
+                }
+
+                let blocked_by = if let Some(winner) = claimed.get(&to) {
+                    Some(Occupant::Agent(*winner))
+                } else {
+                    // Agents that leave the tile this turn do not block it
+                    self.get_occupants(to)
+                        .iter()
+                        .find(|occupant| match occupant {
+                            Occupant::Agent(other_id) => {
+                                other_id != id && outcomes.get(other_id) != Some(&MoveOutcome::Move)
+                            }
+                            _ => true,
+                        })
+                        .copied()
+                };
+
+                match blocked_by {
+                    Some(blocked_by) => {
+                        outcomes.insert(*id, MoveOutcome::Wait { blocked_by });
+                        changed = true;
+                    }
+                    None => {
+                        claimed.insert(to, *id);
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        outcomes
+    }
+}
@@ -41,8 +41,7 @@ pub fn highlight_moveable_player_squares(
         ];
 
         for position in adjacent_positions {
-            if let Some(tile_arc) = game_world.get_tile(position.x, position.y) {
-                let tile = tile_arc.lock().unwrap();
+            if let Some(tile) = game_world.get_tile(position.x, position.y) {
 
                 // Ensure the tile is traversable
                 let is_traversable = tile.get_tile_type().get_travel_weight() > 0.0;
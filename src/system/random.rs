@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// The subsystems that draw random numbers. Each one gets its own stream so that
+/// adding draws in one subsystem does not shift the numbers seen by another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RngStream {
+    Genes,
+    Spawning,
+    Selection,
+    Rollout,
+    WorldGen,
+    Benchmark,
+    Treasure,
+}
+
+impl RngStream {
+    fn label(&self) -> u64 {
+        match self {
+            RngStream::Genes => 1,
+            RngStream::Spawning => 2,
+            RngStream::Selection => 3,
+            RngStream::Rollout => 4,
+            RngStream::WorldGen => 5,
+            RngStream::Benchmark => 6,
+            RngStream::Treasure => 7,
+        }
+    }
+}
+
+/// All randomness of a run is derived from the master seed passed with `--seed`.
+/// Streams are created on first use and keep their state for the rest of the run.
+#[derive(Resource)]
+pub struct RngStreams {
+    seed: u64,
+    streams: HashMap<(RngStream, Option<i32>), StdRng>,
+}
+
+impl RngStreams {
+    pub fn new(seed: u64) -> Self {
+        RngStreams {
+            seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the shared stream of a subsystem.
+    pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+        self.get_or_create(stream, None)
+    }
+
+    /// Returns a subsystem stream owned by one agent, so the numbers an agent sees
+    /// do not depend on the order agents are processed in.
+    pub fn agent_stream(&mut self, stream: RngStream, agent_id: i32) -> &mut StdRng {
+        self.get_or_create(stream, Some(agent_id))
+    }
+
+    fn get_or_create(&mut self, stream: RngStream, agent_id: Option<i32>) -> &mut StdRng {
+        let seed = self.seed;
+        self.streams
+            .entry((stream, agent_id))
+            .or_insert_with(|| StdRng::seed_from_u64(derive_seed(seed, stream, agent_id)))
+    }
+}
+
+/// Mixes the master seed with the stream label and agent id (splitmix64 finaliser).
+pub fn derive_seed(seed: u64, stream: RngStream, agent_id: Option<i32>) -> u64 {
+    let mut z = seed
+        ^ stream.label().wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ agent_id.map_or(0, |id| (id as u32 as u64 + 1).wrapping_mul(0xD1B5_4A32_D192_ED03));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
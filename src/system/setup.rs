@@ -2,17 +2,19 @@ use crate::components::TileComponent;
 use crate::gameworld::highlight::Highlight;
 use crate::gameworld::position::Position;
 use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world_file::{SpawnKind, WorldDefinition};
+use crate::gameworld::world_validator::WorldConnectivity;
 use crate::npcs::agent::Agent;
-use crate::npcs::monster::Monster;
+use crate::npcs::monster::{Monster, MonsterStats};
+use crate::npcs::npc_components::inventory::ResourceType;
 use crate::npcs::npc_components::npc_base::NPCBase;
 use crate::npcs::npc_components::npc_type::NPCType;
 use crate::npcs::player::Player;
-use crate::npcs::treasure::Treasure;
+use crate::npcs::treasure::TreasureConfig;
 use crate::GameWorld;
-use crate::IterationCount;
-use crate::WorldRandom;
 use crate::WorldSim;
 use bevy::prelude::*;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 
 // use super::mcst;
@@ -21,11 +23,11 @@ use std::collections::HashMap;
 use bevy::ecs::bundle::Bundle;
 
 use super::mcst_tree::mcst_tree::MCTSTree;
+use super::mcst_tree::selection_policy::SelectionPolicyConfig;
 use super::mcst_tree::simulation_tree::SimulationTree;
-
-const START_AGENT_COUNT: usize = 10;
-const START_MONSTER_COUNT: usize = 5;
-const START_TREASURE_COUNT: usize = 5;
+use super::forward_model::economy::STARTING_FOOD;
+use super::random::{RngStream, RngStreams};
+use super::simulation::handle_treasure_respawn::TreasureSpawner;
 
 #[derive(Bundle)]
 pub struct TileBundle {
@@ -39,10 +41,11 @@ pub fn setup(
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     world: ResMut<GameWorld>,
-    mut iteration_total: ResMut<IterationCount>,
+    world_definition: Res<WorldDefinition>,
     mut world_sim: ResMut<WorldSim>,
-    mut world_random: ResMut<WorldRandom>,
+    mut rng_streams: ResMut<RngStreams>,
     mut simulation_tree: ResMut<SimulationTree>,
+    policy_config: Res<SelectionPolicyConfig>,
     npc_query: Query<(Entity, &NPCBase)>, // Add the NPC query here
 ) {
     // 1. Load textures dynamically based on tile names
@@ -64,8 +67,8 @@ pub fn setup(
     }
 
     // 2. Spawn tiles
-    for (position, tile) in world.tiles.iter() {
-        let tile_type = tile.lock().unwrap().get_tile_type();
+    for (position, tile) in world.iter_tiles() {
+        let tile_type = tile.get_tile_type();
         let texture_handle = tile_textures.get(&tile_type).unwrap().clone();
 
         commands.spawn(TileBundle {
@@ -91,19 +94,21 @@ pub fn setup(
     }
 
     // 3. Get SPAWN_LOCATION (villages)
+    // Tiles come row by row, so a seed always gives the same spawns
     let spawn_locations: Vec<Position> = world
-        .tiles
-        .iter()
-        .filter_map(|(position, tile)| {
-            if tile.lock().unwrap().get_tile_type() == TileType::Village {
-                Some(*position) // Ensure this uses the correct Position type
-            } else {
-                None
-            }
-        })
+        .iter_tiles()
+        .filter(|(_, tile)| tile.get_tile_type() == TileType::Village)
+        .map(|(position, _)| position)
         .collect();
 
-    if let Some(spawn_position) = spawn_locations.first() {
+    // Spawns fixed by the world file, resolved before any other random draw
+    let spawning_rng = rng_streams.stream(RngStream::Spawning);
+    let player_spawns = world_definition.spawn_positions(SpawnKind::Player, &world, spawning_rng);
+    let agent_spawns = world_definition.spawn_positions(SpawnKind::Agent, &world, spawning_rng);
+    let monster_spawns = world_definition.monster_spawns(&world, spawning_rng);
+    let treasure_spawns = world_definition.spawn_positions(SpawnKind::Treasure, &world, spawning_rng);
+
+    if let Some(spawn_position) = player_spawns.first().or(spawn_locations.first()) {
         add_player(
             *spawn_position,
             &mut commands,
@@ -113,21 +118,26 @@ pub fn setup(
     }
 
     populate_agents(
-        START_AGENT_COUNT,
+        world_definition.get_count(SpawnKind::Agent),
+        &agent_spawns,
         &spawn_locations,
         &mut commands,
         &asset_server,
         &mut texture_atlases,
         &mut simulation_tree,
+        &policy_config,
+        rng_streams.stream(RngStream::Genes),
     );
 
-    // 5. Spawn monsters and treasures
+    // 5. Spawn monsters and treasures, only where agents from a village can reach them
+    let connectivity = WorldConnectivity::compute(&world);
     let valid_monster_treasure_spawns: Vec<Position> = world
-        .tiles
-        .iter()
+        .iter_tiles()
         .filter_map(|(position, tile)| {
-            let tile_type = tile.lock().unwrap().get_tile_type();
-            if tile_type != TileType::Village
+            let tile_type = tile.get_tile_type();
+            if tile.is_monster_spawn()
+                && connectivity.is_connected_to_village(position)
+                && tile_type != TileType::Village
                 && tile_type != TileType::Mountain
                 && tile_type != TileType::Lake
             {
@@ -137,7 +147,7 @@ pub fn setup(
                     dx > 5 || dy > 5
                 });
                 if is_far_enough {
-                    return Some(*position);
+                    return Some(position);
                 }
             }
             None
@@ -146,26 +156,35 @@ pub fn setup(
 
     // Populate monsters
     populate_monsters(
-        START_MONSTER_COUNT,
+        world_definition.get_count(SpawnKind::Monster),
+        &monster_spawns,
+        world_definition.monster_stats(None),
         &valid_monster_treasure_spawns,
         &mut commands,
         &asset_server,
         &mut texture_atlases,
-        &mut world_random, // Pass WorldRandom here
+        rng_streams.stream(RngStream::Spawning),
     );
 
     // Populate treasures
     populate_treasures(
-        START_TREASURE_COUNT,
+        world_definition.get_count(SpawnKind::Treasure),
+        &treasure_spawns,
+        &world_definition.treasure,
         &valid_monster_treasure_spawns,
         &mut commands,
         &asset_server,
         &mut texture_atlases,
-        &mut world_random, // Pass WorldRandom here
+        rng_streams.stream(RngStream::Spawning),
     );
-
-    // 6. Set iteration_total and world_sim
-    iteration_total.0 = 50;
+    // Emptied treasures come back on the same tiles
+    commands.insert_resource(TreasureSpawner::new(
+        world_definition.treasure,
+        world_definition.get_count(SpawnKind::Treasure),
+        valid_monster_treasure_spawns,
+    ));
+
+    // 6. Set world_sim
     world_sim.0 = world.clone();
 }
 
@@ -201,16 +220,29 @@ pub fn add_player(
     println!("Spawned Player entity: {:?}", entity);
 }
 
+/// Spawns `count` agents, first on the fixed spawns of the world file, the rest spread over the villages.
 fn populate_agents(
     count: usize,
+    fixed_spawns: &[Position],
     spawn_locations: &[Position],
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
     simulation_tree: &mut ResMut<SimulationTree>,
+    policy_config: &SelectionPolicyConfig,
+    genes_rng: &mut StdRng,
 ) {
     for i in 0..count {
-        let spawn = spawn_locations[i % spawn_locations.len()];
+        let spawn = match fixed_spawns.get(i) {
+            Some(spawn) => *spawn,
+            None if !spawn_locations.is_empty() => {
+                spawn_locations[(i - fixed_spawns.len()) % spawn_locations.len()]
+            }
+            None => {
+                eprintln!("No village to spawn the remaining agents in");
+                break;
+            }
+        };
 
         // Add the agent and assign it an MCTS tree
         add_agent(
@@ -219,26 +251,34 @@ fn populate_agents(
             asset_server,
             texture_atlases,
             simulation_tree,
+            policy_config,
+            genes_rng,
         );
 
         println!("Spawned Agent at position: {:?}", spawn);
     }
 }
 
+/// Spawns `count` monsters, first on the fixed spawns of the world file with the stats of their type,
+/// the rest on random valid tiles with the default stats.
 fn populate_monsters(
     count: usize,
+    fixed_spawns: &[(Position, MonsterStats)],
+    default_stats: MonsterStats,
     valid_spawns: &[Position],
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-    world_random: &mut ResMut<WorldRandom>,
+    rng: &mut StdRng,
 ) {
-    let mut rng = &mut world_random.0;
-
-    for _ in 0..count {
-        // Randomly select a spawn position from valid_spawns
-        if let Some(spawn) = valid_spawns.choose(&mut rng) {
-            let npc_base = NPCBase::new(
+    for i in 0..count {
+        // Use the fixed spawns first, then randomly select a spawn position from valid_spawns
+        let spawn = match fixed_spawns.get(i) {
+            Some((spawn, stats)) => Some((spawn, stats.clone())),
+            None => valid_spawns.choose(rng).map(|spawn| (spawn, default_stats.clone())),
+        };
+        if let Some((spawn, stats)) = spawn {
+            let mut npc_base = NPCBase::new(
                 spawn.x,
                 spawn.y,
                 NPCType::Monster,
@@ -247,26 +287,36 @@ fn populate_monsters(
                 texture_atlases,
             );
 
-            let monster = Monster::new_monster(*spawn);
+            npc_base.set_max_energy(stats.max_energy);
+            npc_base.set_energy(stats.max_energy);
+
+            let mut monster = Monster::new_monster(*spawn);
+            monster.set_stats(stats);
 
             commands.spawn((monster, npc_base));
         }
     }
 }
 
+/// Spawns `count` treasures, first on the fixed spawns of the world file, the rest on random valid tiles.
+/// Their values are drawn from the treasure settings of the world file.
 fn populate_treasures(
     count: usize,
+    fixed_spawns: &[Position],
+    config: &TreasureConfig,
     valid_spawns: &[Position],
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-    world_random: &mut ResMut<WorldRandom>,
+    rng: &mut StdRng,
 ) {
-    let mut rng = &mut world_random.0;
-
-    for _ in 0..count {
-        // Randomly select a spawn position from valid_spawns
-        if let Some(spawn) = valid_spawns.choose(&mut rng) {
+    for i in 0..count {
+        // Use the fixed spawns first, then randomly select a spawn position from valid_spawns
+        let spawn = match fixed_spawns.get(i) {
+            Some(spawn) => Some(spawn),
+            None => valid_spawns.choose(rng),
+        };
+        if let Some(spawn) = spawn {
             let npc_base = NPCBase::new(
                 spawn.x,
                 spawn.y,
@@ -276,19 +326,24 @@ fn populate_treasures(
                 texture_atlases,
             );
 
-            let treasure = Treasure::new_treasure();
+            let treasure = config.roll_treasure(rng);
 
             commands.spawn((treasure, npc_base));
         }
     }
 }
 
-fn assign_tree(agent: &Agent, simulation_tree: &mut ResMut<SimulationTree>) {
+fn assign_tree(
+    agent: &Agent,
+    simulation_tree: &mut ResMut<SimulationTree>,
+    policy_config: &SelectionPolicyConfig,
+) {
     // Create a new MCTS tree for the agent
     let mut tree = MCTSTree::new();
 
     // Initialize the tree with the agent's data
     tree.initialize_tree();
+    tree.set_policy(policy_config.policy_for(agent.get_id()));
 
     // Add the tree to the SimulationTree resource
     simulation_tree.add_tree(agent.get_id(), tree);
@@ -300,6 +355,8 @@ fn add_agent(
     asset_server: &Res<AssetServer>,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
     simulation_tree: &mut ResMut<SimulationTree>,
+    policy_config: &SelectionPolicyConfig,
+    genes_rng: &mut StdRng,
 ) {
     // Create the NPCBase component
     let npc_base = NPCBase::new(
@@ -318,8 +375,11 @@ fn add_agent(
         NPCType::Agent
     );
 
-    // Create the Agent component
-    let agent = Agent::new_agent();
+    // Create the Agent component, with some food to start with
+    let mut agent = Agent::new_agent(genes_rng);
+    agent
+        .get_inventory_mut()
+        .add(ResourceType::Food, STARTING_FOOD);
     println!("Created Agent with ID: {}", agent.get_id());
 
     // Spawn the entity with NPCBase and Agent components
@@ -327,8 +387,12 @@ fn add_agent(
     println!("Spawned Agent entity: {:?}", entity);
 
     // Assign the agent an MCTS tree
-    assign_tree(&agent, simulation_tree);
-    println!("Assigned MCTS tree to Agent with ID: {}", agent.get_id());
+    assign_tree(&agent, simulation_tree, policy_config);
+    println!(
+        "Assigned MCTS tree to Agent with ID: {} using {:?} selection",
+        agent.get_id(),
+        policy_config.kind_for(agent.get_id())
+    );
 }
 
 pub fn check_npc_count(npc_query: Query<(Entity, &NPCBase)>) {
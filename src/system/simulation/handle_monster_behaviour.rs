@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::agent::Agent;
+use crate::npcs::monster::Monster;
+use crate::npcs::npc_components::npc_base::NPCBase;
+use crate::npcs::player::Player;
+use crate::system::forward_model::events::GameEvent;
+use crate::system::forward_model::game_state::{GameState, NpcSnapshot};
+
+/// Runs the monsters' turn through the forward model: they patrol, hunt agents near them
+/// and hit the ones next to them. Agents killed by a monster are greyed out.
+pub fn handle_monster_system(
+    mut commands: Commands,
+    mut agents: Query<(&mut Agent, &mut NPCBase)>,
+    mut monsters: Query<(&mut Monster, &mut NPCBase), Without<Agent>>,
+    player: Query<&NPCBase, (With<Player>, Without<Agent>, Without<Monster>)>,
+    mut sprites: Query<&mut TextureAtlasSprite>,
+    world: Res<GameWorld>,
+) {
+    // Snapshot the NPCs the monsters can see
+    let mut state = GameState::new(Arc::new(world.clone()));
+    for (agent, npc_base) in agents.iter() {
+        state.add_agent(agent.clone(), NpcSnapshot::from_base(npc_base));
+    }
+    for (monster, npc_base) in monsters.iter() {
+        state.add_monster(monster.clone(), NpcSnapshot::from_base(npc_base));
+    }
+    state.player = player.get_single().ok().map(|npc_base| npc_base.get_position());
+
+    let events = state.update_monsters();
+
+    for (mut monster, mut npc_base) in monsters.iter_mut() {
+        if let Some(monster_state) = state
+            .monsters
+            .iter()
+            .find(|other| other.monster.get_id() == monster.get_id())
+        {
+            *monster = monster_state.monster.clone();
+            let to = monster_state.npc.position;
+            if to != npc_base.get_position() {
+                npc_base.move_to(to.x, to.y, &mut commands);
+            }
+        }
+    }
+
+    for (mut agent, mut npc_base) in agents.iter_mut() {
+        if let Some(agent_state) = state.get_agent(agent.get_id()) {
+            *agent = agent_state.agent.clone();
+            npc_base.set_energy(agent_state.npc.energy);
+        }
+    }
+
+    for event in events.iter() {
+        if let GameEvent::KilledByMonster { monster_id, agent_id } = event {
+            println!("Monster {} killed agent {}", monster_id, agent_id);
+            if let Some((_, npc_base)) = agents.iter().find(|(agent, _)| agent.get_id() == *agent_id) {
+                if let Ok(mut sprite) = sprites.get_mut(npc_base.get_entity()) {
+                    sprite.color = Color::GRAY;
+                }
+            }
+        }
+    }
+}
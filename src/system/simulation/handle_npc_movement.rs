@@ -1,35 +1,52 @@
+use std::sync::Arc;
+
 use bevy::prelude::*;
-use crate::gameworld::position::Position;
+use crate::gameworld::world::GameWorld;
 use crate::npcs::agent::Agent;
+use crate::npcs::monster::Monster;
 use crate::npcs::npc_components::npc_base::NPCBase;
-use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::player::Player;
+use crate::system::forward_model::events::GameEvent;
+use crate::system::forward_model::game_state::{GameState, NpcSnapshot};
 
+/// Moves the agents along their paths through the forward model, so no two NPCs end up on the same tile.
 pub fn handle_agent_movement(
-    mut query: Query<(&mut Agent, &mut NPCBase)>, // Query for agents and their NPCBase
+    mut agents: Query<(&mut Agent, &mut NPCBase)>, // Query for agents and their NPCBase
+    monsters: Query<(&Monster, &NPCBase), Without<Agent>>,
+    player: Query<&NPCBase, (With<Player>, Without<Agent>)>,
+    world: Res<GameWorld>,
     mut commands: Commands,
 ) {
-    for (mut agent, mut npc_base) in query.iter_mut() {
-        // Check if the agent's status is Moving
-        if agent.get_status() == Status::Moving {
-            // Get the agent's path
-            if let Some(mut path) = agent.get_path() {
-                // Pop the first position in the path
-                if let Some(next_position) = path.first() {
-                    // Move the NPCBase to the next position
-                    npc_base.move_to(next_position.x, next_position.y, &mut commands);
+    // Snapshot everything that takes up a tile
+    let mut state = GameState::new(Arc::new(world.clone()));
+    for (agent, npc_base) in agents.iter() {
+        state.add_agent(agent.clone(), NpcSnapshot::from_base(npc_base));
+    }
+    for (monster, npc_base) in monsters.iter() {
+        state.add_monster(monster.clone(), NpcSnapshot::from_base(npc_base));
+    }
+    state.player = player.get_single().ok().map(|npc_base| npc_base.get_position());
 
-                    // Remove the position from the path
-                    path.remove(0);
+    let events = state.move_agents();
 
-                    // Update the agent's path
-                    agent.set_path(path);
+    for (mut agent, mut npc_base) in agents.iter_mut() {
+        if let Some(agent_state) = state.get_agent(agent.get_id()) {
+            *agent = agent_state.agent.clone();
+            // Move the NPCBase to the next position
+            let to = agent_state.npc.position;
+            if to != npc_base.get_position() {
+                npc_base.move_to(to.x, to.y, &mut commands);
+            }
+        }
+    }
 
-                    // If the path is empty, set the agent's status to Idle
-                    if agent.get_path().is_none() || agent.get_path().unwrap().is_empty() {
-                        agent.set_status(Status::Idle);
-                    }
-                }
+    for event in events.iter() {
+        // Both agents report the deadlock, print it once
+        if let GameEvent::DeadlockDetected { agent_id, other_id } = event {
+            if agent_id > other_id {
+                continue;
             }
+            println!("Agents {} and {} are blocking each other", agent_id, other_id);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::gameworld::position::Position;
+use crate::npcs::npc_components::npc_base::NPCBase;
+use crate::npcs::npc_components::npc_type::NPCType;
+use crate::npcs::treasure::{Treasure, TreasureConfig};
+use crate::system::random::{RngStream, RngStreams};
+
+/// Keeps the number of treasures at the count of the world file by replacing emptied ones.
+#[derive(Resource, Clone, Debug)]
+pub struct TreasureSpawner {
+    config: TreasureConfig,
+    count: usize,
+    valid_spawns: Vec<Position>,
+    /// Turns every missing treasure has been gone for.
+    missing: Vec<u32>,
+}
+
+impl TreasureSpawner {
+    pub fn new(config: TreasureConfig, count: usize, valid_spawns: Vec<Position>) -> Self {
+        TreasureSpawner {
+            config,
+            count,
+            valid_spawns,
+            missing: Vec::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> &TreasureConfig {
+        &self.config
+    }
+
+    /// Advances the timers of the missing treasures by one turn and returns how many reappear.
+    /// `present` is the number of treasures still on the map.
+    pub fn tick<R: Rng>(&mut self, present: usize, rng: &mut R) -> usize {
+        self.missing.resize(self.count.saturating_sub(present), 0);
+
+        let config = self.config;
+        let before = self.missing.len();
+        self.missing.retain_mut(|turns| {
+            *turns += 1;
+            *turns <= config.respawn_delay || rng.gen::<f32>() >= config.respawn_chance
+        });
+        before - self.missing.len()
+    }
+
+    /// A random valid spawn that is not in `occupied`.
+    pub fn pick_spawn<R: Rng>(&self, occupied: &[Position], rng: &mut R) -> Option<Position> {
+        let free: Vec<&Position> = self
+            .valid_spawns
+            .iter()
+            .filter(|position| !occupied.contains(position))
+            .collect();
+        free.choose(rng).map(|position| **position)
+    }
+}
+
+/// OnEnter(EndTurn): gives emptied treasures a chance to reappear on a free valid spawn tile.
+pub fn treasure_respawn_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut spawner: ResMut<TreasureSpawner>,
+    mut rng_streams: ResMut<RngStreams>,
+    treasures: Query<&Treasure>,
+    npcs: Query<&NPCBase>,
+) {
+    let rng = rng_streams.stream(RngStream::Treasure);
+    let respawned = spawner.tick(treasures.iter().count(), rng);
+
+    let mut occupied: Vec<Position> = npcs
+        .iter()
+        .map(|npc_base| npc_base.get_position())
+        .collect();
+    for _ in 0..respawned {
+        let spawn = match spawner.pick_spawn(&occupied, rng) {
+            Some(spawn) => spawn,
+            None => {
+                eprintln!("No free tile to respawn a treasure on");
+                break;
+            }
+        };
+        occupied.push(spawn);
+
+        let npc_base = NPCBase::new(
+            spawn.x,
+            spawn.y,
+            NPCType::Treasure,
+            &mut commands,
+            &asset_server,
+            &mut texture_atlases,
+        );
+        let treasure = spawner.get_config().roll_treasure(rng);
+        println!(
+            "Treasure {} worth {} appeared at {:?}",
+            treasure.get_id(),
+            treasure.get_reward(),
+            spawn
+        );
+        commands.spawn((treasure, npc_base));
+    }
+}
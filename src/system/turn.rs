@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+
+use crate::npcs::agent::Agent;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::system::mcst_system::budget::MctsUsage;
+use crate::system::mcst_tree::mcst_node::NodeType;
+use crate::system::mcst_tree::simulation_tree::SimulationTree;
+use crate::{HighlightMovement, ScoreTracker, TurnState};
+
+/// OnEnter(PlayerTurn): lets the player pick a new square to move to.
+pub fn start_player_turn(mut highlight_movement: ResMut<HighlightMovement>) {
+    println!("Player turn");
+    highlight_movement.0 = true;
+}
+
+/// OnEnter(MCTSPhase): every tree starts a fresh search from its root.
+pub fn start_mcts_phase(
+    mut simulation_tree: ResMut<SimulationTree>,
+    mut usage: ResMut<MctsUsage>,
+) {
+    println!("Starting MCTS phase");
+    for tree in simulation_tree.trees_mut() {
+        tree.reset_search();
+    }
+    usage.reset();
+}
+
+/// OnEnter(ExecutionPhase): idle agents commit to the most visited action of their tree,
+/// and the tree is re-rooted at that action so the statistics below it are kept.
+pub fn start_execution_phase(
+    mut simulation_tree: ResMut<SimulationTree>,
+    mut agents: Query<&mut Agent>,
+) {
+    for mut agent in agents.iter_mut() {
+        if agent.get_status() != Status::Idle {
+            continue;
+        }
+
+        let agent_id = agent.get_id();
+        if let Some(tree) = simulation_tree.get_tree_mut(agent_id) {
+            if let Some(best_child) = tree.get_best_child(tree.get_root()) {
+                if let NodeType::ActionNode { action } = tree.get_node(best_child).node_type {
+                    println!("Agent {} commits to {}", agent_id, action.to_string());
+                    agent.set_action(action);
+                }
+                tree.set_root(best_child);
+            }
+        }
+    }
+}
+
+/// Runs after the execution systems: one game tick is played per turn.
+pub fn finish_execution_phase(mut next_state: ResMut<NextState<TurnState>>) {
+    next_state.set(TurnState::EndTurn);
+}
+
+/// OnEnter(EndTurn): records every agent's score for this turn.
+pub fn score_turn(agents: Query<&Agent>, mut score_tracker: ResMut<ScoreTracker>) {
+    for agent in agents.iter() {
+        let agent_id = agent.get_id() as u32;
+        let score = agent.get_reward() as i32;
+        match score_tracker.0.iter_mut().find(|(id, _)| *id == agent_id) {
+            Some(entry) => entry.1 = score,
+            None => score_tracker.0.push((agent_id, score)),
+        }
+    }
+    score_tracker.0.sort_by_key(|(id, _)| *id);
+    println!("Scores: {:?}", score_tracker.0);
+}
+
+/// Hands control back to the player once the turn has been scored.
+pub fn end_turn_system(mut next_state: ResMut<NextState<TurnState>>) {
+    next_state.set(TurnState::PlayerTurn);
+}
@@ -1,114 +1,853 @@
-use std::sync::{Arc, Mutex};
-use crate::mcst_system::mcst_tree::mcst_node::{Node, NodeType};
-use crate::mcst_system::mcst::NpcAction;
+use std::sync::Arc;
+use std::time::Duration;
 
-// Helper function to create a sample tree for testing
-fn create_sample_tree() -> Arc<Mutex<Node>> {
-    let root = Node {
-        node_type: NodeType::ActionNode {
-            action: NpcAction::None,
-            visits: 0,
-            total_reward: 0,
-            children: Vec::new(),
-        },
-        depth: 0,
-        parent: None,
-    };
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
 
-    let root_arc = Arc::new(Mutex::new(root));
+use crate::gameworld::position::Position;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::agent::Agent;
+use crate::npcs::monster::{Monster, MonsterStats};
+use crate::npcs::npc_components::gene_type::GeneType;
+use crate::npcs::npc_components::inventory::ResourceType;
+use crate::npcs::npc_components::memory::Memory;
+use crate::npcs::npc_components::npc_action::{NpcAction, WorkType};
+use crate::npcs::npc_components::npc_status::Status;
+use crate::npcs::npc_components::npc_type::NPCType;
+use crate::npcs::npc_components::target::Target;
+use crate::npcs::treasure::{Treasure, TreasureConfig};
+use crate::system::forward_model::conversation::TALK_TURNS;
+use crate::system::forward_model::economy::{production, FOOD_RESERVE, WORK_TURNS};
+use crate::system::forward_model::events::GameEvent;
+use crate::system::forward_model::game_state::{GameState, NpcSnapshot};
+use crate::system::forward_model::theft::{theft_outcome, TheftOutcome};
+use crate::system::mcst_system::budget::parse_agent_budget;
+use crate::system::mcst_system::rollout_policy::{RolloutConfig, RolloutPolicy, UniformRollout};
+use crate::system::mcst_system::simulation::rollout;
+use crate::system::mcst_tree::mcst_node::{NodeId, NodeType};
+use crate::system::mcst_tree::mcst_tree::MCTSTree;
+use crate::system::mcst_tree::selection_policy::{parse_agent_policy, SelectionPolicyKind};
+use crate::system::random::{RngStream, RngStreams};
+use crate::system::simulation::handle_treasure_respawn::TreasureSpawner;
+
+// Helper function to create a sample tree for testing
+fn create_sample_tree() -> (MCTSTree, NodeId, NodeId) {
+    let mut tree = MCTSTree::new();
+    let root = tree.get_root();
 
     // Add child nodes
-    {
-        let mut root_lock = root_arc.lock().unwrap();
-        root_lock.expand(NpcAction::Move);
-        root_lock.expand(NpcAction::Attack);
-    }
+    let attack = tree.expand(root, NpcAction::AttackAgent);
+    let steal = tree.expand(root, NpcAction::Steal);
 
     // Add grandchildren to the first child
-    {
-        let root_lock = root_arc.lock().unwrap();
-        let first_child = root_lock
-            .select_best_action()
-            .expect("First child should exist");
-        let mut first_child_lock = first_child.lock().unwrap();
-        first_child_lock.expand(NpcAction::Defend);
-        first_child_lock.expand(NpcAction::Gather);
-    }
+    tree.expand(attack, NpcAction::Rest);
+    tree.expand(attack, NpcAction::Talk);
 
-    root_arc
+    (tree, attack, steal)
 }
 
 #[test]
 fn test_tree_creation() {
-    let root = create_sample_tree();
+    let (tree, attack, steal) = create_sample_tree();
+    let root = tree.get_root();
 
     // Check root node
-    let root_lock = root.lock().unwrap();
-    assert!(matches!(root_lock.node_type, NodeType::ActionNode { .. }));
-    assert_eq!(root_lock.depth, 0);
-
-    // Check children of root
-    if let NodeType::ActionNode { children, .. } = &root_lock.node_type {
-        assert_eq!(children.len(), 2);
-    } else {
-        panic!("Root node should be an ActionNode");
+    assert!(matches!(tree.get_node(root).node_type, NodeType::NullNode));
+    assert_eq!(tree.get_node(root).get_children(), &vec![attack, steal]);
+    assert_eq!(tree.node_count(), 5);
+
+    // Children point back at their real parent
+    assert_eq!(tree.get_node(attack).get_parent(), Some(root));
+    assert_eq!(tree.get_node(attack).depth, 1);
+    assert_eq!(tree.get_height(), 3);
+}
+
+#[test]
+fn test_child_lookup_by_action() {
+    let (mut tree, attack, _) = create_sample_tree();
+    let root = tree.get_root();
+
+    assert_eq!(tree.get_node(root).get_action_child(NpcAction::AttackAgent), Some(attack));
+    assert_eq!(tree.get_node(root).get_action_child(NpcAction::Talk), None);
+
+    // Expanding an existing action returns the same node instead of a duplicate
+    assert_eq!(tree.expand(root, NpcAction::AttackAgent), attack);
+    assert!(tree
+        .add_child(root, NodeType::ActionNode { action: NpcAction::Steal })
+        .is_err());
+}
+
+#[test]
+fn test_backpropagation_reaches_root() {
+    let (mut tree, attack, _) = create_sample_tree();
+    let root = tree.get_root();
+    let rest = tree.get_node(attack).get_action_child(NpcAction::Rest).unwrap();
+
+    tree.backpropagate(rest, 10.0);
+
+    assert_eq!(tree.get_node(rest).visits, 1);
+    assert_eq!(tree.get_node(attack).visits, 1);
+    assert_eq!(tree.get_node(root).visits, 1);
+    assert_eq!(tree.get_node(root).total_reward, 10.0);
+}
+
+#[test]
+fn test_signed_reward_statistics() {
+    let (mut tree, attack, _) = create_sample_tree();
+
+    tree.backpropagate(attack, 6.0);
+    tree.backpropagate(attack, -2.0);
+
+    let node = tree.get_node(attack);
+    assert_eq!(node.mean_reward(), 2.0);
+    assert_eq!(node.reward_variance(), 16.0);
+    assert_eq!(node.min_reward, -2.0);
+    assert_eq!(node.max_reward, 6.0);
+}
+
+#[test]
+fn test_set_root_keeps_subtree() {
+    let (mut tree, attack, _) = create_sample_tree();
+    tree.backpropagate(attack, 4.0);
+
+    tree.set_root(attack);
+    let root = tree.get_root();
+
+    assert_eq!(tree.node_count(), 3);
+    assert!(!tree.get_node(root).has_parent());
+    assert_eq!(tree.get_node(root).visits, 1);
+    assert_eq!(tree.get_current_node(), Some(root));
+
+    let talk = tree.get_node(root).get_action_child(NpcAction::Talk).unwrap();
+    assert_eq!(tree.get_node(talk).get_parent(), Some(root));
+}
+
+#[test]
+#[should_panic]
+fn test_information_node_cannot_be_root() {
+    let mut tree = MCTSTree::new();
+    let root = tree.get_root();
+    let information = tree
+        .add_child(
+            root,
+            NodeType::InformationNode {
+                action_taken: NpcAction::Steal,
+                npc_type: NPCType::Agent,
+                npc_id: 1,
+            },
+        )
+        .unwrap();
+
+    tree.set_root(information);
+}
+
+#[test]
+fn test_uct_prefers_unvisited_children() {
+    let (mut tree, attack, steal) = create_sample_tree();
+    tree.backpropagate(attack, 100.0);
+
+    let mut rng = StdRng::seed_from_u64(0);
+    assert_eq!(tree.select_action(tree.get_root(), &mut rng), Some(steal));
+}
+
+#[test]
+fn test_puct_follows_priors() {
+    let (mut tree, attack, steal) = create_sample_tree();
+    tree.set_policy(SelectionPolicyKind::Puct.build(1.414, 0.1));
+    tree.get_node_mut(attack).set_prior(0.2);
+    tree.get_node_mut(steal).set_prior(0.8);
+    tree.backpropagate(tree.get_root(), 0.0);
+
+    let mut rng = StdRng::seed_from_u64(0);
+    assert_eq!(tree.select_action(tree.get_root(), &mut rng), Some(steal));
+}
+
+#[test]
+fn test_parse_agent_policy() {
+    assert_eq!(parse_agent_policy("3=ucb1-tuned"), Ok((3, SelectionPolicyKind::Ucb1Tuned)));
+    assert!(parse_agent_policy("puct").is_err());
+}
+
+#[test]
+fn test_seed_prior_adds_virtual_visits() {
+    let (mut tree, attack, _) = create_sample_tree();
+    tree.seed_prior(attack, 0.4, 2, 0.75);
+
+    let node = tree.get_node(attack);
+    assert_eq!(node.prior, 0.4);
+    assert_eq!(node.visits, 2);
+    assert_eq!(node.mean_reward(), 0.75);
+    // The parent sees the virtual visits of its children
+    assert_eq!(tree.get_node(tree.get_root()).visits, 2);
+
+    // Only real rollouts shape the variance and the reward range
+    assert_eq!(node.reward_variance(), 0.0);
+    assert_eq!(node.min_reward, f64::INFINITY);
+    tree.backpropagate(attack, 1.0);
+    tree.backpropagate(attack, 3.0);
+    let node = tree.get_node(attack);
+    assert_eq!(node.real_visits(), 2);
+    assert_eq!(node.mean_reward(), 1.375);
+    assert_eq!(node.reward_variance(), 1.0);
+    assert_eq!((node.min_reward, node.max_reward), (1.0, 3.0));
+}
+
+#[test]
+fn test_rollout_collects_treasure() {
+    let world = GameWorld::create_world(vec!["vffff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let agent = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let agent_id = agent.get_id();
+    state.add_agent(agent, NpcSnapshot::new(Position::new(0, 0), 100, 100));
+    state.add_treasure(Treasure::new_treasure(), NpcSnapshot::new(Position::new(4, 1), 0, 0));
+
+    let policies: Vec<(i32, Box<dyn RolloutPolicy>)> = vec![(agent_id, Box::new(UniformRollout))];
+    let mut rng = StdRng::seed_from_u64(7);
+    let reward = rollout(&state, agent_id, &[NpcAction::TreasureHunt], &policies, 10, &mut rng);
+    assert_eq!(reward, 100.0);
+
+    // The rollout works on a clone, the original state keeps its treasure
+    assert_eq!(state.treasures.len(), 1);
+
+    // Stored in the tree, a whole treasure is worth as much as the best possible prior
+    assert_eq!(RolloutConfig::default().normalise_reward(reward), 1.0);
+}
+
+/// Rollout policy that always picks the same action, so rollouts only depend on the plan.
+struct FixedRollout(NpcAction);
+
+impl RolloutPolicy for FixedRollout {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn choose_action(&self, _rng: &mut dyn RngCore) -> NpcAction {
+        self.0
     }
 }
 
 #[test]
-fn test_monte_carlo_calculation() {
-    let root = create_sample_tree();
-
-    // Simulate some visits and rewards
-    {
-        let root_lock = root.lock().unwrap();
-        if let NodeType::ActionNode { children, .. } = &root_lock.node_type {
-            let first_child = &children[0];
-            let mut first_child_lock = first_child.lock().unwrap();
-            if let NodeType::ActionNode {
-                visits,
-                total_reward,
-                ..
-            } = &mut first_child_lock.node_type
-            {
-                *visits = 10;
-                *total_reward = 50;
-            }
-        }
+fn test_rollout_replays_path_from_root() {
+    let world = GameWorld::create_world(vec!["vffff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let agent = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let agent_id = agent.get_id();
+    state.add_agent(agent, NpcSnapshot::new(Position::new(0, 0), 100, 100));
+    state.add_treasure(Treasure::new_treasure(), NpcSnapshot::new(Position::new(4, 1), 0, 0));
+
+    // Two depth-2 nodes with the same action, reached through different parents
+    let mut tree = MCTSTree::new();
+    let root = tree.get_root();
+    let hunt = tree.expand(root, NpcAction::TreasureHunt);
+    let hunt_then_rest = tree.expand(hunt, NpcAction::Rest);
+    let rest = tree.expand(root, NpcAction::Rest);
+    let rest_then_rest = tree.expand(rest, NpcAction::Rest);
+    assert_eq!(
+        tree.get_action_path(hunt_then_rest),
+        vec![NpcAction::TreasureHunt, NpcAction::Rest]
+    );
+
+    let policies: Vec<(i32, Box<dyn RolloutPolicy>)> =
+        vec![(agent_id, Box::new(FixedRollout(NpcAction::Rest)))];
+    let score = |node| {
+        let plan = tree.get_action_path(node);
+        rollout(&state, agent_id, &plan, &policies, 15, &mut StdRng::seed_from_u64(7))
+    };
+    let hunted = score(hunt_then_rest);
+    let rested = score(rest_then_rest);
+    assert!(hunted >= 50.0);
+    assert!(rested <= 0.0);
+
+    // Once the hunt is committed and becomes the root, it is not played a second time
+    tree.set_root(hunt);
+    let rest = tree.get_node(tree.get_root()).get_action_child(NpcAction::Rest).unwrap();
+    assert_eq!(tree.get_action_path(tree.get_root()), Vec::new());
+    assert_eq!(tree.get_action_path(rest), vec![NpcAction::Rest]);
+    let plan = tree.get_action_path(rest);
+    let replayed = rollout(&state, agent_id, &plan, &policies, 15, &mut StdRng::seed_from_u64(7));
+    assert_eq!(replayed, rested);
+}
+
+#[test]
+fn test_best_child_is_most_visited() {
+    let (mut tree, attack, steal) = create_sample_tree();
+    tree.backpropagate(attack, 1.0);
+    tree.backpropagate(steal, 5.0);
+    tree.backpropagate(steal, 5.0);
+
+    assert_eq!(tree.get_best_child(tree.get_root()), Some(steal));
+}
+
+#[test]
+fn test_parse_agent_budget() {
+    let (id, limits) = parse_agent_budget("2=iterations:100,time:20").unwrap();
+    assert_eq!(id, 2);
+    assert_eq!(limits.iterations, Some(100));
+    assert_eq!(limits.time, Some(Duration::from_millis(20)));
+    assert_eq!(limits.max_nodes, None);
+    assert!(parse_agent_budget("2=depth:3").is_err());
+}
+
+#[test]
+fn test_same_seed_gives_same_streams() {
+    let mut first = RngStreams::new(42);
+    let mut second = RngStreams::new(42);
+
+    let first_agent = Agent::new_agent(first.stream(RngStream::Genes));
+    let second_agent = Agent::new_agent(second.stream(RngStream::Genes));
+    for gene in [
+        GeneType::Greed,
+        GeneType::Aggression,
+        GeneType::Social,
+        GeneType::SelfPreservation,
+        GeneType::Vision,
+    ] {
+        assert_eq!(
+            first_agent.get_genes().return_type_score(gene.clone()),
+            second_agent.get_genes().return_type_score(gene)
+        );
     }
 
-    // Check Monte Carlo calculation
-    let root_lock = root.lock().unwrap();
-    if let NodeType::ActionNode { children, .. } = &root_lock.node_type {
-        let first_child = &children[0];
-        let first_child_lock = first_child.lock().unwrap();
-        assert_eq!(first_child_lock.calculate_monte_carlo(), 5); // 50 / 10 = 5
+    // Agent streams do not depend on the order they are first used in
+    let rollout_a = first.agent_stream(RngStream::Rollout, 1).next_u64();
+    second.agent_stream(RngStream::Rollout, 2).next_u64();
+    assert_eq!(second.agent_stream(RngStream::Rollout, 1).next_u64(), rollout_a);
+    assert_ne!(first.agent_stream(RngStream::Selection, 1).next_u64(), rollout_a);
+}
+
+#[test]
+fn test_combat_damages_kills_and_rewards() {
+    let world = GameWorld::create_world(vec!["vffff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+
+    let mut attacker = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let mut victim = Agent::new_agent(&mut StdRng::seed_from_u64(2));
+    for (agent, aggression) in [(&attacker, 1.0), (&victim, 0.0)] {
+        let mut scores = agent.get_genes().gene_scores.lock().unwrap();
+        scores.insert(GeneType::Aggression, aggression);
+        scores.insert(GeneType::SelfPreservation, 0.0);
     }
+    let (attacker_id, victim_id) = (attacker.get_id(), victim.get_id());
+    attacker.set_action(NpcAction::AttackAgent);
+    attacker.set_target(Target::Agent);
+    attacker.set_agent_target_id(victim_id);
+    attacker.set_status(Status::Attacking);
+    victim.set_action(NpcAction::Rest);
+    victim.set_status(Status::Moving);
+    state.add_agent(attacker, NpcSnapshot::new(Position::new(2, 1), 100, 100));
+    state.add_agent(victim, NpcSnapshot::new(Position::new(3, 1), 20, 100));
+
+    // A full Aggression hit does 15 damage, the victim fights back
+    let events = state.update_agents();
+    assert!(events.iter().any(|event| *event
+        == GameEvent::Attacked {
+            agent_id: attacker_id,
+            target: Target::Agent,
+            target_id: victim_id,
+            damage: 15,
+        }));
+    let victim = &state.get_agent(victim_id).unwrap().agent;
+    assert_eq!(state.get_agent(victim_id).unwrap().npc.energy, 5);
+    assert_eq!(victim.get_status(), Status::Retaliating);
+    assert_eq!(victim.get_retaliation_target_id(), attacker_id);
+
+    // The next hit kills it and pays the attacker
+    let events = state.update_agents();
+    assert_eq!(state.get_agent(victim_id).unwrap().agent.get_status(), Status::Dead);
+    assert_eq!(events.reward_for(attacker_id), 20.0);
+    assert!(state.get_agent(attacker_id).unwrap().npc.energy < 100);
+
+    // Monsters take less damage and are worth more
+    let monster = Monster::new_monster(Position::new(1, 1));
+    let monster_id = monster.get_id();
+    state.add_monster(monster, NpcSnapshot::new(Position::new(1, 1), 11, 100));
+    let attacker = &mut state.get_agent_mut(attacker_id).unwrap().agent;
+    attacker.set_action(NpcAction::AttackMonster);
+    attacker.set_target(Target::Monster);
+    attacker.set_monster_target_id(monster_id);
+    attacker.set_status(Status::Attacking);
+    let events = state.update_agents();
+    assert_eq!(state.monsters[0].monster.get_status(), Status::Dead);
+    assert_eq!(events.reward_for(attacker_id), 50.0);
+}
+
+#[test]
+fn test_monster_hunts_and_leashes() {
+    let world = GameWorld::create_world(vec!["vfffffffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+
+    let mut monster = Monster::new_monster(Position::new(5, 0));
+    monster.set_stats(MonsterStats {
+        aggro_radius: 2,
+        leash_radius: 3,
+        ..MonsterStats::default()
+    });
+    let monster_id = monster.get_id();
+    state.add_monster(monster, NpcSnapshot::new(Position::new(5, 0), 100, 100));
+    let agent = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let agent_id = agent.get_id();
+    state.add_agent(agent, NpcSnapshot::new(Position::new(7, 0), 20, 100));
+
+    // The agent is in range, the monster closes in and then hits it
+    let events = state.update_monsters();
+    assert!(events.iter().any(|event| *event
+        == GameEvent::MonsterMoved {
+            monster_id,
+            from: Position::new(5, 0),
+            to: Position::new(6, 0),
+        }));
+    assert_eq!(state.monsters[0].monster.get_status(), Status::Attacking);
+    let events = state.update_monsters();
+    assert!(events.iter().any(|event| *event
+        == GameEvent::MonsterAttacked {
+            monster_id,
+            agent_id,
+            damage: 8,
+        }));
+    assert_eq!(state.get_agent(agent_id).unwrap().npc.energy, 12);
+
+    // Past the leash the monster gives up and walks home
+    state.get_agent_mut(agent_id).unwrap().npc.position = Position::new(9, 0);
+    state.update_monsters();
+    assert_eq!(state.monsters[0].monster.get_status(), Status::Finished);
+    assert!(!state.monsters[0].monster.has_target());
+    assert_eq!(state.monsters[0].npc.position, Position::new(5, 0));
+    state.update_monsters();
+    assert_eq!(state.monsters[0].monster.get_status(), Status::Idle);
 }
 
 #[test]
-fn test_tree_traversal_and_print() {
-    let root = create_sample_tree();
+fn test_treasure_is_looted_shared_and_respawned() {
+    let world = GameWorld::create_world(vec!["vffff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+
+    let mut leader = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let mut follower = Agent::new_agent(&mut StdRng::seed_from_u64(2));
+    let mut treasure = Treasure::new_treasure();
+    treasure.set_reward(30);
+    treasure.set_loot_per_turn(20);
+    let (leader_id, follower_id, treasure_id) =
+        (leader.get_id(), follower.get_id(), treasure.get_id());
+    leader.set_action(NpcAction::TreasureHunt);
+    leader.set_treasure_target_id(treasure_id);
+    leader.set_status(Status::Working);
+    follower.set_action(NpcAction::TreasureHunt);
+    follower.set_leader_id(leader_id);
+    follower.set_status(Status::Following);
+    state.add_agent(leader, NpcSnapshot::new(Position::new(3, 1), 100, 100));
+    state.add_agent(follower, NpcSnapshot::new(Position::new(2, 1), 100, 100));
+    state.add_treasure(treasure, NpcSnapshot::new(Position::new(4, 1), 0, 0));
+
+    // One turn of loot is split with the follower and the leader keeps working
+    let events = state.update_agents();
+    assert_eq!(events.reward_for(leader_id), 10.0);
+    assert_eq!(events.reward_for(follower_id), 10.0);
+    assert_eq!(state.treasures[0].treasure.get_reward(), 10);
+    assert_eq!(state.get_agent(leader_id).unwrap().agent.get_status(), Status::Working);
+
+    // The last of it empties the treasure
+    let events = state.update_agents();
+    assert_eq!(events.reward_for(leader_id), 5.0);
+    assert!(events.iter().any(|event| *event
+        == GameEvent::TreasureDepleted {
+            agent_id: leader_id,
+            treasure_id,
+        }));
+    assert!(state.treasures.is_empty());
+    assert_eq!(state.get_agent(leader_id).unwrap().agent.get_status(), Status::Finished);
+
+    // A missing treasure waits out the delay before it can come back
+    let config = TreasureConfig {
+        respawn_delay: 1,
+        respawn_chance: 1.0,
+        ..TreasureConfig::default()
+    };
+    let mut spawner = TreasureSpawner::new(config, 2, vec![Position::new(4, 1)]);
+    let mut rng = StdRng::seed_from_u64(3);
+    assert_eq!(spawner.tick(1, &mut rng), 0);
+    assert_eq!(spawner.tick(1, &mut rng), 1);
+    assert_eq!(spawner.pick_spawn(&[Position::new(4, 1)], &mut rng), None);
+}
+
+#[test]
+fn test_work_produces_and_sells_goods() {
+    let world = GameWorld::create_world(vec!["vfffF".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let farm = Position::new(4, 0);
+
+    let mut ids = Vec::new();
+    for (seed, position) in [(1, Position::new(3, 0)), (2, Position::new(3, 1))] {
+        let mut agent = Agent::new_agent(&mut StdRng::seed_from_u64(seed));
+        agent.set_action(NpcAction::Work(WorkType::Farming(None)));
+        ids.push(agent.get_id());
+        state.add_agent(agent, NpcSnapshot::new(position, 100, 100));
+    }
+
+    // Both pick the farm next to them and share its yield
+    let events = state.update_agents();
+    assert_eq!(
+        state.get_agent(ids[0]).unwrap().agent.get_action(),
+        NpcAction::Work(WorkType::Farming(Some(farm)))
+    );
+    assert!(events.iter().any(|event| *event
+        == GameEvent::Produced {
+            agent_id: ids[1],
+            tile: farm,
+            resource: ResourceType::Food,
+            amount: 3,
+        }));
+    assert_eq!(production(WorkType::Farming(Some(farm)), 1), 4);
+
+    // After a few turns the goods are carried back to the village
+    for _ in 1..WORK_TURNS {
+        state.update_agents();
+    }
+    let agent = &state.get_agent(ids[0]).unwrap().agent;
+    assert_eq!(agent.get_inventory().get(ResourceType::Food), 3 * WORK_TURNS);
+    assert_eq!(agent.get_status(), Status::Finished);
 
-    fn traverse_and_print(node: Arc<Mutex<Node>>, depth: usize) {
-        let node_lock = node.lock().unwrap();
-        match &node_lock.node_type {
-            NodeType::ActionNode { action, .. } => {
-                println!("{}ActionNode: {:?}", "  ".repeat(depth), action);
-            }
-            NodeType::InformationNode { action_taken, .. } => {
-                println!("{}InformationNode: {:?}", "  ".repeat(depth), action_taken);
-            }
+    // A merchant in the village sells the most valuable goods first and keeps some food
+    let merchant = state.get_agent_mut(ids[0]).unwrap();
+    merchant.npc.position = Position::new(0, 0);
+    merchant.agent.get_inventory_mut().add(ResourceType::Ore, 4);
+    merchant.agent.set_action(NpcAction::Work(WorkType::Merchant));
+    merchant.agent.set_status(Status::Idle);
+    let events = state.update_agents();
+    assert_eq!(events.reward_for(ids[0]), 14.0);
+    let events = state.update_agents();
+    assert_eq!(events.reward_for(ids[0]), 4.0);
+    let inventory = state.get_agent(ids[0]).unwrap().agent.get_inventory().clone();
+    assert_eq!(inventory.get(ResourceType::Food), FOOD_RESERVE);
+    assert_eq!(inventory.get(ResourceType::Gold), 18);
+
+    // Agents eat every turn, the hungry ones lose energy
+    state.update_agents();
+    assert_eq!(state.get_agent(ids[0]).unwrap().agent.get_status(), Status::Idle);
+    let hungry = state.get_agent_mut(ids[1]).unwrap();
+    hungry.agent.get_inventory_mut().remove(ResourceType::Food, u32::MAX);
+    let events = state.consume_food();
+    assert_eq!(
+        events.0,
+        vec![GameEvent::Hungry {
+            agent_id: ids[1],
+            energy_lost: 2,
+        }]
+    );
+    let fed = &state.get_agent(ids[0]).unwrap().agent;
+    assert_eq!(fed.get_inventory().get(ResourceType::Food), FOOD_RESERVE - 1);
+}
+
+#[test]
+fn test_steal_against_perception() {
+    assert_eq!(
+        theft_outcome(0.75, 0.25),
+        TheftOutcome {
+            fraction: 0.5,
+            detected: false,
+        }
+    );
+    assert_eq!(
+        theft_outcome(0.25, 0.75),
+        TheftOutcome {
+            fraction: 0.0,
+            detected: true,
         }
+    );
 
-        if let NodeType::ActionNode { children, .. } | NodeType::InformationNode { children, .. } =
-            &node_lock.node_type
-        {
-            for child in children {
-                traverse_and_print(child.clone(), depth + 1);
-            }
+    let world = GameWorld::create_world(vec!["vffff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let mut thief = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let mut victim = Agent::new_agent(&mut StdRng::seed_from_u64(2));
+    let mut follower = Agent::new_agent(&mut StdRng::seed_from_u64(3));
+    for (agent, stealth, perception) in [(&thief, 0.75, 0.0), (&victim, 0.0, 0.25)] {
+        let mut scores = agent.get_genes().gene_scores.lock().unwrap();
+        scores.insert(GeneType::Stealth, stealth);
+        scores.insert(GeneType::Perception, perception);
+        scores.insert(GeneType::Aggression, 1.0);
+        scores.insert(GeneType::SelfPreservation, 0.0);
+    }
+    let (thief_id, victim_id, follower_id) = (thief.get_id(), victim.get_id(), follower.get_id());
+    thief.set_action(NpcAction::Steal);
+    thief.set_agent_target_id(victim_id);
+    thief.set_status(Status::Working);
+    victim.set_action(NpcAction::Rest);
+    victim.set_status(Status::Moving);
+    victim.get_inventory_mut().add(ResourceType::Gold, 100);
+    follower.set_action(NpcAction::Rest);
+    follower.set_leader_id(victim_id);
+    follower.set_status(Status::Following);
+    state.add_agent(thief, NpcSnapshot::new(Position::new(2, 1), 100, 100));
+    state.add_agent(victim, NpcSnapshot::new(Position::new(3, 1), 100, 100));
+    state.add_agent(follower, NpcSnapshot::new(Position::new(4, 1), 100, 100));
+
+    // A stealthy thief takes half of the gold without being noticed
+    let events = state.update_agents();
+    assert!(events.iter().any(|event| *event
+        == GameEvent::Stole {
+            agent_id: thief_id,
+            target_id: victim_id,
+            amount: 50,
+        }));
+    assert_eq!(events.reward_for(thief_id), 50.0);
+    let victim_state = state.get_agent(victim_id).unwrap();
+    assert_eq!(victim_state.agent.get_inventory().get(ResourceType::Gold), 50);
+    assert!(victim_state.agent.get_memory().is_empty());
+    assert_ne!(state.get_agent(thief_id).unwrap().agent.get_status(), Status::Working);
+
+    // Against a perceptive victim it takes less and gets caught
+    victim_state
+        .agent
+        .get_genes()
+        .gene_scores
+        .lock()
+        .unwrap()
+        .insert(GeneType::Perception, 1.0);
+    let thief_state = state.get_agent_mut(thief_id).unwrap();
+    thief_state
+        .agent
+        .get_genes()
+        .gene_scores
+        .lock()
+        .unwrap()
+        .insert(GeneType::Stealth, 0.875);
+    thief_state.npc.position = Position::new(2, 1);
+    thief_state.agent.set_path(Vec::new());
+    thief_state.agent.set_status(Status::Working);
+    let events = state.update_agents();
+    assert!(events.iter().any(|event| *event
+        == GameEvent::TheftDetected {
+            agent_id: thief_id,
+            target_id: victim_id,
+            stolen: true,
+        }));
+    let victim = &state.get_agent(victim_id).unwrap().agent;
+    assert_eq!(victim.get_inventory().get(ResourceType::Gold), 41);
+    assert!(victim
+        .get_memory()
+        .contains(&Memory::new(thief_id, victim_id, NpcAction::Steal)));
+    assert!((victim.get_agent_opinion(thief_id) - 0.3).abs() < 1e-6);
+    assert_eq!(victim.get_status(), Status::Retaliating);
+    assert_eq!(victim.get_retaliation_target_id(), thief_id);
+    let follower = &state.get_agent(follower_id).unwrap().agent;
+    assert!((follower.get_agent_opinion(thief_id) - 0.4).abs() < 1e-6);
+}
+
+#[test]
+fn test_talking_exchanges_opinions_and_gossip() {
+    let world = GameWorld::create_world(vec!["vvvff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let mut listener = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let mut gossiper = Agent::new_agent(&mut StdRng::seed_from_u64(2));
+    let thief = Agent::new_agent(&mut StdRng::seed_from_u64(3));
+    let (listener_id, gossiper_id) = (listener.get_id(), gossiper.get_id());
+    let thief_id = thief.get_id();
+    for agent in [&listener, &gossiper] {
+        agent
+            .get_genes()
+            .gene_scores
+            .lock()
+            .unwrap()
+            .insert(GeneType::Social, 1.0);
+    }
+    for agent in [&mut listener, &mut gossiper] {
+        agent.set_action(NpcAction::Talk);
+        agent.set_status(Status::Talking);
+    }
+    let theft = Memory::new(thief_id, 99, NpcAction::Steal);
+    gossiper.add_to_memory(theft);
+    gossiper.add_to_memory(theft);
+    assert_eq!(gossiper.get_memory().len(), 1);
+    gossiper.modify_opinion(thief_id, -0.4);
+    gossiper.modify_opinion(listener_id, 0.5);
+    state.add_agent(listener, NpcSnapshot::new(Position::new(0, 0), 100, 100));
+    state.add_agent(gossiper, NpcSnapshot::new(Position::new(1, 0), 100, 100));
+    state.add_agent(thief, NpcSnapshot::new(Position::new(4, 1), 100, 100));
+
+    // Both hear what the other thought at the start of the turn, whoever goes first
+    let mut reversed = state.clone();
+    reversed.agents.reverse();
+    reversed.update_agents();
+
+    // The listener hears about the theft and takes on some of the gossiper's dislike of the thief
+    let events = state.update_agents();
+    for id in [listener_id, gossiper_id] {
+        let (agent, other) = (
+            &state.get_agent(id).unwrap().agent,
+            &reversed.get_agent(id).unwrap().agent,
+        );
+        for about in [listener_id, gossiper_id, thief_id] {
+            assert_eq!(agent.get_agent_opinion(about), other.get_agent_opinion(about));
         }
+        assert_eq!(agent.get_memory(), other.get_memory());
     }
+    assert!(events.iter().any(|event| *event
+        == GameEvent::Talked {
+            agent_id: listener_id,
+            partner_id: gossiper_id,
+            gossip: 1,
+        }));
+    let listener = &state.get_agent(listener_id).unwrap().agent;
+    assert_eq!(listener.get_memory(), &vec![theft]);
+    assert!((listener.get_agent_opinion(thief_id) - 0.36).abs() < 1e-6);
+    // Two social agents like each other more, the gossiper's opinion of the listener is not copied
+    assert!((listener.get_agent_opinion(gossiper_id) - 0.6).abs() < 1e-6);
+    assert!(!listener.get_opinions().opinion_scores.contains_key(&listener_id));
 
-    traverse_and_print(root, 0);
-}
\ No newline at end of file
+    // They talk until the last turn and stop together
+    let mut events = state.update_agents();
+    for _ in 2..TALK_TURNS {
+        events = state.update_agents();
+    }
+    for (id, partner) in [(listener_id, gossiper_id), (gossiper_id, listener_id)] {
+        assert!(events.iter().any(|event| matches!(event,
+            GameEvent::Talked { agent_id, partner_id, .. } if *agent_id == id && *partner_id == partner)));
+        assert_ne!(state.get_agent(id).unwrap().agent.get_status(), Status::Talking);
+    }
+
+    // Agents that are not talking are not partners, the talking agent stops right away
+    for id in [listener_id, gossiper_id] {
+        let agent_state = state.get_agent_mut(id).unwrap();
+        agent_state.agent.set_action(NpcAction::Rest);
+        agent_state.agent.set_status(Status::Recovering);
+    }
+    let memory_before = state.get_agent(listener_id).unwrap().agent.get_memory().clone();
+    let opinion_before = state
+        .get_agent(listener_id)
+        .unwrap()
+        .agent
+        .get_agent_opinion(thief_id);
+    let thief = state.get_agent_mut(thief_id).unwrap();
+    thief.npc.position = Position::new(1, 1);
+    thief.agent.set_action(NpcAction::Talk);
+    thief.agent.set_status(Status::Talking);
+    let events = state.update_agents();
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, GameEvent::Talked { .. })));
+    let thief = &state.get_agent(thief_id).unwrap().agent;
+    assert_eq!(thief.get_status(), Status::Idle);
+    assert!(thief.get_memory().is_empty());
+    let listener = &state.get_agent(listener_id).unwrap().agent;
+    assert_eq!(listener.get_memory(), &memory_before);
+    assert_eq!(listener.get_agent_opinion(thief_id), opinion_before);
+}
+
+#[test]
+fn test_trade_upkeep_and_worth_score() {
+    let world = GameWorld::create_world(vec!["vvfff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let mut merchant = Agent::new_agent(&mut StdRng::seed_from_u64(1));
+    let mut miner = Agent::new_agent(&mut StdRng::seed_from_u64(2));
+    let (merchant_id, miner_id) = (merchant.get_id(), miner.get_id());
+    merchant.get_inventory_mut().add(ResourceType::Gold, 10);
+    merchant.get_inventory_mut().remove(ResourceType::Food, u32::MAX);
+    merchant.get_inventory_mut().add(ResourceType::Food, 8);
+    merchant.set_action(NpcAction::Work(WorkType::Merchant));
+    miner.get_inventory_mut().remove(ResourceType::Food, u32::MAX);
+    miner.get_inventory_mut().add(ResourceType::Ore, 4);
+    miner.set_action(NpcAction::Rest);
+    miner.set_status(Status::Recovering);
+    state.add_agent(merchant, NpcSnapshot::new(Position::new(0, 0), 100, 100));
+    state.add_agent(miner, NpcSnapshot::new(Position::new(1, 0), 50, 100));
+
+    // The merchant buys the ore next to it, then sells the miner food out of its surplus
+    let events = state.update_agents();
+    assert!(events.iter().any(|event| *event
+        == GameEvent::Traded {
+            agent_id: miner_id,
+            partner_id: merchant_id,
+            resource: ResourceType::Ore,
+            amount: 4,
+            gold: 4,
+        }));
+    assert!(events.iter().any(|event| *event
+        == GameEvent::Traded {
+            agent_id: merchant_id,
+            partner_id: miner_id,
+            resource: ResourceType::Food,
+            amount: 3,
+            gold: 3,
+        }));
+    let miner = &state.get_agent(miner_id).unwrap().agent;
+    assert_eq!(miner.get_inventory().get(ResourceType::Ore), 0);
+    assert_eq!(miner.get_inventory().get(ResourceType::Food), 3);
+    assert_eq!(miner.get_inventory().get(ResourceType::Gold), 1);
+    let merchant = &state.get_agent(merchant_id).unwrap().agent;
+    assert_eq!(merchant.get_inventory().get(ResourceType::Food), FOOD_RESERVE);
+
+    // Eating keeps the miner going, without food it loses energy
+    let energy = state.get_agent(miner_id).unwrap().npc.energy;
+    state.consume_food();
+    assert_eq!(state.get_agent(miner_id).unwrap().npc.energy, energy + 1);
+    state
+        .get_agent_mut(miner_id)
+        .unwrap()
+        .agent
+        .get_inventory_mut()
+        .remove(ResourceType::Food, u32::MAX);
+    state.consume_food();
+    assert_eq!(state.get_agent(miner_id).unwrap().npc.energy, energy - 1);
+
+    // Rollouts score the change in worth, four meals of food count against a resting agent
+    let world = GameWorld::create_world(vec!["vffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let mut agent = Agent::new_agent(&mut StdRng::seed_from_u64(3));
+    let agent_id = agent.get_id();
+    agent.get_inventory_mut().remove(ResourceType::Food, u32::MAX);
+    agent.get_inventory_mut().add(ResourceType::Food, 10);
+    state.add_agent(agent, NpcSnapshot::new(Position::new(0, 0), 100, 100));
+    let policies: Vec<(i32, Box<dyn RolloutPolicy>)> =
+        vec![(agent_id, Box::new(FixedRollout(NpcAction::Rest)))];
+    let reward = rollout(&state, agent_id, &[NpcAction::Rest], &policies, 4, &mut StdRng::seed_from_u64(7));
+    assert_eq!(reward, -4.0 * ResourceType::Food.get_value());
+}
+
+#[test]
+fn test_step_follows_the_execution_order() {
+    let world = GameWorld::create_world(vec!["vffff".to_string(), "fffff".to_string()]);
+    let mut state = GameState::new(Arc::new(world));
+    let mut rng = StdRng::seed_from_u64(1);
+    let resting = Agent::new_agent(&mut rng);
+    let mut stuck = Agent::new_agent(&mut rng);
+    let mut walker = Agent::new_agent(&mut rng);
+    let (resting_id, stuck_id, walker_id) = (resting.get_id(), stuck.get_id(), walker.get_id());
+    stuck.set_action(NpcAction::Rest);
+    stuck.set_status(Status::RequiresInstruction);
+    walker.get_inventory_mut().remove(ResourceType::Food, u32::MAX);
+    walker.set_action(NpcAction::Rest);
+    walker.set_path(vec![Position::new(1, 1), Position::new(0, 0)]);
+    walker.set_status(Status::Moving);
+    state.add_agent(resting, NpcSnapshot::new(Position::new(0, 0), 50, 100));
+    state.add_agent(stuck, NpcSnapshot::new(Position::new(4, 1), 100, 100));
+    state.add_agent(walker, NpcSnapshot::new(Position::new(2, 1), 100, 100));
+
+    let events = state.step(&[(resting_id, NpcAction::Rest)]);
+    assert_eq!(state.turn, 1);
+    assert_eq!(
+        events.0[0],
+        GameEvent::ActionChosen {
+            agent_id: resting_id,
+            action: NpcAction::Rest,
+        }
+    );
+    assert_eq!(state.get_agent(resting_id).unwrap().agent.get_status(), Status::Recovering);
+
+    // An agent without instructions is handed back for a new action
+    assert!(events.iter().any(|event| *event
+        == GameEvent::StatusChanged {
+            agent_id: stuck_id,
+            from: Status::RequiresInstruction,
+            to: Status::Idle,
+        }));
+    assert_eq!(state.get_agent(stuck_id).unwrap().agent.get_status(), Status::Idle);
+
+    // Agents eat before anyone moves, like in the ECS turn
+    let hungry = events.iter().position(|event| {
+        matches!(event, GameEvent::Hungry { agent_id, .. } if *agent_id == walker_id)
+    });
+    let moved = events.iter().position(|event| {
+        matches!(event, GameEvent::Moved { agent_id, .. } if *agent_id == walker_id)
+    });
+    assert!(hungry.unwrap() < moved.unwrap());
+    assert_eq!(state.get_agent(walker_id).unwrap().npc.position, Position::new(1, 1));
+}
@@ -0,0 +1,328 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+use crate::npcs::agent::Agent;
+use crate::npcs::npc_components::npc_status::Status;
+use crate::system::forward_model::events::GameEvent;
+use crate::system::forward_model::game_state::{GameState, NpcSnapshot};
+use crate::system::pathfinding::danger::{DangerConfig, DangerMap, DangerMode};
+use crate::gameworld::world_generator::{generate_world, WorldGenConfig};
+use crate::system::pathfinding::hierarchical::{HierarchicalGraph, CLUSTER_SIZE};
+use crate::system::pathfinding::movement::{Connectivity, CornerCutting, MovementModel};
+use crate::system::pathfinding::pathfinding_calculation::{
+    a_star_pathfinding, a_star_with_danger, get_neighbors, plain_a_star_pathfinding,
+};
+use crate::system::pathfinding::reservation::{MoveOutcome, Occupant, ReservationTable};
+
+fn path_cost(world: &GameWorld, start: Position, path: &[Position]) -> f32 {
+    let movement = world.get_movement();
+    let mut previous = start;
+    let mut cost = 0.0;
+    for position in path {
+        cost += movement.step_cost(world, previous, *position).unwrap();
+        previous = *position;
+    }
+    cost
+}
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+}
+
+#[test]
+fn test_distance_field_matches_a_star() {
+    let world = GameWorld::create_world(vec![
+        "vffffffff".to_string(),
+        "lllllllfl".to_string(),
+        "fffmfffff".to_string(),
+        "ffffffffv".to_string(),
+    ]);
+    let start = Position::new(0, 3);
+
+    // The closest village by travel is around the lake, not the one above
+    let (village, path) = world
+        .path_to_closest_tiletype(start, TileType::Village)
+        .unwrap();
+    assert_eq!(village, Position::new(8, 3));
+    assert_eq!(path.last(), Some(&village));
+
+    let field = world
+        .get_path_service()
+        .distance_field(&world, TileType::Village);
+    let a_star = a_star_pathfinding(&world, start, village);
+    assert_close(field.get_distance(start).unwrap(), path_cost(&world, start, &a_star));
+    assert_close(path_cost(&world, start, &path), path_cost(&world, start, &a_star));
+}
+
+#[test]
+fn test_path_service_invalidates_on_tile_change() {
+    let mut world = GameWorld::create_world(vec!["vfffff".to_string(), "ffffff".to_string()]);
+    assert_eq!(
+        world.find_closest_tiletype(Position::new(5, 1), TileType::Village),
+        Some(Position::new(0, 0))
+    );
+    assert_eq!(world.get_path_service().cached_fields(), 1);
+
+    // Copies share the tiles and the cache until one of them changes a tile
+    let copy = world.copy();
+    assert_eq!(copy.get_path_service().cached_fields(), 1);
+
+    world.set_tile_type(4, 1, TileType::Village);
+    assert_eq!(world.get_path_service().cached_fields(), 0);
+    assert_eq!(
+        world.find_closest_tiletype(Position::new(5, 1), TileType::Village),
+        Some(Position::new(4, 1))
+    );
+
+    // The copy still has the old tiles and its cache is still valid
+    assert_eq!(copy.get_tile_type(4, 1), Some(TileType::Forest));
+    assert_eq!(copy.get_path_service().cached_fields(), 1);
+    assert_eq!(
+        copy.find_closest_tiletype(Position::new(5, 1), TileType::Village),
+        Some(Position::new(0, 0))
+    );
+}
+
+#[test]
+fn test_movement_model_limits_steps_and_heuristic() {
+    let mut world = GameWorld::create_world(vec![
+        "flf".to_string(),
+        "lff".to_string(),
+        "fff".to_string(),
+    ]);
+    let start = Position::new(0, 0);
+    let goal = Position::new(2, 2);
+
+    // Squeezing between the two lakes is not allowed by default
+    assert!(!world
+        .get_movement()
+        .neighbors(&world, start)
+        .contains(&Position::new(1, 1)));
+    assert!(a_star_pathfinding(&world, start, goal).is_empty());
+
+    world.set_movement(MovementModel::new(Connectivity::Eight, CornerCutting::Allow));
+    let path = a_star_pathfinding(&world, start, goal);
+    assert_eq!(path, vec![Position::new(1, 1), goal]);
+
+    // With four-connected movement every step is orthogonal
+    world.set_movement(MovementModel::new(Connectivity::Four, CornerCutting::Allow));
+    let path = a_star_pathfinding(&world, Position::new(2, 0), Position::new(0, 2));
+    assert_eq!(path.len(), 4);
+    let mut previous = Position::new(2, 0);
+    for position in path.iter() {
+        assert_eq!((position.x - previous.x).abs() + (position.y - previous.y).abs(), 1);
+        previous = *position;
+    }
+
+    // The heuristic never overestimates the cost of the path found
+    for connectivity in [Connectivity::Four, Connectivity::Eight] {
+        world.set_movement(MovementModel::new(connectivity, CornerCutting::Forbid));
+        let movement = world.get_movement();
+        let start = Position::new(2, 0);
+        let goal = Position::new(0, 2);
+        let path = a_star_pathfinding(&world, start, goal);
+        assert!(movement.heuristic(start, goal) <= path_cost(&world, start, &path));
+    }
+}
+
+#[test]
+fn test_danger_map_routes_around_monsters() {
+    let world = GameWorld::create_world(vec!["fffffffff".to_string(); 7]);
+    let start = Position::new(0, 3);
+    let goal = Position::new(8, 3);
+    let monster = Position::new(4, 3);
+    let config = DangerConfig {
+        radius: 2,
+        ..Default::default()
+    };
+
+    // A fearless agent walks straight past the monster
+    let fearless = DangerMap::compute(&world, &[monster], &config, 0.0);
+    assert!(!fearless.is_dangerous(monster));
+    let path = a_star_with_danger(&world, start, goal, &fearless);
+    assert_eq!(path, a_star_pathfinding(&world, start, goal));
+
+    // A careful agent keeps its distance
+    let careful = DangerMap::compute(&world, &[monster], &config, 1.0);
+    let path = a_star_with_danger(&world, start, goal, &careful);
+    assert_eq!(path.last(), Some(&goal));
+    assert!(!path.contains(&monster));
+    assert!(path.len() > 8);
+
+    // In the hard mode the blocked tiles are never entered
+    let hard_config = DangerConfig {
+        mode: DangerMode::Hard,
+        ..config
+    };
+    let hard = DangerMap::compute(&world, &[monster], &hard_config, 1.0);
+    let path = a_star_with_danger(&world, start, goal, &hard);
+    assert_eq!(path.last(), Some(&goal));
+    assert!(path.iter().all(|position| !hard.is_blocked(*position)));
+
+    // A goal inside the blocked area can still be reached
+    let path = a_star_with_danger(&world, start, monster, &hard);
+    assert_eq!(path.last(), Some(&monster));
+}
+
+#[test]
+fn test_reservation_table_resolves_conflicts() {
+    let world = GameWorld::create_world(vec!["fffff".to_string(); 3]);
+    let mut table = ReservationTable::new();
+    let mut add = |id: i32, from: Position, to: Position| {
+        table.occupy(from, Occupant::Agent(id));
+        table.request(id, from, &[to]);
+    };
+
+    // Two agents want the same tile, the third queues behind the loser
+    add(1, Position::new(0, 0), Position::new(1, 0));
+    add(2, Position::new(2, 0), Position::new(1, 0));
+    add(3, Position::new(3, 0), Position::new(2, 0));
+    // An agent can follow one that moves on
+    add(4, Position::new(0, 1), Position::new(1, 1));
+    add(5, Position::new(1, 1), Position::new(2, 1));
+    // Two agents swapping tiles
+    add(6, Position::new(3, 2), Position::new(4, 2));
+    add(7, Position::new(4, 2), Position::new(3, 2));
+    // A monster does not move out of the way
+    add(8, Position::new(0, 2), Position::new(1, 2));
+    table.occupy(Position::new(1, 2), Occupant::Monster(0));
+
+    let outcomes = table.resolve(&world);
+    assert_eq!(outcomes[&1], MoveOutcome::Move);
+    assert_eq!(outcomes[&2], MoveOutcome::Wait { blocked_by: Occupant::Agent(1) });
+    assert_eq!(outcomes[&3], MoveOutcome::Wait { blocked_by: Occupant::Agent(2) });
+    assert_eq!(outcomes[&4], MoveOutcome::Move);
+    assert_eq!(outcomes[&5], MoveOutcome::Move);
+    assert_eq!(outcomes[&6], MoveOutcome::Deadlock { with: 7 });
+    assert_eq!(outcomes[&7], MoveOutcome::Deadlock { with: 6 });
+    assert_eq!(outcomes[&8], MoveOutcome::Wait { blocked_by: Occupant::Monster(0) });
+}
+
+#[test]
+fn test_agents_pass_each_other_in_a_corridor() {
+    let world = GameWorld::create_world(vec![
+        "llllfll".to_string(),
+        "fffffff".to_string(),
+        "lllllll".to_string(),
+    ]);
+    let mut state = GameState::new(Arc::new(world.clone()));
+    let mut rng = StdRng::seed_from_u64(5);
+    let goals = [Position::new(6, 1), Position::new(0, 1)];
+    for goal in goals.iter() {
+        let start = Position::new(6 - goal.x, 1);
+        let mut agent = Agent::new_agent(&mut rng);
+        agent.set_path(a_star_pathfinding(&world, start, *goal));
+        agent.set_status(Status::Moving);
+        state.add_agent(agent, NpcSnapshot::new(start, 100, 100));
+    }
+
+    let mut deadlocks = 0;
+    for _ in 0..20 {
+        let events = state.move_agents();
+        deadlocks += events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::DeadlockDetected { .. }))
+            .count();
+
+        // Never two agents on one tile
+        let positions: HashSet<Position> =
+            state.agents.iter().map(|agent| agent.npc.position).collect();
+        assert_eq!(positions.len(), 2);
+    }
+
+    assert!(deadlocks > 0);
+    for (agent, goal) in state.agents.iter().zip(goals.iter()) {
+        assert_eq!(agent.npc.position, *goal);
+    }
+}
+
+#[test]
+fn test_hierarchical_paths_match_a_star() {
+    let config = WorldGenConfig {
+        width: 64,
+        height: 64,
+        ..Default::default()
+    };
+    let world = generate_world(&config, &mut StdRng::seed_from_u64(11));
+    let graph = HierarchicalGraph::build(&world, CLUSTER_SIZE);
+    assert_eq!(graph.cluster_count(), 16);
+
+    let villages: Vec<Position> = world
+        .iter_tiles()
+        .filter(|(_, tile)| tile.get_tile_type() == TileType::Village)
+        .map(|(position, _)| position)
+        .collect();
+    let mut compared = 0;
+    for start in villages.iter() {
+        for goal in villages.iter() {
+            if (start.x - goal.x).abs().max((start.y - goal.y).abs()) <= CLUSTER_SIZE {
+                continue;
+            }
+            let plain = plain_a_star_pathfinding(&world, *start, *goal);
+            let path = graph.find_path(&world, *start, *goal).unwrap();
+            assert_eq!(path.last(), Some(goal));
+
+            // Every step goes to a neighbour of the previous tile
+            let mut previous = *start;
+            for position in path.iter() {
+                assert!(get_neighbors(&world, previous).contains(position));
+                previous = *position;
+            }
+            let ratio = path_cost(&world, *start, &path) / path_cost(&world, *start, &plain);
+            assert!(ratio >= 1.0 - 1e-4 && ratio < 1.5, "cost ratio {}", ratio);
+            compared += 1;
+        }
+    }
+    assert!(compared > 0);
+
+    // Long searches through the default entry point build the graph once
+    let (start, goal) = (villages[0], *villages.last().unwrap());
+    a_star_pathfinding(&world, start, goal);
+    assert_eq!(
+        world.get_path_service().has_hierarchy(),
+        (start.x - goal.x).abs().max((start.y - goal.y).abs()) > CLUSTER_SIZE
+    );
+}
+
+#[test]
+fn test_agents_back_off_in_a_corridor_without_side_tiles() {
+    let world = GameWorld::create_world(vec![
+        "lllllll".to_string(),
+        "vfffffv".to_string(),
+        "lllllll".to_string(),
+    ]);
+    let mut state = GameState::new(Arc::new(world.clone()));
+    let mut rng = StdRng::seed_from_u64(5);
+    let goals = [Position::new(6, 1), Position::new(0, 1)];
+    for (start, goal) in [(Position::new(2, 1), goals[0]), (Position::new(4, 1), goals[1])] {
+        let mut agent = Agent::new_agent(&mut rng);
+        agent.set_path(a_star_pathfinding(&world, start, goal));
+        agent.set_status(Status::Moving);
+        state.add_agent(agent, NpcSnapshot::new(start, 100, 100));
+    }
+
+    let mut deadlocks = 0;
+    for _ in 0..20 {
+        let events = state.move_agents();
+        deadlocks += events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::DeadlockDetected { .. }))
+            .count();
+
+        // Only villages hold both agents
+        let (first, second) = (state.agents[0].npc.position, state.agents[1].npc.position);
+        assert!(first != second || world.get_tile_type(first.x, first.y) == Some(TileType::Village));
+    }
+
+    // One agent gave way back to a village so the other could pass
+    assert!(deadlocks > 0);
+    for (agent, goal) in state.agents.iter().zip(goals.iter()) {
+        assert_eq!(agent.npc.position, *goal);
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::errors::WorldLoadError;
+use crate::gameworld::position::Position;
+use crate::gameworld::tile_types::TileType;
+use crate::gameworld::world::GameWorld;
+use crate::gameworld::world_file::{parse_world, SpawnKind};
+use crate::gameworld::world_generator::{generate_world, WorldGenConfig};
+use crate::gameworld::world_validator::{
+    find_unreachable, validate_world, ValidationMode, WorldConnectivity,
+};
+use crate::system::pathfinding::movement::{Connectivity, CornerCutting, MovementModel};
+
+const WORLD_FILE: &str = r#"
+name = "small"
+
+[counts]
+agents = 3
+monsters = 1
+treasures = 0
+
+[[regions]]
+name = "camp"
+x = 0
+y = 0
+width = 2
+height = 2
+
+[[spawns]]
+kind = "agent"
+x = 4
+y = 2
+
+[[monster_types]]
+name = "wolf"
+damage = 5
+aggro_radius = 4
+
+[[spawns]]
+kind = "monster"
+region = "camp"
+monster_type = "wolf"
+
+[[overrides]]
+region = "camp"
+valid_monster_spawn = false
+
+[[overrides]]
+x = 4
+y = 0
+tile = "d"
+---
+vffff
+ffmff
+fffff
+"#;
+
+#[test]
+fn test_parse_world_file() {
+    let (world, definition) = parse_world(WORLD_FILE).unwrap();
+    assert_eq!(definition.name, "small");
+    assert_eq!(definition.get_count(SpawnKind::Agent), 3);
+    assert_eq!(world.get_width_max(), 4);
+    assert_eq!(world.get_height_max(), 2);
+
+    // Overrides are applied to the tiles
+    assert!(!world.get_tile(1, 1).unwrap().is_monster_spawn());
+    assert!(world.get_tile(2, 2).unwrap().is_monster_spawn());
+    assert_eq!(world.get_tile_type(4, 0), Some(TileType::Dungeon));
+
+    let mut rng = StdRng::seed_from_u64(0);
+    assert_eq!(
+        definition.spawn_positions(SpawnKind::Agent, &world, &mut rng),
+        vec![Position::new(4, 2)]
+    );
+    let monster = definition.spawn_positions(SpawnKind::Monster, &world, &mut rng);
+    assert_eq!(monster.len(), 1);
+    assert!(definition.get_region("camp").unwrap().contains(&monster[0]));
+
+    // Monsters keep the stats of their type, the rest come from the defaults
+    let monsters = definition.monster_spawns(&world, &mut rng);
+    assert_eq!(monsters.len(), 1);
+    assert_eq!(monsters[0].1.name, "wolf");
+    assert_eq!((monsters[0].1.damage, monsters[0].1.max_energy), (5, 100));
+}
+
+#[test]
+fn test_world_file_errors() {
+    // Line numbers count the header
+    match parse_world("name = \"bad\"\n---\nfff\nfxf\n") {
+        Err(WorldLoadError::InvalidTile { line, column, character }) => {
+            assert_eq!((line, column, character), (4, 2, 'x'))
+        }
+        _ => panic!("expected an invalid tile"),
+    }
+
+    let unknown_region = "[[spawns]]\nkind = \"agent\"\nregion = \"nowhere\"\n---\nvff\n";
+    assert!(matches!(parse_world(unknown_region), Err(WorldLoadError::UnknownRegion(_))));
+
+    let too_many = "[counts]\nagents = 1\n\n[[spawns]]\nkind = \"agent\"\nx = 0\ny = 0\ncount = 2\n---\nvff\n";
+    assert!(matches!(parse_world(too_many), Err(WorldLoadError::TooManySpawns { .. })));
+
+    let unknown_type = "[[spawns]]\nkind = \"monster\"\nx = 1\ny = 0\nmonster_type = \"troll\"\n---\nvff\n";
+    assert!(matches!(parse_world(unknown_type), Err(WorldLoadError::UnknownMonsterType(_))));
+
+    for tile in ["l", "m"] {
+        let blocked = format!("[[spawns]]\nkind = \"agent\"\nx = 2\ny = 0\n---\nvf{}\n", tile);
+        assert!(matches!(parse_world(&blocked), Err(WorldLoadError::InvalidSpawn(_))));
+    }
+
+    let bad_treasure = "[treasure]\nmin_value = 10\nmax_value = 5\n---\nvff\n";
+    assert!(matches!(parse_world(bad_treasure), Err(WorldLoadError::InvalidTreasureConfig(_))));
+
+    assert!(matches!(parse_world("agents = 3\n---\nvff\n"), Err(WorldLoadError::Header(_))));
+    assert!(matches!(parse_world("name = \"empty\"\n---\n"), Err(WorldLoadError::EmptyGrid)));
+}
+
+/// Tiles reachable from `start` without crossing water.
+fn reachable_from(world: &GameWorld, start: Position) -> HashSet<Position> {
+    let mut reached = HashSet::from([start]);
+    let mut queue = vec![start];
+    while let Some(position) = queue.pop() {
+        for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+            let next = Position::new(position.x + dx, position.y + dy);
+            let passable = world
+                .get_tile_type(next.x, next.y)
+                .map_or(false, |tile_type| tile_type.get_travel_weight() > 0.0);
+            if passable && reached.insert(next) {
+                queue.push(next);
+            }
+        }
+    }
+    reached
+}
+
+fn positions_of(world: &GameWorld, tile_type: TileType) -> Vec<Position> {
+    world
+        .iter_tiles()
+        .filter(|(_, tile)| tile.get_tile_type() == tile_type)
+        .map(|(position, _)| position)
+        .collect()
+}
+
+#[test]
+fn test_generated_world_is_seeded_and_connected() {
+    let config = WorldGenConfig::default();
+    let world = generate_world(&config, &mut StdRng::seed_from_u64(3));
+    let same = generate_world(&config, &mut StdRng::seed_from_u64(3));
+    let other = generate_world(&config, &mut StdRng::seed_from_u64(4));
+
+    let grid = |world: &GameWorld| {
+        (0..config.height)
+            .flat_map(|y| (0..config.width).map(move |x| (x, y)))
+            .map(|(x, y)| world.get_tile_type(x, y).unwrap().to_char())
+            .collect::<String>()
+    };
+    assert_eq!(world.get_width_max(), config.width - 1);
+    assert_eq!(grid(&world), grid(&same));
+    assert_ne!(grid(&world), grid(&other));
+
+    // Every village can be reached from every other village
+    let villages = positions_of(&world, TileType::Village);
+    assert!(!villages.is_empty());
+    let reached = reachable_from(&world, villages[0]);
+    assert!(villages.iter().all(|village| reached.contains(village)));
+
+    for tile_type in [TileType::Farm, TileType::Mine, TileType::Dungeon] {
+        assert!(!positions_of(&world, tile_type).is_empty(), "no {:?}", tile_type);
+    }
+}
+
+#[test]
+fn test_validator_repairs_or_rejects_unreachable_points() {
+    let contents = "name = \"split\"\n---\nvvflfd\nffflff\nlllllf\nfflfff\nfflflf\n";
+
+    let (mut world, definition) = parse_world(contents).unwrap();
+    let connectivity = WorldConnectivity::compute(&world);
+    assert_eq!(connectivity.component_count(), 3);
+    assert!(!connectivity.is_connected_to_village(Position::new(0, 4)));
+
+    // The dungeon is across the water from the villages
+    let unreachable = find_unreachable(&world, &definition);
+    assert_eq!(unreachable.len(), 1);
+    assert_eq!(unreachable[0].position, Position::new(5, 0));
+
+    assert!(matches!(
+        validate_world(&mut world, &definition, ValidationMode::Reject),
+        Err(WorldLoadError::Unreachable(1))
+    ));
+    assert!(validate_world(&mut world, &definition, ValidationMode::Warn).is_ok());
+
+    let report = validate_world(&mut world, &definition, ValidationMode::Repair).unwrap();
+    assert!(report.is_valid());
+    assert_eq!(report.repaired_tiles.len(), 1);
+    assert!(WorldConnectivity::compute(&world).is_connected(Position::new(0, 0), Position::new(5, 0)));
+}
+
+#[test]
+fn test_validator_follows_movement_model() {
+    let cutting_corners = MovementModel::new(Connectivity::Eight, CornerCutting::Allow);
+
+    // The dungeon only touches the farm land at a corner between two lakes
+    let (mut world, definition) = parse_world("name = \"corner\"\n---\nvfl\nlld\n").unwrap();
+    assert_eq!(find_unreachable(&world, &definition).len(), 1);
+    world.set_movement(cutting_corners);
+    assert!(WorldConnectivity::compute(&world).is_connected(Position::new(0, 0), Position::new(2, 1)));
+    let report = validate_world(&mut world, &definition, ValidationMode::Reject).unwrap();
+    assert!(report.is_valid());
+    assert!(report.repaired_tiles.is_empty());
+
+    // Cutting corners, a diagonal road is enough
+    let contents = "name = \"lake\"\n---\nvll\nlll\nlld\n";
+    let (mut world, definition) = parse_world(contents).unwrap();
+    let report = validate_world(&mut world, &definition, ValidationMode::Repair).unwrap();
+    assert_eq!(report.repaired_tiles.len(), 3);
+    let (mut world, definition) = parse_world(contents).unwrap();
+    world.set_movement(cutting_corners);
+    let report = validate_world(&mut world, &definition, ValidationMode::Repair).unwrap();
+    assert!(report.is_valid());
+    assert_eq!(report.repaired_tiles, vec![Position::new(1, 1)]);
+}
+
+#[test]
+fn test_tile_grid_ragged_rows_and_copy_on_write() {
+    let mut world = GameWorld::create_world(vec!["vff".to_string(), "f".to_string(), "ffm".to_string()]);
+
+    // Short rows leave missing cells, lookups outside the grid find nothing
+    let tiles = world.get_tiles();
+    assert_eq!((tiles.get_width(), tiles.get_height()), (3, 3));
+    assert_eq!(tiles.len(), 7);
+    assert!(world.get_tile(1, 1).is_none());
+    assert!(world.get_tile_mut(2, 1).is_none());
+    assert_eq!(world.get_tile_type(2, 2), Some(TileType::Mountain));
+    for (x, y) in [(-1, 0), (0, -1), (3, 0), (0, 3)] {
+        assert!(!world.is_valid_position(x, y));
+        assert_eq!(world.get_tile_type(x, y), None);
+    }
+    let positions: Vec<Position> = world.get_tiles().positions().collect();
+    assert_eq!(positions[3], Position::new(0, 1));
+    assert_eq!(positions[4], Position::new(0, 2));
+
+    // A copy shares the tiles and cached fields until it changes a tile
+    let start = Position::new(2, 0);
+    assert_eq!(world.find_closest_tiletype(start, TileType::Village), Some(Position::new(0, 0)));
+    let mut copy = world.copy();
+    assert_eq!(copy.get_path_service().cached_fields(), 1);
+    copy.set_tile_type(2, 0, TileType::Village);
+
+    assert_eq!(copy.get_tile_type(2, 0), Some(TileType::Village));
+    assert_eq!(copy.find_closest_tiletype(start, TileType::Village), Some(start));
+    assert_eq!(world.get_tile_type(2, 0), Some(TileType::Forest));
+    assert_eq!(world.get_path_service().cached_fields(), 1);
+    assert_eq!(world.find_closest_tiletype(start, TileType::Village), Some(Position::new(0, 0)));
+}
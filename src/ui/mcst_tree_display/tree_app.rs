@@ -2,9 +2,8 @@ use bevy::ecs::system::ResMut;
 use bevy_egui::EguiContexts;
 use eframe::egui;
 use egui::Vec2;
-use std::sync::{Arc, Mutex};
 use crate::system::mcst_tree::mcst_tree::MCTSTree;
-use crate::system::mcst_tree::mcst_node::Node;
+use crate::system::mcst_tree::mcst_node::NodeId;
 
 use super::mcst_tree_display::DisplayTreeWindowState;
 
@@ -42,7 +41,9 @@ struct TreeApp {
 impl eframe::App for TreeApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |ui| {
-            if let Some(root) = &self.tree.root {
+            if self.tree.has_root() {
+                let root = self.tree.get_root();
+
                 // Allocate the entire window's space for rendering
                 let available_rect = ui.allocate_rect(
                     egui::Rect::from_min_size(ui.min_rect().min, ui.available_size()),
@@ -99,6 +100,7 @@ impl eframe::App for TreeApp {
                 let center = available_rect.rect.center();
                 render_node(
                     painter,
+                    &self.tree,
                     root,
                     center.x + self.camera.offset.x * self.camera.zoom,
                     center.y + self.camera.offset.y * self.camera.zoom,
@@ -123,7 +125,9 @@ pub fn display_tree_window_system(
             .open(&mut is_open) // Allow closing the window
             .show(egui_contexts.ctx_mut(), |ui| {
                 if let Some(tree) = &tree {
-                    if let Some(root) = &tree.root {
+                    if tree.has_root() {
+                        let root = tree.get_root();
+
                         // Allocate the entire window's space for rendering
                         let available_rect = ui.allocate_rect(
                             egui::Rect::from_min_size(ui.min_rect().min, ui.available_size()),
@@ -171,6 +175,7 @@ pub fn display_tree_window_system(
                         let center = available_rect.rect.center();
                         render_node(
                             painter,
+                            tree,
                             root,
                             center.x + window_state.camera.offset.x * window_state.camera.zoom,
                             center.y + window_state.camera.offset.y * window_state.camera.zoom,
@@ -191,12 +196,13 @@ pub fn display_tree_window_system(
 
 fn render_node(
     painter: &egui::Painter,
-    node: &Arc<Mutex<Node>>,
+    tree: &MCTSTree,
+    node_id: NodeId,
     x: f32,
     y: f32,
     x_spacing: f32,
 ) {
-    let node_lock = node.lock().unwrap();
+    let node = tree.get_node(node_id);
 
     // Draw the node as a circle
     painter.circle_filled(
@@ -207,14 +213,14 @@ fn render_node(
     painter.text(
         egui::pos2(x, y),
         egui::Align2::CENTER_CENTER,
-        format!("{:?}", node_lock.get_node_type().to_string()),
+        format!("{:?}", node.get_node_type().to_string()),
         egui::FontId::default(),
         egui::Color32::BLACK,
     );
 
     // Draw edges and child nodes
-    let num_children = node_lock.children.len();
-    for (i, child) in node_lock.children.iter().enumerate() {
+    let num_children = node.children.len();
+    for (i, child) in node.children.iter().enumerate() {
         let child_x = x - x_spacing / 2.0 + i as f32 * (x_spacing / num_children as f32);
         let child_y = y + 100.0;
 
@@ -225,6 +231,6 @@ fn render_node(
         );
 
         // Recursively render the child node
-        render_node(painter, child, child_x, child_y, x_spacing / 2.0);
+        render_node(painter, tree, *child, child_x, child_y, x_spacing / 2.0);
     }
 }
\ No newline at end of file
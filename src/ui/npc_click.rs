@@ -131,13 +131,14 @@ pub fn update_selected_npc_text(
                     NPCType::Agent => {
                         if let Ok(agent) = agent_query.get(selected_entity) {
                             format!(
-                                "NPC Type: Agent\nPosition: {:?}\nEnergy: {}/{}\nID: {}\nStatus: {:?}\nAction: {:?}",
+                                "NPC Type: Agent\nPosition: {:?}\nEnergy: {}/{}\nID: {}\nStatus: {:?}\nAction: {:?}\nInventory: {}",
                                 npc_base.position,
                                 npc_base.energy,
                                 npc_base.max_energy,
                                 agent.get_id(),
                                 agent.get_status(),
                                 agent.get_action(),
+                                agent.get_inventory(),
                             )
                         } else {
                             "Error: Unable to retrieve Agent information.".to_string()
@@ -5,7 +5,7 @@ use crate::gameworld::world::GameWorld;
 use crate::npcs::npc_components::npc_base::NPCBase;
 use crate::npcs::npc_components::npc_type::NPCType;
 use crate::npcs::player::Player;
-use crate::{EndTurn, SelectedNPC};
+use crate::{SelectedNPC, TurnState};
 
 pub fn setup_ui(mut commands: Commands) {
     // Collapsed state indicator (>>)
@@ -268,7 +268,7 @@ pub fn toggle_panel_system(
 
 pub fn end_turn_button_system(
     mut commands: Commands,
-    mut end_turn: ResMut<EndTurn>,
+    mut next_state: ResMut<NextState<TurnState>>,
     mut interaction_query: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<EndTurnButton>)>,
     player_query: Query<&NPCBase, With<Player>>,
     monsters_query: Query<&NPCBase>,
@@ -280,8 +280,8 @@ pub fn end_turn_button_system(
     for (interaction, mut background_color) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Pressed => {
-                // Set the EndTurn resource to true
-                end_turn.0 = true;
+                // Hand the turn over to the agents
+                next_state.set(TurnState::MCTSPhase);
                 println!("End Turn triggered!");
 
                 // Set the flag to true